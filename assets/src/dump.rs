@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+use sundile_graphics::BlobSummary;
+use thiserror::Error;
+
+use crate::*;
+
+// ---
+// Human-readable dumps of baked assets, for debugging.
+// ---
+
+/// A raw asset map in human-readable form, keyed by asset name. See [Serializer::with_dump].
+pub type DumpAssetMap = BTreeMap<String, serde_json::Value>;
+/// Every raw asset map in a dump, keyed by asset type.
+pub type DumpAssetTypeMap = BTreeMap<String, DumpAssetMap>;
+
+/// Error type for reading dumps.
+#[derive(Error, Debug)]
+pub enum DumpError {
+    #[error("Unable to parse dump: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("Unable to read `{name}` from dump: {source}")]
+    Asset {
+        name: String,
+        source: serde_json::Error,
+    },
+    #[error("Unable to decode `{name}` from dump: {source}")]
+    Blob {
+        name: String,
+        source: base64::DecodeError,
+    },
+}
+
+/// Dumps byte buffers as [BlobSummary]s. Used for the serialized assets of mappers which don't implement [RawAssetMapper::dump].
+pub fn blob_dump<'a, I>(blobs: I) -> DumpAssetMap
+where
+    I: IntoIterator<Item = (&'a String, &'a Vec<u8>)>,
+{
+    blobs
+        .into_iter()
+        .map(|(name, bytes)| {
+            let value = serde_json::to_value(BlobSummary::new(bytes)).expect("Unable to dump blob");
+            (name.to_owned(), value)
+        })
+        .collect()
+}
+
+/// Reads back a dump written by [blob_dump].
+pub fn load_blob_dump(dump: DumpAssetMap) -> Result<BincodeAssetMap, DumpError> {
+    dump.into_iter()
+        .map(|(name, value)| {
+            let blob = match serde_json::from_value::<BlobSummary>(value) {
+                Ok(blob) => blob,
+                Err(source) => return Err(DumpError::Asset { name, source }),
+            };
+            match blob.decode() {
+                Ok(bytes) => Ok((name, bytes)),
+                Err(source) => Err(DumpError::Blob { name, source }),
+            }
+        })
+        .collect()
+}
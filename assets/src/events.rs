@@ -0,0 +1,149 @@
+use crate::*;
+use std::any::*;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+// ---
+// Change notifications for assets stored in an [AssetTypeMap].
+// ---
+
+/// What happened to an asset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetEventKind {
+    /// An asset was added under a name that was previously empty.
+    Inserted,
+    /// An asset was added under a name that already held an asset.
+    Replaced,
+    /// An asset was taken, unloaded or evicted.
+    Removed,
+}
+
+/// Sent to subscribers whenever an asset in an [AssetTypeMap] is inserted, replaced or removed.
+/// Mutating an asset in place (e.g. through [AssetTypeMap::try_get_asset_mut]) does not send events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetEvent {
+    pub kind: AssetEventKind,
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+    pub name: String,
+}
+impl AssetEvent {
+    /// Returns true if the event concerns an asset of type T.
+    pub fn is<T>(&self) -> bool
+    where
+        T: 'static,
+    {
+        self.type_id == TypeId::of::<T>()
+    }
+}
+
+/// A subscription registered with [AssetTypeMap::subscribe] or [AssetTypeMap::subscribe_to].
+#[derive(Debug)]
+pub(crate) struct AssetSubscriber {
+    /// Only events for this type are sent, or all events if None.
+    filter: Option<TypeId>,
+    sender: Sender<AssetEvent>,
+}
+
+impl AssetTypeMap {
+    /// Subscribes to every asset event. Events are queued until read from the returned receiver.
+    /// Dropping the receiver ends the subscription.
+    /// ```rust
+    /// use sundile_assets::*;
+    /// let mut assets = AssetTypeMap::new();
+    /// let events = assets.subscribe();
+    /// assets.try_insert_asset("answer", 42).unwrap();
+    /// let event = events.try_recv().unwrap();
+    /// assert_eq!(event.kind, AssetEventKind::Inserted);
+    /// assert_eq!(event.name, "answer");
+    /// ```
+    pub fn subscribe(&mut self) -> Receiver<AssetEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(AssetSubscriber {
+            filter: None,
+            sender,
+        });
+        receiver
+    }
+
+    /// Subscribes to events for assets of type T only.
+    pub fn subscribe_to<T>(&mut self) -> Receiver<AssetEvent>
+    where
+        T: 'static,
+    {
+        let (sender, receiver) = channel();
+        self.subscribers.push(AssetSubscriber {
+            filter: Some(TypeId::of::<T>()),
+            sender,
+        });
+        receiver
+    }
+
+    /// Sends an event to every interested subscriber, dropping subscribers whose receivers are gone.
+    pub(crate) fn emit(
+        &mut self,
+        kind: AssetEventKind,
+        type_id: TypeId,
+        type_name: &'static str,
+        name: &str,
+    ) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        let event = AssetEvent {
+            kind,
+            type_id,
+            type_name,
+            name: name.to_owned(),
+        };
+        self.subscribers.retain(|subscriber| match subscriber.filter {
+            Some(filter) if filter != type_id => true,
+            _ => subscriber.sender.send(event.clone()).is_ok(),
+        });
+    }
+
+    /// Sends one event per name.
+    pub(crate) fn emit_all<'a, I>(
+        &mut self,
+        kind: AssetEventKind,
+        type_id: TypeId,
+        type_name: &'static str,
+        names: I,
+    ) where
+        I: IntoIterator<Item = &'a String>,
+    {
+        for name in names {
+            self.emit(kind, type_id, type_name, name);
+        }
+    }
+
+    /// Sends an event for an asset of type T.
+    pub(crate) fn emit_for<T>(&mut self, kind: AssetEventKind, name: &str)
+    where
+        T: 'static,
+    {
+        self.emit(kind, TypeId::of::<T>(), type_name::<T>(), name);
+    }
+
+    /// Lists the events caused by `new` replacing `old`: Inserted or Replaced for each new asset, Removed for each asset that disappears.
+    pub(crate) fn map_change_events(
+        old: Option<&AssetMap>,
+        new: &AssetMap,
+    ) -> Vec<(AssetEventKind, String)> {
+        let mut events = vec![];
+        for name in new.map.keys() {
+            let kind = match old.map(|old| old.map.contains_key(name)) {
+                Some(true) => AssetEventKind::Replaced,
+                _ => AssetEventKind::Inserted,
+            };
+            events.push((kind, name.to_owned()));
+        }
+        if let Some(old) = old {
+            for name in old.map.keys() {
+                if !new.map.contains_key(name) {
+                    events.push((AssetEventKind::Removed, name.to_owned()));
+                }
+            }
+        }
+        events
+    }
+}
@@ -0,0 +1,83 @@
+use crate::*;
+use log::warn;
+use std::any::*;
+use std::rc::Rc;
+pub use sundile_graphics::FallbackMode;
+
+// ---
+// Placeholder assets returned in place of assets which are missing or fail to build.
+// ---
+
+impl AssetTypeMap {
+    /// Sets what happens when an asset is missing or fails to build. See [FallbackMode].
+    pub fn set_fallback_mode(&mut self, mode: FallbackMode) {
+        self.fallback_mode = mode;
+    }
+
+    /// Returns the current fallback mode.
+    pub fn fallback_mode(&self) -> FallbackMode {
+        self.fallback_mode
+    }
+
+    /// Registers the placeholder returned by [AssetTypeMap::try_get_asset_or_fallback] for assets of type T, replacing any previous one.
+    /// Fallbacks are not assets: they cannot be looked up by name and are never unloaded.
+    pub fn set_fallback<T>(&mut self, asset: T)
+    where
+        T: 'static,
+    {
+        self.fallbacks
+            .insert(TypeId::of::<T>(), AssetStorage::new(asset));
+    }
+
+    /// Returns the placeholder registered for assets of type T, if any.
+    pub fn try_get_fallback<T>(&self) -> Option<Rc<T>>
+    where
+        T: 'static,
+    {
+        self.fallbacks
+            .get(&TypeId::of::<T>())
+            .and_then(|storage| storage.try_get().ok())
+    }
+
+    /// Like [AssetTypeMap::try_get_asset], but returns the fallback for T if the asset cannot be found.
+    /// A warning is logged the first time each missing asset is requested.
+    /// In [FallbackMode::Strict], or if no fallback is registered for T, the original error is returned.
+    /// ```rust
+    /// use sundile_assets::*;
+    /// let mut assets = AssetTypeMap::new();
+    /// assets.set_fallback_mode(FallbackMode::Placeholder);
+    /// assets.set_fallback("placeholder");
+    /// assert_eq!(*assets.try_get_asset_or_fallback::<&str>("missing").unwrap(), "placeholder");
+    /// assets.set_fallback_mode(FallbackMode::Strict);
+    /// assert!(assets.try_get_asset_or_fallback::<&str>("missing").is_err());
+    /// ```
+    pub fn try_get_asset_or_fallback<T>(&self, name: &str) -> Result<Rc<T>, AssetError>
+    where
+        T: 'static,
+    {
+        let error = match self.try_get_asset::<T>(name) {
+            Ok(asset) => return Ok(asset),
+            Err(error) => error,
+        };
+        if !self.fallback_mode.is_placeholder() {
+            return Err(error);
+        }
+        let fallback = match self.try_get_fallback::<T>() {
+            Some(fallback) => fallback,
+            None => return Err(error),
+        };
+        if self
+            .warned_fallbacks
+            .borrow_mut()
+            .insert((TypeId::of::<T>(), name.to_owned()))
+        {
+            warn!(
+                "{} `{}` could not be loaded; using a placeholder. {}",
+                type_name::<T>(),
+                name,
+                error
+            );
+        }
+        Ok(fallback)
+    }
+}
@@ -1,8 +1,14 @@
-use sundile_graphics::HeadlessRenderTarget;
-use sundile_graphics::RenderTarget;
+use sundile_graphics::DrawTarget;
 
+use crate::{
+    AssetEventKind, AssetOrigin, AssetSizeFn, AssetSubscriber, BakeProfile, FallbackMode,
+    MemoryBudget, Schema, ShadowedAsset,
+};
+#[cfg(feature = "dump")]
+use crate::{DumpAssetMap, DumpError};
 use std::any::*;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::*;
 use std::rc::Rc;
 use std::result::Result::{Err, Ok};
@@ -20,10 +26,16 @@ pub enum AssetError {
     InvalidType,
     #[error("Could not convert to owned type. This probably means there are existing references to this value!")]
     InvalidTake,
+    #[error("Could not borrow mutably. This probably means there are existing references to this value!")]
+    InvalidBorrow,
     #[error("No asset map with the specified type.")]
     AssetMapNotFound,
     #[error("No asset found with given name.")]
     AssetNotFound(String),
+    #[error("No asset pack mounted with name `{0}`.")]
+    PackNotFound(String),
+    #[error("An asset pack with name `{0}` is already mounted.")]
+    PackAlreadyMounted(String),
     #[error("IO Error `{0}`")]
     Io(#[from] std::io::Error),
 }
@@ -38,6 +50,10 @@ impl PartialEq for AssetError {
 pub struct AssetStorage {
     // TODO: Convert to Arc<Mutex<dyn Any>> and reimplement, cascading upwards.
     value: Rc<dyn Any>,
+    /// Value of the owning [AssetTypeMap]'s clock when this asset was last accessed.
+    pub(crate) last_used: Cell<u64>,
+    /// Size in bytes when the asset was stored, as counted in the owning [AssetTypeMap]'s memory usage.
+    pub(crate) size: Cell<u64>,
 }
 impl std::ops::Deref for AssetStorage {
     type Target = Rc<dyn Any>;
@@ -53,6 +69,8 @@ impl AssetStorage {
     {
         Self {
             value: Rc::new(value),
+            last_used: Cell::new(0),
+            size: Cell::new(0),
         }
     }
     /// Constructs a new asset from a heap-allocated value.
@@ -60,7 +78,11 @@ impl AssetStorage {
     where
         T: 'static,
     {
-        Self { value }
+        Self {
+            value,
+            last_used: Cell::new(0),
+            size: Cell::new(0),
+        }
     }
     /// Tries to return a reference counted pointer to the underlying asset.
     /// This function fails if the asset is not of type T.
@@ -83,10 +105,42 @@ impl AssetStorage {
         match self.value.downcast::<T>() {
             Ok(cast_value) => match Rc::try_unwrap(cast_value) {
                 Ok(inner) => Ok(inner),
-                Err(value) => Err((AssetError::InvalidTake, Self { value })),
+                Err(value) => Err((
+                    AssetError::InvalidTake,
+                    Self {
+                        value,
+                        last_used: self.last_used,
+                        size: self.size,
+                    },
+                )),
             },
-            Err(value) => Err((AssetError::InvalidType, Self { value })),
+            Err(value) => Err((
+                AssetError::InvalidType,
+                Self {
+                    value,
+                    last_used: self.last_used,
+                    size: self.size,
+                },
+            )),
+        }
+    }
+    /// Tries to return a mutable reference to the underlying asset.
+    /// This function fails if the asset is not of type T, or if there are other extant references to that asset.
+    pub fn try_get_mut<T>(&mut self) -> Result<&mut T, AssetError>
+    where
+        T: 'static,
+    {
+        if !self.value.is::<T>() {
+            return Err(AssetError::InvalidType);
         }
+        match Rc::get_mut(&mut self.value) {
+            Some(value) => Ok(value.downcast_mut::<T>().unwrap()),
+            None => Err(AssetError::InvalidBorrow),
+        }
+    }
+    /// Returns true if anything outside of the asset maps holds a reference to this asset.
+    pub fn is_referenced(&self) -> bool {
+        Rc::strong_count(&self.value) > 1
     }
 }
 
@@ -95,7 +149,9 @@ impl AssetStorage {
 #[derive(Debug)]
 pub struct AssetMap {
     pub type_id: TypeId,
-    map: HashMap<String, AssetStorage>,
+    pub type_name: &'static str,
+    pub(crate) map: HashMap<String, AssetStorage>,
+    pub(crate) pinned: HashSet<String>,
 }
 impl AssetMap {
     /// Creates a new AssetMap from the specified type.
@@ -105,14 +161,18 @@ impl AssetMap {
     {
         Self {
             type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
             map: HashMap::new(),
+            pinned: HashSet::new(),
         }
     }
     /// Creates a new AssetMap from the specified TypeId
     pub fn from_type_id(type_id: TypeId) -> Self {
         Self {
             type_id,
+            type_name: "unknown",
             map: HashMap::new(),
+            pinned: HashSet::new(),
         }
     }
     /// Creates a new AssetMap, automatically inserting the asset.
@@ -123,7 +183,9 @@ impl AssetMap {
     {
         Self {
             type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
             map: HashMap::from_iter([(name.into(), AssetStorage::new(asset))]),
+            pinned: HashSet::new(),
         }
     }
     /// Creates a new AssetMap from a HashMap.
@@ -138,7 +200,9 @@ impl AssetMap {
 
         Self {
             type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
             map,
+            pinned: HashSet::new(),
         }
     }
 
@@ -146,6 +210,7 @@ impl AssetMap {
     pub fn try_extend(&mut self, other: AssetMap) -> Result<&Self, AssetError> {
         if other.type_id == self.type_id {
             self.map.extend(other.map);
+            self.pinned.extend(other.pinned);
             return Ok(self);
         }
         return Err(AssetError::InvalidType);
@@ -207,6 +272,19 @@ impl AssetMap {
             None => Err(AssetError::AssetNotFound(name)),
         }
     }
+    /// Tries to get a mutable reference to an asset.
+    /// This function fails if it cannot find the asset, if it cannot convert the asset to the specified type, or if there are other extant references to it.
+    pub fn try_get_mut<S, T>(&mut self, name: S) -> Result<&mut T, AssetError>
+    where
+        S: Into<String>,
+        T: 'static,
+    {
+        let name = name.into();
+        match self.map.get_mut(&name) {
+            Some(storage) => storage.try_get_mut(),
+            None => Err(AssetError::AssetNotFound(name)),
+        }
+    }
     /// Tries to take an asset. If the asset is found, it is removed from the map.
     /// This function fails if it cannot find the asset with the given name, or if it cannot convert the asset to the specified type.
     /// This function removes assets from the heap and will fail if there are multiple extant references to that asset.
@@ -236,7 +314,8 @@ impl AssetMap {
     {
         let mut res = HashMap::new();
         let mut errdata = None;
-        for (name, storage) in self.map {
+        let mut iter = self.map.into_iter();
+        for (name, storage) in iter.by_ref() {
             match storage.try_take::<T>() {
                 Ok(asset) => {
                     res.insert(name, asset);
@@ -245,14 +324,19 @@ impl AssetMap {
                     errdata = Some((
                         err,
                         Self {
-                            type_id: storage.type_id(),
+                            type_id: self.type_id,
+                            type_name: self.type_name,
                             map: HashMap::from_iter([(name, storage)]),
+                            pinned: self.pinned.clone(),
                         },
                     ));
                     break;
                 }
             }
         }
+        if let Some((_, asset_map)) = errdata.as_mut() {
+            asset_map.map.extend(iter);
+        }
         if errdata.is_some() {
             let (err, mut asset_map) = errdata.unwrap();
             for (name, asset) in res {
@@ -262,6 +346,18 @@ impl AssetMap {
         }
         Ok(res)
     }
+    /// Tries to convert to a hashmap containing mutable references to values of the specified type.
+    /// Fails if any member cannot be converted or is referenced elsewhere.
+    pub fn try_as_mut<T>(&mut self) -> Result<HashMap<String, &mut T>, AssetError>
+    where
+        T: 'static,
+    {
+        let mut res = HashMap::new();
+        for (name, storage) in self.map.iter_mut() {
+            res.insert(name.to_owned(), storage.try_get_mut::<T>()?);
+        }
+        Ok(res)
+    }
     /// Tries to convert to a hashmap containing references to values of the specified type.
     pub fn try_as<T>(&self) -> Result<HashMap<String, Rc<T>>, AssetError>
     where
@@ -299,43 +395,111 @@ impl AssetMap {
 /// assert_eq!(TypeId::of::<Data1>(), TypeId::of::<Data2>());
 /// assert_ne!(TypeId::of::<Data1>(), TypeId::of::<Data3>());
 /// ```
-/// This system does not implement internal mutability. Instead, use the following pattern:
+/// This system does not implement internal mutability. Instead, borrow assets mutably while nothing else references them:
 /// ```rust
 /// use sundile_assets::AssetTypeMap;
-/// fn f<T>(assets: &mut AssetTypeMap) where T: 'static {
-///     let mut taken = assets.try_take_asset::<T>("my_asset");
-///     // do things here
-///     assets.try_insert_asset("my_asset", taken);
+/// fn f(assets: &mut AssetTypeMap) {
+///     if let Ok(value) = assets.try_get_asset_mut::<i32>("my_asset") {
+///         *value += 1;
+///     }
 /// }
 /// ```
-/// TODO: Is this pattern efficient? Perhaps do benchmarks to determine if the size/efficiency tradeoff is worthwhile.
+/// Subscribe with [AssetTypeMap::subscribe] to be notified when assets are inserted, replaced or removed.
 #[derive(Debug)]
 pub struct AssetTypeMap {
     // size_of::<TypeId> == 8 vs size_of::<String> == 24 vs size_of::<&str> == 16
     // Small memory redundancy for O(1) lookup time.
-    map: HashMap<TypeId, AssetMap>,
+    pub(crate) map: HashMap<TypeId, AssetMap>,
+    /// Size functions used for memory accounting. See [AssetTypeMap::register_asset_size].
+    pub(crate) sizers: HashMap<TypeId, AssetSizeFn>,
+    /// Incremented on every access; used to find the least recently used assets.
+    pub(crate) clock: Cell<u64>,
+    /// Combined size of every stored asset, including overridden copies. See [AssetTypeMap::memory_usage].
+    pub(crate) usage: Cell<u64>,
+    /// Pins of maps removed with [AssetTypeMap::try_take_asset_map], restored when the map is reinserted.
+    pub(crate) taken_pins: HashMap<TypeId, HashSet<String>>,
+    pub(crate) budget: Option<MemoryBudget>,
+    /// Whether a warning has been logged since the budget was last exceeded.
+    pub(crate) over_budget: bool,
+    pub(crate) subscribers: Vec<AssetSubscriber>,
+    /// Packs mounted with [AssetTypeMap::mount].
+    pub(crate) mounted: Vec<AssetOrigin>,
+    pub(crate) mount_count: u64,
+    /// Pack that provided each asset. Assets inserted at runtime have no entry.
+    pub(crate) origins: HashMap<(TypeId, String), AssetOrigin>,
+    /// Assets overridden by a higher-priority pack.
+    pub(crate) shadowed: Vec<ShadowedAsset>,
+    pub(crate) fallback_mode: FallbackMode,
+    /// Placeholders registered with [AssetTypeMap::set_fallback].
+    pub(crate) fallbacks: HashMap<TypeId, AssetStorage>,
+    /// Missing assets which have already been warned about.
+    pub(crate) warned_fallbacks: RefCell<HashSet<(TypeId, String)>>,
 }
 impl AssetTypeMap {
     /// Creates an empty [AssetTypeMap].
     pub fn new() -> Self {
         Self {
             map: HashMap::new(),
+            sizers: crate::memory::default_sizers(),
+            clock: Cell::new(0),
+            usage: Cell::new(0),
+            taken_pins: HashMap::new(),
+            budget: None,
+            over_budget: false,
+            subscribers: vec![],
+            mounted: vec![],
+            mount_count: 0,
+            origins: HashMap::new(),
+            shadowed: vec![],
+            fallback_mode: FallbackMode::default(),
+            fallbacks: HashMap::new(),
+            warned_fallbacks: RefCell::new(HashSet::new()),
         }
     }
 
-    /// Combines this asset typemap with another in-place.
+    /// Combines this asset typemap with another in-place. Assets in `other` replace assets with the same name, with a warning.
+    /// To layer several sets of assets with explicit priorities, use [AssetTypeMap::mount] instead.
     pub fn try_combine(&mut self, other: AssetTypeMap) -> Result<&Self, AssetError> {
+        self.warn_conflicts(&other);
         for (asset_type, new_map) in other.map.into_iter() {
+            let (type_id, type_name) = (new_map.type_id, new_map.type_name);
+            let mut events = vec![];
             match self.map.get_mut(&asset_type) {
-                Some(old_map) => match old_map.try_extend(new_map) {
-                    Ok(_) => {}
-                    Err(e) => return Err(e),
-                },
+                Some(old_map) => {
+                    for name in new_map.map.keys() {
+                        let kind = match old_map.map.contains_key(name) {
+                            true => AssetEventKind::Replaced,
+                            false => AssetEventKind::Inserted,
+                        };
+                        events.push((kind, name.to_owned()));
+                        if self
+                            .origins
+                            .remove(&(asset_type, name.to_owned()))
+                            .is_some()
+                        {
+                            self.shadowed.retain(|shadowed| {
+                                shadowed.type_id != asset_type || &shadowed.name != name
+                            });
+                        }
+                    }
+                    match old_map.try_extend(new_map) {
+                        Ok(_) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
                 None => {
+                    for name in new_map.map.keys() {
+                        events.push((AssetEventKind::Inserted, name.to_owned()));
+                    }
                     self.map.insert(asset_type, new_map);
                 }
             }
+            for (kind, name) in events {
+                self.emit(kind, type_id, type_name, &name);
+            }
         }
+        self.track_all();
+        self.enforce_memory_budget();
         Ok(self)
     }
 
@@ -352,13 +516,28 @@ impl AssetTypeMap {
         T: 'static,
     {
         let ty = TypeId::of::<T>();
-        match self.map.get_mut(&ty) {
+        let replaced_size = self.stored_size(ty, name);
+        let result = match self.map.get_mut(&ty) {
             Some(map) => map.try_insert(name, asset),
             None => {
                 self.map.insert(ty, AssetMap::from_asset(name, asset));
                 Ok(None)
             }
+        };
+        if result.is_ok() || matches!(result, Err((_, Some(_)))) {
+            self.forget_origin(ty, name);
+            self.untrack(replaced_size);
+            if let Some(storage) = self.map.get(&ty).and_then(|map| map.map.get(name)) {
+                self.track(&ty, storage);
+            }
+        }
+        match &result {
+            Ok(None) => self.emit_for::<T>(AssetEventKind::Inserted, name),
+            Ok(Some(_)) | Err((_, Some(_))) => self.emit_for::<T>(AssetEventKind::Replaced, name),
+            Err((_, None)) => {}
         }
+        self.enforce_memory_budget();
+        result
     }
 
     /// Same as [try_insert_asset], but assumes the value is already on the heap.
@@ -371,13 +550,30 @@ impl AssetTypeMap {
         T: 'static,
     {
         let ty = TypeId::of::<T>();
-        match self.map.get_mut(&ty) {
+        let replaced_size = self.stored_size(ty, name);
+        let result = match self.map.get_mut(&ty) {
             Some(map) => map.try_insert_ref(name, asset),
             None => {
-                self.map.insert(ty, AssetMap::from_asset(name, asset));
+                let mut map = AssetMap::new::<T>();
+                map.try_insert_ref(name, asset).ok();
+                self.map.insert(ty, map);
                 Ok(None)
             }
+        };
+        if result.is_ok() || matches!(result, Err((_, Some(_)))) {
+            self.forget_origin(ty, name);
+            self.untrack(replaced_size);
+            if let Some(storage) = self.map.get(&ty).and_then(|map| map.map.get(name)) {
+                self.track(&ty, storage);
+            }
+        }
+        match &result {
+            Ok(None) => self.emit_for::<T>(AssetEventKind::Inserted, name),
+            Ok(Some(_)) | Err((_, Some(_))) => self.emit_for::<T>(AssetEventKind::Replaced, name),
+            Err((_, None)) => {}
         }
+        self.enforce_memory_budget();
+        result
     }
 
     /// Tries to get an asset reference. Will return an error if it cannot find the type or asset name, and if it cannot be converted to the specified type.
@@ -387,27 +583,75 @@ impl AssetTypeMap {
     {
         let ty = TypeId::of::<T>();
         match self.map.get(&ty) {
-            Some(map) => map.try_get(name),
+            Some(map) => {
+                if let Some(storage) = map.map.get(name) {
+                    self.touch(storage);
+                }
+                map.try_get(name)
+            }
             None => Err(AssetError::AssetMapNotFound),
         }
     }
 
-    /// Tries to take an asset as an owned value, removing it from the map in the process.
-    /// This function removes assets from the heap and will fail if there are multiple extant references to that asset.
-    pub fn try_take_asset<'a, T>(&mut self, name: &'a str) -> Result<T, AssetError>
+    /// Tries to get a mutable reference to an asset. Will return an error if it cannot find the type or asset name, if it cannot be converted to the specified type,
+    /// or if the asset is referenced elsewhere.
+    pub fn try_get_asset_mut<'a, T>(&mut self, name: &'a str) -> Result<&mut T, AssetError>
     where
         T: 'static,
     {
         let ty = TypeId::of::<T>();
         match self.map.get_mut(&ty) {
-            Some(val) => val.try_take(name),
+            Some(map) => {
+                if let Some(storage) = map.map.get(name) {
+                    let now = self.clock.get() + 1;
+                    self.clock.set(now);
+                    storage.last_used.set(now);
+                }
+                map.try_get_mut(name)
+            }
             None => Err(AssetError::AssetMapNotFound),
         }
     }
 
+    /// Tries to take an asset as an owned value, removing it from the map in the process.
+    /// This function removes assets from the heap and will fail if there are multiple extant references to that asset.
+    pub fn try_take_asset<'a, T>(&mut self, name: &'a str) -> Result<T, AssetError>
+    where
+        T: 'static,
+    {
+        let ty = TypeId::of::<T>();
+        let size = self.stored_size(ty, name);
+        let (asset, type_name) = match self.map.get_mut(&ty) {
+            Some(val) => (val.try_take(name)?, val.type_name),
+            None => return Err(AssetError::AssetMapNotFound),
+        };
+        self.untrack(size);
+        self.forget_origin(ty, name);
+        self.emit(AssetEventKind::Removed, ty, type_name, name);
+        Ok(asset)
+    }
+
     /// Directly inserts an [AssetMap].
-    pub fn insert_asset_map(&mut self, map: AssetMap) -> Option<AssetMap> {
-        self.map.insert(map.type_id, map)
+    /// Pins set on a previous map of the same type are kept.
+    pub fn insert_asset_map(&mut self, mut map: AssetMap) -> Option<AssetMap> {
+        if let Some(old_map) = self.map.get(&map.type_id) {
+            map.pinned.extend(old_map.pinned.iter().cloned());
+        }
+        if let Some(pins) = self.taken_pins.remove(&map.type_id) {
+            map.pinned.extend(pins);
+        }
+        let old_map = self.map.remove(&map.type_id);
+        let events = AssetTypeMap::map_change_events(old_map.as_ref(), &map);
+        let (type_id, type_name) = (map.type_id, map.type_name);
+        self.origins.retain(|(ty, _), _| *ty != type_id);
+        self.shadowed.retain(|shadowed| shadowed.type_id != type_id);
+        self.map.insert(type_id, map);
+        for (kind, name) in events {
+            self.emit(kind, type_id, type_name, &name);
+        }
+        self.track_all();
+        self.enforce_memory_budget();
+        old_map
     }
 
     /// Takes a HashMap of specific assets and inserts them into the table, converting them into the [Asset] storage type.
@@ -422,7 +666,7 @@ impl AssetTypeMap {
                 .try_insert(name, asset)
                 .expect("If you're seeing this, something went wrong.");
         }
-        self.map.insert(TypeId::of::<T>(), asset_map)
+        self.insert_asset_map(asset_map)
     }
 
     /// Tries to return a HashMap with type-converted asset references. Will return an error if it cannot find a map for the type, or if any member cannot be converted.
@@ -437,6 +681,19 @@ impl AssetTypeMap {
         }
     }
 
+    /// Tries to return a HashMap with mutable asset references. Will return an error if it cannot find a map for the type,
+    /// or if any member cannot be converted or is referenced elsewhere.
+    pub fn try_get_asset_map_mut<T>(&mut self) -> Result<HashMap<String, &mut T>, AssetError>
+    where
+        T: 'static,
+    {
+        let ty = TypeId::of::<T>();
+        match self.map.get_mut(&ty) {
+            Some(map) => map.try_as_mut(),
+            None => Err(AssetError::AssetMapNotFound),
+        }
+    }
+
     /// Returns an owned, type-converted HashMap by removing it from the AssetTypeMap.
     /// This function fails if it cannot find a map for the type, or if any member cannot be converted.
     /// On failure, the AssetMap is not removed.
@@ -446,16 +703,31 @@ impl AssetTypeMap {
         T: 'static,
     {
         let ty = TypeId::of::<T>();
-        match self.map.remove(&ty) {
-            Some(map) => match map.try_into() {
-                Ok(val) => Ok(val),
-                Err((err, map)) => {
+        let result = match self.map.remove(&ty) {
+            Some(map) => match (map.pinned.clone(), map.try_into()) {
+                (pinned, Ok(val)) => {
+                    if !pinned.is_empty() {
+                        self.taken_pins.insert(ty, pinned);
+                    }
+                    self.origins.retain(|(type_id, _), _| *type_id != ty);
+                    self.shadowed.retain(|shadowed| shadowed.type_id != ty);
+                    self.emit_all(
+                        AssetEventKind::Removed,
+                        ty,
+                        std::any::type_name::<T>(),
+                        val.keys(),
+                    );
+                    Ok(val)
+                }
+                (_, Err((err, map))) => {
                     self.map.insert(ty, map);
                     Err(err)
                 }
             },
-            None => Err(AssetError::AssetMapNotFound),
-        }
+            None => return Err(AssetError::AssetMapNotFound),
+        };
+        self.track_all();
+        result
     }
 
     /// Returns a list of asset names of the available type.
@@ -475,9 +747,10 @@ impl AssetTypeMap {
 // ---
 
 /// [AssetMap] to be used with bytecode data. Automatically derives serialization.
-pub type BincodeAssetMap = HashMap<String, Vec<u8>>;
+pub type BincodeAssetMap = BTreeMap<String, Vec<u8>>;
 /// [AssetTypeMap] to be used with bytecode data. Automatically derives serialization.
-pub type BincodeAssetTypeMap = HashMap<String, BincodeAssetMap>;
+/// Ordered maps are used so that serialized output does not depend on hashing.
+pub type BincodeAssetTypeMap = BTreeMap<String, BincodeAssetMap>;
 
 // ---
 // The below types deal with Assets as they are loaded from disk in the raw form.
@@ -497,6 +770,13 @@ where
 
 /// Type that converts a specified RawAsset type to a specified Asset type.
 pub trait RawAssetMapper {
+    /// Stable name of this asset type, used as its key in packs and by [crate::register_mapper]. Usually its directory under the asset directory.
+    fn type_tag(&self) -> &str;
+    /// File extensions this mapper loads from its directory, e.g. "png". Used by [LintRules] to tell assets apart from the files they use.
+    /// Empty by default.
+    fn extensions(&self) -> &[&str] {
+        &[]
+    }
     /// Loads all relevant files from disk.
     /// Tip: call RawAsset::load_from_disk internally.
     /// TODO: This should return a Result, so compilation could (optionally) continue.
@@ -507,33 +787,48 @@ pub trait RawAssetMapper {
     fn load_bin_map(&mut self, bin_map: BincodeAssetMap);
     /// Serializes self from raw asset data to bytecode
     fn to_bin_map(self: Box<Self>) -> BincodeAssetMap;
+    /// Applies the options of a [BakeProfile] which concern this asset type. Called by the [Serializer] before loading.
+    /// Does nothing by default.
+    fn apply_profile(&mut self, _profile: &BakeProfile) {}
+    /// Describes the raw assets in human-readable form, for [Serializer::with_dump]. Called before [RawAssetMapper::to_bin_map].
+    /// Returns None by default, in which case the serialized bytes are dumped with [crate::blob_dump].
+    #[cfg(feature = "dump")]
+    fn dump(&self) -> Option<DumpAssetMap> {
+        None
+    }
+    /// Loads raw assets from the form returned by [RawAssetMapper::dump].
+    /// By default, reads back the output of [crate::blob_dump].
+    #[cfg(feature = "dump")]
+    fn load_dump_map(&mut self, dump: DumpAssetMap) -> Result<(), DumpError> {
+        self.load_bin_map(crate::load_blob_dump(dump)?);
+        Ok(())
+    }
+    /// The version of the layout written by [RawAssetMapper::to_bin_map], and migrations from older layouts.
+    /// The [Deserializer] migrates older packs before calling [RawAssetMapper::load_bin_map]. Version 0 with no migrations by default.
+    fn schema(&self) -> Schema {
+        Schema::default()
+    }
 }
 
 /// Hashmap from string to RawAsset.
 pub type RawAssetMap<'a, AssetType> = HashMap<String, Box<dyn RawAsset<AssetType> + 'a>>;
 
-/// Builds assets from a RenderTarget.
+/// Builds assets from any [DrawTarget].
 /// Only intended to last for the duration of a deserialization call.
 pub struct AssetBuildTarget<'a> {
     pub device: &'a Device,
     pub queue: &'a Queue,
     pub texture_layout: &'a wgpu::BindGroupLayout,
+    /// What to do with assets which fail to build.
+    pub fallback_mode: FallbackMode,
 }
-impl<'a> From<&'a HeadlessRenderTarget> for AssetBuildTarget<'a> {
-    fn from(other: &'a HeadlessRenderTarget) -> Self {
-        AssetBuildTarget {
-            device: &other.device,
-            queue: &other.queue,
-            texture_layout: &other.texture_layout,
-        }
-    }
-}
-impl<'a> From<&'a RenderTarget> for AssetBuildTarget<'a> {
-    fn from(other: &'a RenderTarget) -> Self {
+impl<'a, T: DrawTarget> From<&'a T> for AssetBuildTarget<'a> {
+    fn from(other: &'a T) -> Self {
         AssetBuildTarget {
-            device: &other.device,
-            queue: &other.queue,
-            texture_layout: &other.texture_layout,
+            device: other.device(),
+            queue: other.queue(),
+            texture_layout: other.texture_layout(),
+            fallback_mode: FallbackMode::default(),
         }
     }
 }
@@ -3,4 +3,26 @@ pub mod util;
 pub mod types;
 pub mod serializer;
 pub use serializer::*;
-pub use internal_types::*;
\ No newline at end of file
+pub use internal_types::*;
+mod memory;
+pub use memory::*;
+mod events;
+pub use events::*;
+mod packs;
+pub use packs::*;
+mod fallback;
+pub use fallback::*;
+mod profile;
+pub use profile::*;
+#[cfg(feature = "dump")]
+mod dump;
+#[cfg(feature = "dump")]
+pub use dump::*;
+mod schema;
+pub use schema::*;
+mod registry;
+pub use registry::*;
+#[cfg(feature = "lint")]
+mod lint;
+#[cfg(feature = "lint")]
+pub use lint::*;
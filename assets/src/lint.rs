@@ -0,0 +1,362 @@
+use log::{error, warn};
+use serde::*;
+use std::path::*;
+use thiserror::Error;
+
+use crate::RawAssetMapper;
+
+/// Error type for asset linting.
+#[derive(Error, Debug)]
+pub enum LintError {
+    #[error("{path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("Unable to write lint report: {0}")]
+    Report(#[from] serde_json::Error),
+}
+
+/// Whether a broken rule is reported as a warning or fails the bake.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LintLevel {
+    #[default]
+    Warn,
+    Deny,
+}
+
+/// A lint rule's limit, and the level it is reported at.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LintRule<T> {
+    pub value: T,
+    #[serde(default)]
+    pub level: LintLevel,
+}
+
+/// Naming conventions for asset file names, not counting the extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NamingConvention {
+    /// Lowercase letters, digits and underscores, e.g. `stone_wall_2`.
+    SnakeCase,
+    /// Lowercase letters, digits and hyphens, e.g. `stone-wall-2`.
+    KebabCase,
+}
+impl NamingConvention {
+    /// Returns true if the name follows the convention.
+    pub fn matches(&self, name: &str) -> bool {
+        let separator = match self {
+            NamingConvention::SnakeCase => '_',
+            NamingConvention::KebabCase => '-',
+        };
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == separator)
+    }
+}
+
+/// Rules checked by [LintRules::check] and [Serializer::with_lint]. Every rule is off by default.
+/// Rules may also be loaded from a .ron, .json or .toml file, where omitted rules are off:
+/// ```ron
+/// (
+///     max_texture_size: Some((value: 2048, level: Deny)),
+///     power_of_two: Some(Warn),
+///     max_triangles: Some((value: 100000)),
+/// )
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LintRules {
+    /// Largest width or height of a texture, in pixels.
+    #[serde(default)]
+    pub max_texture_size: Option<LintRule<u32>>,
+    /// Requires texture sides to be powers of two.
+    #[serde(default)]
+    pub power_of_two: Option<LintLevel>,
+    /// Largest number of triangles in a model, across all of its meshes.
+    #[serde(default)]
+    pub max_triangles: Option<LintRule<usize>>,
+    /// Largest size of any asset file, in bytes.
+    #[serde(default)]
+    pub max_file_size: Option<LintRule<u64>>,
+    /// Names of the assets the game uses. Any other asset is reported as unused.
+    #[serde(default)]
+    pub referenced: Option<LintRule<Vec<String>>>,
+    #[serde(default)]
+    pub naming: Option<LintRule<NamingConvention>>,
+}
+
+/// Identifies the rule behind a [LintIssue].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LintKind {
+    TextureSize,
+    PowerOfTwo,
+    TriangleBudget,
+    FileSize,
+    Unused,
+    Naming,
+}
+
+/// A broken rule.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LintIssue {
+    pub kind: LintKind,
+    pub level: LintLevel,
+    /// The asset's directory under the asset directory, e.g. "textures".
+    pub asset_type: String,
+    pub name: String,
+    pub path: PathBuf,
+    pub message: String,
+}
+
+/// Result of linting an asset directory. Serializes to JSON for CI.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LintReport {
+    /// Sorted by path.
+    pub issues: Vec<LintIssue>,
+}
+impl LintReport {
+    pub fn errors(&self) -> impl Iterator<Item = &LintIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.level == LintLevel::Deny)
+    }
+    pub fn warnings(&self) -> impl Iterator<Item = &LintIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.level == LintLevel::Warn)
+    }
+    /// Returns true if any [LintLevel::Deny] rule was broken.
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+    /// Logs every issue at the matching level.
+    pub fn log(&self) {
+        for issue in &self.issues {
+            match issue.level {
+                LintLevel::Warn => warn!("{}: {}", issue.path.display(), issue.message),
+                LintLevel::Deny => error!("{}: {}", issue.path.display(), issue.message),
+            }
+        }
+    }
+    pub fn to_json(&self) -> Result<String, LintError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+    /// Writes the report as JSON.
+    pub fn write_json<P>(&self, path: P) -> Result<(), LintError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_json()?).map_err(|source| LintError::Io {
+            path: path.display().to_string(),
+            source,
+        })
+    }
+}
+
+impl LintRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with_max_texture_size(mut self, size: u32, level: LintLevel) -> Self {
+        self.max_texture_size = Some(LintRule { value: size, level });
+        self
+    }
+    pub fn with_power_of_two(mut self, level: LintLevel) -> Self {
+        self.power_of_two = Some(level);
+        self
+    }
+    pub fn with_max_triangles(mut self, triangles: usize, level: LintLevel) -> Self {
+        self.max_triangles = Some(LintRule {
+            value: triangles,
+            level,
+        });
+        self
+    }
+    pub fn with_max_file_size(mut self, bytes: u64, level: LintLevel) -> Self {
+        self.max_file_size = Some(LintRule {
+            value: bytes,
+            level,
+        });
+        self
+    }
+    /// Reports assets whose names are not in `names`.
+    pub fn with_referenced<I, S>(mut self, names: I, level: LintLevel) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.referenced = Some(LintRule {
+            value: names.into_iter().map(Into::into).collect(),
+            level,
+        });
+        self
+    }
+    pub fn with_naming(mut self, convention: NamingConvention, level: LintLevel) -> Self {
+        self.naming = Some(LintRule {
+            value: convention,
+            level,
+        });
+        self
+    }
+
+    /// Lints every file in the asset directory. Each subdirectory is treated as an asset type, as the mappers do.
+    /// Texture rules apply to every image file, and the triangle budget to .obj files.
+    /// The unused and naming rules only apply to files one of the `mappers` loads, i.e. those in its directory with one of its
+    /// [RawAssetMapper::extensions], so that e.g. a model's materials and textures are not reported.
+    pub fn check<'m, I>(&self, asset_dir: &Path, mappers: I) -> Result<LintReport, LintError>
+    where
+        I: IntoIterator<Item = &'m dyn RawAssetMapper>,
+    {
+        let mappers = mappers.into_iter().collect::<Vec<_>>();
+        let mut files = vec![];
+        collect_files(asset_dir, &mut files)?;
+        files.sort();
+
+        let mut issues = vec![];
+        for path in files {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let mut components = path.strip_prefix(asset_dir).unwrap_or(&path).components();
+            let asset_type = match (components.next(), components.next()) {
+                (Some(dir), Some(_)) => dir.as_os_str().to_string_lossy().into_owned(),
+                _ => String::new(),
+            };
+            let extension = path.extension().and_then(|ext| ext.to_str());
+            let is_asset = extension.is_some_and(|extension| {
+                mappers.iter().any(|mapper| {
+                    mapper.type_tag() == asset_type && mapper.extensions().contains(&extension)
+                })
+            });
+            let mut report = |kind, level, message: String| {
+                issues.push(LintIssue {
+                    kind,
+                    level,
+                    asset_type: asset_type.clone(),
+                    name: name.clone(),
+                    path: path.clone(),
+                    message,
+                })
+            };
+
+            if let Some(rule) = &self.max_file_size {
+                let size = std::fs::metadata(&path)
+                    .map_err(|source| LintError::Io {
+                        path: path.display().to_string(),
+                        source,
+                    })?
+                    .len();
+                if size > rule.value {
+                    report(
+                        LintKind::FileSize,
+                        rule.level,
+                        format!("File is {} bytes; the limit is {}", size, rule.value),
+                    );
+                }
+            }
+            if is_asset {
+                if let Some(rule) = &self.referenced {
+                    if !rule.value.contains(&name) {
+                        report(
+                            LintKind::Unused,
+                            rule.level,
+                            format!("`{}` is not referenced", name),
+                        );
+                    }
+                }
+                if let Some(rule) = &self.naming {
+                    if !rule.value.matches(&name) {
+                        report(
+                            LintKind::Naming,
+                            rule.level,
+                            format!("`{}` is not {:?}", name, rule.value),
+                        );
+                    }
+                }
+            }
+
+            match extension {
+                #[cfg(feature = "textures")]
+                Some(ext) if image::ImageFormat::from_extension(ext).is_some() => {
+                    let (width, height) = match image::image_dimensions(&path) {
+                        Ok(dimensions) => dimensions,
+                        Err(e) => {
+                            warn!("Unable to read {} for linting. {}", path.display(), e);
+                            continue;
+                        }
+                    };
+                    if let Some(rule) = &self.max_texture_size {
+                        if width > rule.value || height > rule.value {
+                            report(
+                                LintKind::TextureSize,
+                                rule.level,
+                                format!(
+                                    "Texture is {}x{}; the limit is {}",
+                                    width, height, rule.value
+                                ),
+                            );
+                        }
+                    }
+                    if let Some(level) = self.power_of_two {
+                        if !width.is_power_of_two() || !height.is_power_of_two() {
+                            report(
+                                LintKind::PowerOfTwo,
+                                level,
+                                format!(
+                                    "Texture is {}x{}, which is not a power of two",
+                                    width, height
+                                ),
+                            );
+                        }
+                    }
+                }
+                #[cfg(feature = "models")]
+                Some("obj") => {
+                    if let Some(rule) = &self.max_triangles {
+                        let options = tobj::LoadOptions {
+                            triangulate: true,
+                            ..Default::default()
+                        };
+                        let triangles = match tobj::load_obj(&path, &options) {
+                            Ok((models, _)) => models
+                                .iter()
+                                .map(|model| model.mesh.indices.len() / 3)
+                                .sum::<usize>(),
+                            Err(e) => {
+                                warn!("Unable to read {} for linting. {}", path.display(), e);
+                                continue;
+                            }
+                        };
+                        if triangles > rule.value {
+                            report(
+                                LintKind::TriangleBudget,
+                                rule.level,
+                                format!(
+                                    "Model has {} triangles; the budget is {}",
+                                    triangles, rule.value
+                                ),
+                            );
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(LintReport { issues })
+    }
+}
+
+/// Recursively lists every file in the directory.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), LintError> {
+    let io_error = |source| LintError::Io {
+        path: dir.display().to_string(),
+        source,
+    };
+    for entry in std::fs::read_dir(dir).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+        match path.is_dir() {
+            true => collect_files(&path, files)?,
+            false => files.push(path),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,465 @@
+use crate::*;
+use log::{info, warn};
+use std::any::*;
+use std::collections::HashMap;
+use sundile_graphics::{Font, Material, Mesh, Model, TextureWrapper};
+
+// ---
+// Size accounting, unloading and memory budgets for assets stored in an [AssetTypeMap].
+// ---
+
+/// Implement this trait to let an [AssetTypeMap] account for the memory an asset occupies.
+/// For GPU-backed assets this should report the size of their buffers and textures.
+pub trait AssetSize {
+    /// Returns the size of this asset in bytes.
+    fn asset_size(&self) -> u64;
+}
+
+/// Returns the number of bytes used by all mip levels of a texture.
+pub fn texture_size(texture: &wgpu::Texture) -> u64 {
+    let info = texture.format().describe();
+    let (block_width, block_height) = (
+        info.block_dimensions.0 as u64,
+        info.block_dimensions.1 as u64,
+    );
+    let size = texture.size();
+    (0..texture.mip_level_count())
+        .map(|level| {
+            let width = (size.width as u64 >> level).max(1);
+            let height = (size.height as u64 >> level).max(1);
+            let blocks = width.div_ceil(block_width) * height.div_ceil(block_height);
+            blocks * info.block_size as u64 * size.depth_or_array_layers as u64
+        })
+        .sum()
+}
+
+impl AssetSize for TextureWrapper {
+    fn asset_size(&self) -> u64 {
+        texture_size(&self.texture)
+    }
+}
+impl AssetSize for Mesh {
+    fn asset_size(&self) -> u64 {
+        self.vertex_buffer.size()
+            + self.index_buffer.size()
+            + self
+                .lods
+                .iter()
+                .map(|lod| lod.index_buffer.size())
+                .sum::<u64>()
+    }
+}
+impl AssetSize for Material {
+    fn asset_size(&self) -> u64 {
+        self.textures
+            .iter()
+            .map(|texture| texture.asset_size())
+            .sum::<u64>()
+            + self.factor_buffer.size()
+    }
+}
+impl AssetSize for Model {
+    fn asset_size(&self) -> u64 {
+        self.meshes
+            .iter()
+            .map(|mesh| mesh.asset_size())
+            .sum::<u64>()
+            + self
+                .materials
+                .iter()
+                .map(|material| material.asset_size())
+                .sum::<u64>()
+    }
+}
+impl AssetSize for Font {
+    fn asset_size(&self) -> u64 {
+        self.data.len() as u64
+    }
+}
+
+/// Type-erased size function, as registered with [AssetTypeMap::register_asset_size].
+pub type AssetSizeFn = fn(&dyn Any) -> u64;
+
+fn size_of_asset<T>(asset: &dyn Any) -> u64
+where
+    T: AssetSize + 'static,
+{
+    asset.downcast_ref::<T>().map(T::asset_size).unwrap_or(0)
+}
+
+/// Size functions registered with every new [AssetTypeMap].
+pub(crate) fn default_sizers() -> HashMap<TypeId, AssetSizeFn> {
+    HashMap::from_iter([
+        (
+            TypeId::of::<TextureWrapper>(),
+            size_of_asset::<TextureWrapper> as AssetSizeFn,
+        ),
+        (TypeId::of::<Model>(), size_of_asset::<Model> as AssetSizeFn),
+        (TypeId::of::<Mesh>(), size_of_asset::<Mesh> as AssetSizeFn),
+        (TypeId::of::<Font>(), size_of_asset::<Font> as AssetSizeFn),
+    ])
+}
+
+/// What an [AssetTypeMap] does when its [MemoryBudget] is exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetPolicy {
+    /// Logs a warning.
+    Warn,
+    /// Unloads unpinned, unreferenced assets, least recently used first, until the budget is met.
+    /// Logs a warning if that is not enough.
+    EvictLeastRecentlyUsed,
+}
+
+/// Upper bound on the memory used by the assets of an [AssetTypeMap].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget {
+    /// Limit in bytes.
+    pub limit: u64,
+    pub policy: BudgetPolicy,
+}
+impl MemoryBudget {
+    /// Creates a budget which only warns when exceeded.
+    pub fn warn(limit: u64) -> Self {
+        Self {
+            limit,
+            policy: BudgetPolicy::Warn,
+        }
+    }
+    /// Creates a budget which evicts least recently used assets when exceeded.
+    pub fn evict_lru(limit: u64) -> Self {
+        Self {
+            limit,
+            policy: BudgetPolicy::EvictLeastRecentlyUsed,
+        }
+    }
+}
+
+/// Memory information about a single asset, as returned by [AssetTypeMap::memory_report].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssetMemoryInfo {
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+    pub name: String,
+    /// Size in bytes, or 0 if no size function is registered for the type.
+    pub size: u64,
+    pub pinned: bool,
+    pub referenced: bool,
+    pub last_used: u64,
+    /// For a copy overridden by a higher-priority pack, the pack it came from. Such copies are kept to be restored when
+    /// the overriding pack is unmounted. None for the asset that lookups return.
+    pub overridden: Option<AssetOrigin>,
+}
+
+/// Identifies an asset that was removed from an [AssetTypeMap].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnloadedAsset {
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+    pub name: String,
+}
+
+impl AssetTypeMap {
+    /// Registers a size function for assets of type T. Sizes for [TextureWrapper], [Model], [Mesh] and [Font] are registered by default.
+    pub fn register_asset_size<T>(&mut self)
+    where
+        T: AssetSize + 'static,
+    {
+        self.sizers
+            .insert(TypeId::of::<T>(), size_of_asset::<T> as AssetSizeFn);
+        self.track_all();
+    }
+
+    /// Marks an asset as used now. Used to determine eviction order.
+    pub(crate) fn touch(&self, storage: &AssetStorage) {
+        let now = self.clock.get() + 1;
+        self.clock.set(now);
+        storage.last_used.set(now);
+    }
+
+    /// Measures a newly stored asset, adds it to the memory usage and marks it as used now.
+    pub(crate) fn track(&self, type_id: &TypeId, storage: &AssetStorage) {
+        let size = self.size_of(type_id, storage);
+        storage.size.set(size);
+        self.usage.set(self.usage.get() + size);
+        self.touch(storage);
+    }
+
+    /// Removes the size of an asset which is no longer stored, as recorded by [AssetTypeMap::track], from the memory usage.
+    pub(crate) fn untrack(&self, size: u64) {
+        self.usage.set(self.usage.get() - size);
+    }
+
+    /// Returns the recorded size of a stored asset, or 0 if there is none.
+    pub(crate) fn stored_size(&self, type_id: TypeId, name: &str) -> u64 {
+        self.map
+            .get(&type_id)
+            .and_then(|asset_map| asset_map.map.get(name))
+            .map_or(0, |storage| storage.size.get())
+    }
+
+    /// Measures every asset again, including overridden copies, and marks every asset that was never accessed as used now.
+    /// Called after maps are inserted or removed as a whole; single assets are tracked as they are stored.
+    pub(crate) fn track_all(&self) {
+        let now = self.clock.get() + 1;
+        self.clock.set(now);
+        let stored = self
+            .map
+            .iter()
+            .flat_map(|(ty, asset_map)| asset_map.map.values().map(move |storage| (ty, storage)));
+        let shadowed = self
+            .shadowed
+            .iter()
+            .map(|shadowed| (&shadowed.type_id, &shadowed.storage));
+        let mut usage = 0;
+        for (ty, storage) in stored.chain(shadowed) {
+            if storage.last_used.get() == 0 {
+                storage.last_used.set(now);
+            }
+            let size = self.size_of(ty, storage);
+            storage.size.set(size);
+            usage += size;
+        }
+        self.usage.set(usage);
+    }
+
+    fn size_of(&self, type_id: &TypeId, storage: &AssetStorage) -> u64 {
+        match self.sizers.get(type_id) {
+            Some(sizer) => sizer(storage.as_ref()),
+            None => 0,
+        }
+    }
+
+    /// Returns the size of an asset in bytes, or 0 if no size function is registered for T.
+    pub fn try_asset_size<T>(&self, name: &str) -> Result<u64, AssetError>
+    where
+        T: 'static,
+    {
+        let ty = TypeId::of::<T>();
+        let asset_map = self.map.get(&ty).ok_or(AssetError::AssetMapNotFound)?;
+        let storage = asset_map
+            .map
+            .get(name)
+            .ok_or_else(|| AssetError::AssetNotFound(name.into()))?;
+        Ok(self.size_of(&ty, storage))
+    }
+
+    /// Returns the combined size of all assets in bytes, including copies overridden by a higher-priority pack.
+    /// Assets are measured when they are stored, so an asset changed through [AssetTypeMap::try_get_asset_mut] is counted
+    /// at its old size until it is stored again.
+    pub fn memory_usage(&self) -> u64 {
+        self.usage.get()
+    }
+
+    /// Lists every asset along with its size, sorted from largest to smallest.
+    /// Copies overridden by a higher-priority pack are listed too, with [AssetMemoryInfo::overridden] set.
+    pub fn memory_report(&self) -> Vec<AssetMemoryInfo> {
+        let mut report = vec![];
+        for (ty, asset_map) in &self.map {
+            for (name, storage) in &asset_map.map {
+                report.push(AssetMemoryInfo {
+                    type_id: *ty,
+                    type_name: asset_map.type_name,
+                    name: name.to_owned(),
+                    size: storage.size.get(),
+                    pinned: asset_map.pinned.contains(name),
+                    referenced: storage.is_referenced(),
+                    last_used: storage.last_used.get(),
+                    overridden: None,
+                });
+            }
+        }
+        for shadowed in &self.shadowed {
+            let asset_map = match self.map.get(&shadowed.type_id) {
+                Some(asset_map) => asset_map,
+                None => continue,
+            };
+            report.push(AssetMemoryInfo {
+                type_id: shadowed.type_id,
+                type_name: asset_map.type_name,
+                name: shadowed.name.to_owned(),
+                size: shadowed.storage.size.get(),
+                pinned: asset_map.pinned.contains(&shadowed.name),
+                referenced: shadowed.storage.is_referenced(),
+                last_used: shadowed.storage.last_used.get(),
+                overridden: Some(shadowed.origin.clone()),
+            });
+        }
+        report.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        report
+    }
+
+    /// Pins or unpins an asset. Pinned assets are never unloaded by [AssetTypeMap::collect_garbage] or by a [MemoryBudget].
+    /// Pins are kept by name, so they survive the asset being taken and reinserted.
+    pub fn try_pin_asset<T>(&mut self, name: &str, pinned: bool) -> Result<(), AssetError>
+    where
+        T: 'static,
+    {
+        let asset_map = self
+            .map
+            .get_mut(&TypeId::of::<T>())
+            .ok_or(AssetError::AssetMapNotFound)?;
+        if !asset_map.map.contains_key(name) {
+            return Err(AssetError::AssetNotFound(name.into()));
+        }
+        if pinned {
+            asset_map.pinned.insert(name.into());
+        } else {
+            asset_map.pinned.remove(name);
+        }
+        Ok(())
+    }
+
+    /// Removes an asset from the map. Unlike [AssetTypeMap::try_take_asset], this succeeds even if the asset is referenced elsewhere;
+    /// it will be freed once those references are dropped.
+    pub fn try_unload_asset<T>(&mut self, name: &str) -> Result<(), AssetError>
+    where
+        T: 'static,
+    {
+        let asset_map = self
+            .map
+            .get_mut(&TypeId::of::<T>())
+            .ok_or(AssetError::AssetMapNotFound)?;
+        let storage = asset_map
+            .map
+            .remove(name)
+            .ok_or_else(|| AssetError::AssetNotFound(name.into()))?;
+        asset_map.pinned.remove(name);
+        let type_name = asset_map.type_name;
+        self.untrack(storage.size.get());
+        self.forget_origin(TypeId::of::<T>(), name);
+        self.emit(AssetEventKind::Removed, TypeId::of::<T>(), type_name, name);
+        Ok(())
+    }
+
+    /// Unloads every asset which is neither pinned nor referenced outside of this map.
+    /// Returns the unloaded assets.
+    pub fn collect_garbage(&mut self) -> Vec<UnloadedAsset> {
+        let mut unloaded = vec![];
+        let usage = &self.usage;
+        for (ty, asset_map) in self.map.iter_mut() {
+            let pinned = &asset_map.pinned;
+            let type_name = asset_map.type_name;
+            asset_map.map.retain(|name, storage| {
+                let keep = pinned.contains(name) || storage.is_referenced();
+                if !keep {
+                    usage.set(usage.get() - storage.size.get());
+                    unloaded.push(UnloadedAsset {
+                        type_id: *ty,
+                        type_name,
+                        name: name.to_owned(),
+                    });
+                }
+                keep
+            });
+        }
+        if !unloaded.is_empty() {
+            info!("Collected {} unused assets.", unloaded.len());
+        }
+        self.emit_unloaded(&unloaded);
+        unloaded
+    }
+
+    fn emit_unloaded(&mut self, unloaded: &[UnloadedAsset]) {
+        for asset in unloaded {
+            self.forget_origin(asset.type_id, &asset.name);
+            self.emit(
+                AssetEventKind::Removed,
+                asset.type_id,
+                asset.type_name,
+                &asset.name,
+            );
+        }
+    }
+
+    /// Sets or clears the memory budget. The budget is checked whenever assets are inserted.
+    pub fn set_memory_budget(&mut self, budget: Option<MemoryBudget>) {
+        self.budget = budget;
+        self.enforce_memory_budget();
+    }
+
+    /// Returns the current memory budget.
+    pub fn memory_budget(&self) -> Option<MemoryBudget> {
+        self.budget
+    }
+
+    /// Checks the memory budget, evicting assets if the budget's policy allows it.
+    /// Copies overridden by a higher-priority pack may be evicted too; they are dropped without an event.
+    /// Returns the evicted assets.
+    pub fn enforce_memory_budget(&mut self) -> Vec<UnloadedAsset> {
+        let budget = match self.budget {
+            Some(budget) => budget,
+            None => return vec![],
+        };
+        let mut evicted = vec![];
+
+        if self.memory_usage() > budget.limit
+            && budget.policy == BudgetPolicy::EvictLeastRecentlyUsed
+        {
+            let mut candidates = self
+                .memory_report()
+                .into_iter()
+                .filter(|info| !info.pinned && !info.referenced && info.size > 0)
+                .collect::<Vec<_>>();
+            candidates.sort_by_key(|info| info.last_used);
+
+            let mut dropped_copies = 0;
+            for info in candidates {
+                if self.memory_usage() <= budget.limit {
+                    break;
+                }
+                if let Some(origin) = &info.overridden {
+                    let index = self.shadowed.iter().position(|shadowed| {
+                        shadowed.type_id == info.type_id
+                            && shadowed.name == info.name
+                            && &shadowed.origin == origin
+                    });
+                    // Gone if the asset overriding it was evicted first.
+                    if let Some(index) = index {
+                        let shadowed = self.shadowed.remove(index);
+                        self.untrack(shadowed.storage.size.get());
+                        dropped_copies += 1;
+                    }
+                    continue;
+                }
+                let storage = match self.map.get_mut(&info.type_id) {
+                    Some(asset_map) => asset_map.map.remove(&info.name),
+                    None => None,
+                };
+                if let Some(storage) = storage {
+                    self.untrack(storage.size.get());
+                    // Frees the copies it overrode right away, so they count towards the budget.
+                    self.forget_origin(info.type_id, &info.name);
+                    evicted.push(UnloadedAsset {
+                        type_id: info.type_id,
+                        type_name: info.type_name,
+                        name: info.name,
+                    });
+                }
+            }
+            if !evicted.is_empty() || dropped_copies > 0 {
+                info!(
+                    "Evicted {} assets and {} overridden copies to meet the memory budget of {} bytes.",
+                    evicted.len(),
+                    dropped_copies,
+                    budget.limit
+                );
+            }
+            self.emit_unloaded(&evicted);
+        }
+
+        let usage = self.memory_usage();
+
+        if usage > budget.limit {
+            if !self.over_budget {
+                warn!(
+                    "Assets use {} bytes, exceeding the memory budget of {} bytes.",
+                    usage, budget.limit
+                );
+            }
+            self.over_budget = true;
+        } else {
+            self.over_budget = false;
+        }
+        evicted
+    }
+}
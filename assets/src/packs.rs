@@ -0,0 +1,301 @@
+use crate::*;
+use log::{info, warn};
+use std::any::*;
+
+// ---
+// Layered asset packs. Each pack is mounted with a priority; lookups resolve to the highest-priority pack.
+// ---
+
+/// Where an asset in an [AssetTypeMap] came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetOrigin {
+    pub pack: String,
+    pub priority: i32,
+    /// Order in which the pack was mounted. Breaks ties between packs of equal priority; later mounts win.
+    pub(crate) mount_order: u64,
+}
+impl AssetOrigin {
+    fn rank(&self) -> (i32, u64) {
+        (self.priority, self.mount_order)
+    }
+    fn outranks(&self, other: &AssetOrigin) -> bool {
+        self.rank() > other.rank()
+    }
+}
+
+/// An asset hidden by a higher-priority pack. Restored when that pack is unmounted.
+#[derive(Debug)]
+pub(crate) struct ShadowedAsset {
+    pub(crate) type_id: TypeId,
+    pub(crate) name: String,
+    pub(crate) origin: AssetOrigin,
+    pub(crate) storage: AssetStorage,
+}
+
+/// Describes where an asset came from, as returned by [AssetTypeMap::list_asset_sources].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetSource {
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+    pub name: String,
+    /// The pack providing the asset, or None if it was inserted at runtime.
+    pub origin: Option<AssetOrigin>,
+    /// Packs which also provide this asset but are overridden, from highest to lowest priority.
+    pub overridden: Vec<AssetOrigin>,
+}
+
+impl AssetTypeMap {
+    /// Mounts a pack of assets with the given priority.
+    /// Where names conflict, the pack with the highest priority wins; between equal priorities, the pack mounted last wins.
+    /// Assets inserted at runtime are replaced by any pack.
+    /// Overridden assets are kept and come back when the overriding pack is unmounted.
+    /// Removing an asset, e.g. with [AssetTypeMap::try_unload_asset] or [AssetTypeMap::collect_garbage], drops the copies it overrode too.
+    /// ```rust
+    /// use sundile_assets::*;
+    /// let mut assets = AssetTypeMap::new();
+    /// let mut base = AssetTypeMap::new();
+    /// base.try_insert_asset("greeting", "hello").unwrap();
+    /// let mut a_mod = AssetTypeMap::new();
+    /// a_mod.try_insert_asset("greeting", "howdy").unwrap();
+    ///
+    /// assets.mount("base", 0, base).unwrap();
+    /// assets.mount("my_mod", 10, a_mod).unwrap();
+    /// assert_eq!(*assets.try_get_asset::<&str>("greeting").unwrap(), "howdy");
+    /// assets.unmount("my_mod").unwrap();
+    /// assert_eq!(*assets.try_get_asset::<&str>("greeting").unwrap(), "hello");
+    /// ```
+    pub fn mount<S>(
+        &mut self,
+        pack: S,
+        priority: i32,
+        assets: AssetTypeMap,
+    ) -> Result<&Self, AssetError>
+    where
+        S: Into<String>,
+    {
+        let pack = pack.into();
+        if self.is_mounted(&pack) {
+            return Err(AssetError::PackAlreadyMounted(pack));
+        }
+        self.mount_count += 1;
+        let origin = AssetOrigin {
+            pack: pack.clone(),
+            priority,
+            mount_order: self.mount_count,
+        };
+        self.mounted.push(origin.clone());
+        info!("Mounting asset pack `{}` with priority {}.", pack, priority);
+
+        for (ty, new_map) in assets.map {
+            for storage in new_map.map.values() {
+                self.track(&ty, storage);
+            }
+            let type_name = new_map.type_name;
+            let live_map = self
+                .map
+                .entry(ty)
+                .or_insert_with(|| AssetMap::from_type_id(ty));
+            if live_map.type_name == "unknown" {
+                live_map.type_name = type_name;
+            }
+            let mut events = vec![];
+
+            for (name, storage) in new_map.map {
+                let key = (ty, name.clone());
+                if !live_map.map.contains_key(&name) {
+                    live_map.map.insert(name.clone(), storage);
+                    self.origins.insert(key, origin.clone());
+                    events.push((AssetEventKind::Inserted, name));
+                    continue;
+                }
+                match self.origins.get(&key) {
+                    Some(current) if current.outranks(&origin) => {
+                        info!(
+                            "{} `{}` from pack `{}` is overridden by pack `{}`.",
+                            type_name, name, pack, current.pack
+                        );
+                        self.shadowed.push(ShadowedAsset {
+                            type_id: ty,
+                            name,
+                            origin: origin.clone(),
+                            storage,
+                        });
+                    }
+                    current => {
+                        match current {
+                            Some(current) => info!(
+                                "{} `{}` from pack `{}` overrides pack `{}`.",
+                                type_name, name, pack, current.pack
+                            ),
+                            None => info!(
+                                "{} `{}` from pack `{}` replaces an asset inserted at runtime.",
+                                type_name, name, pack
+                            ),
+                        }
+                        let old = live_map.map.insert(name.clone(), storage).unwrap();
+                        if let Some(current) = self.origins.insert(key, origin.clone()) {
+                            self.shadowed.push(ShadowedAsset {
+                                type_id: ty,
+                                name: name.clone(),
+                                origin: current,
+                                storage: old,
+                            });
+                        }
+                        events.push((AssetEventKind::Replaced, name));
+                    }
+                }
+            }
+            for (kind, name) in events {
+                self.emit(kind, ty, type_name, &name);
+            }
+        }
+        self.enforce_memory_budget();
+        Ok(self)
+    }
+
+    /// Unmounts a pack, removing its assets and restoring any assets it overrode.
+    pub fn unmount(&mut self, pack: &str) -> Result<&Self, AssetError> {
+        if !self.is_mounted(pack) {
+            return Err(AssetError::PackNotFound(pack.into()));
+        }
+        self.mounted.retain(|origin| origin.pack != pack);
+        let usage = &self.usage;
+        self.shadowed.retain(|shadowed| {
+            let keep = shadowed.origin.pack != pack;
+            if !keep {
+                usage.set(usage.get() - shadowed.storage.size.get());
+            }
+            keep
+        });
+        info!("Unmounting asset pack `{}`.", pack);
+
+        let provided = self
+            .origins
+            .iter()
+            .filter(|(_, origin)| origin.pack == pack)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in provided {
+            self.origins.remove(&key);
+            let (ty, name) = key;
+            let replacement = self
+                .shadowed
+                .iter()
+                .enumerate()
+                .filter(|(_, shadowed)| shadowed.type_id == ty && shadowed.name == name)
+                .reduce(|best, next| match next.1.origin.outranks(&best.1.origin) {
+                    true => next,
+                    false => best,
+                })
+                .map(|(index, _)| index);
+
+            let asset_map = match self.map.get_mut(&ty) {
+                Some(asset_map) => asset_map,
+                None => continue,
+            };
+            let type_name = asset_map.type_name;
+            match replacement {
+                Some(index) => {
+                    let shadowed = self.shadowed.remove(index);
+                    if let Some(storage) = asset_map.map.insert(name.clone(), shadowed.storage) {
+                        self.untrack(storage.size.get());
+                    }
+                    self.origins.insert((ty, name.clone()), shadowed.origin);
+                    self.emit(AssetEventKind::Replaced, ty, type_name, &name);
+                }
+                None => {
+                    if let Some(storage) = asset_map.map.remove(&name) {
+                        self.untrack(storage.size.get());
+                        self.emit(AssetEventKind::Removed, ty, type_name, &name);
+                    }
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// Returns true if a pack with the given name is mounted.
+    pub fn is_mounted(&self, pack: &str) -> bool {
+        self.mounted.iter().any(|origin| origin.pack == pack)
+    }
+
+    /// Lists the mounted packs, from highest to lowest priority.
+    pub fn list_packs(&self) -> Vec<AssetOrigin> {
+        let mut packs = self.mounted.clone();
+        packs.sort_by_key(|origin| std::cmp::Reverse(origin.rank()));
+        packs
+    }
+
+    /// Returns the origin of an asset, or None if it was inserted at runtime.
+    pub fn try_asset_origin<T>(&self, name: &str) -> Result<Option<AssetOrigin>, AssetError>
+    where
+        T: 'static,
+    {
+        let ty = TypeId::of::<T>();
+        let asset_map = self.map.get(&ty).ok_or(AssetError::AssetMapNotFound)?;
+        if !asset_map.map.contains_key(name) {
+            return Err(AssetError::AssetNotFound(name.into()));
+        }
+        Ok(self.origins.get(&(ty, name.to_owned())).cloned())
+    }
+
+    /// Lists every asset along with the pack it came from and the packs it overrides, sorted by type and name.
+    pub fn list_asset_sources(&self) -> Vec<AssetSource> {
+        let mut sources = vec![];
+        for (ty, asset_map) in &self.map {
+            for name in asset_map.map.keys() {
+                let key = (*ty, name.to_owned());
+                let mut overridden = self
+                    .shadowed
+                    .iter()
+                    .filter(|shadowed| shadowed.type_id == *ty && &shadowed.name == name)
+                    .map(|shadowed| shadowed.origin.clone())
+                    .collect::<Vec<_>>();
+                overridden.sort_by_key(|origin| std::cmp::Reverse(origin.rank()));
+                sources.push(AssetSource {
+                    type_id: *ty,
+                    type_name: asset_map.type_name,
+                    name: name.to_owned(),
+                    origin: self.origins.get(&key).cloned(),
+                    overridden,
+                });
+            }
+        }
+        sources.sort_by(|a, b| (a.type_name, &a.name).cmp(&(b.type_name, &b.name)));
+        sources
+    }
+
+    /// Forgets where an asset came from, and drops the copies it overrode, which could no longer be restored.
+    /// Called when an asset is replaced or removed outside of [AssetTypeMap::mount].
+    pub(crate) fn forget_origin(&mut self, type_id: TypeId, name: &str) {
+        if self.origins.is_empty() {
+            return;
+        }
+        if self.origins.remove(&(type_id, name.to_owned())).is_some() {
+            let usage = &self.usage;
+            self.shadowed.retain(|shadowed| {
+                let keep = shadowed.type_id != type_id || shadowed.name != name;
+                if !keep {
+                    usage.set(usage.get() - shadowed.storage.size.get());
+                }
+                keep
+            });
+        }
+    }
+
+    /// Logs a warning for every asset in `other` that would replace one in this map.
+    pub(crate) fn warn_conflicts(&self, other: &AssetTypeMap) {
+        for (ty, new_map) in &other.map {
+            if let Some(old_map) = self.map.get(ty) {
+                for name in new_map.map.keys() {
+                    if old_map.map.contains_key(name) {
+                        warn!(
+                            "{} `{}` is defined more than once; the last one combined wins. Use AssetTypeMap::mount to control priority.",
+                            new_map.type_name, name
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
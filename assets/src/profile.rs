@@ -0,0 +1,94 @@
+use sundile_graphics::LodSettings;
+
+/// Environment variable read by [Serializer::serialize] when no profile was set with [Serializer::with_profile].
+/// `sundile_pack` sets it to `web`, so build scripts bake for the web without any changes.
+pub const PROFILE_ENV_VAR: &str = "SUNDILE_BAKE_PROFILE";
+
+/// How baked textures are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureCompression {
+    /// Keeps the source file's bytes, unless the texture has to be resized.
+    Source,
+    /// Re-encodes every PNG texture with the strongest compression, if that makes it smaller. Lossless, but slower to bake.
+    /// Textures in other formats are only re-encoded if they have to be resized, in their own format.
+    Png,
+}
+
+/// A named set of per-type bake options, e.g. for a platform. Applied to every mapper through [RawAssetMapper::apply_profile].
+/// Build scripts which bake with the profile named by [PROFILE_ENV_VAR] should print `cargo:rerun-if-env-changed=SUNDILE_BAKE_PROFILE`
+/// next to their own `cargo:rerun-if-changed=<asset dir>`, so a new profile rebakes the assets.
+/// ```rust
+/// use sundile_assets::*;
+/// let web = BakeProfile::named("web").unwrap();
+/// assert_eq!(web.max_texture_size, Some(1024));
+/// let mobile = BakeProfile::new("mobile").with_max_texture_size(512);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct BakeProfile {
+    pub name: String,
+    /// Textures larger than this on either side are scaled down, keeping their aspect ratio. None keeps every texture at full size.
+    pub max_texture_size: Option<u32>,
+    pub texture_compression: TextureCompression,
+    /// Names of the shaders to include. None includes every shader.
+    pub shaders: Option<Vec<String>>,
+    /// How mesh levels of detail are generated. None bakes no levels of detail.
+    pub lods: Option<LodSettings>,
+}
+impl BakeProfile {
+    /// Creates a profile which bakes everything as the mappers' defaults would.
+    pub fn new<S>(name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            max_texture_size: None,
+            texture_compression: TextureCompression::Source,
+            shaders: None,
+            lods: Some(LodSettings::default()),
+        }
+    }
+    /// Full-size textures and the default levels of detail.
+    pub fn desktop() -> Self {
+        Self::new("desktop")
+    }
+    /// Smaller downloads: textures of at most 1024 pixels, recompressed, and fewer levels of detail.
+    pub fn web() -> Self {
+        Self::new("web")
+            .with_max_texture_size(1024)
+            .with_texture_compression(TextureCompression::Png)
+            .with_lods(Some(LodSettings {
+                levels: 2,
+                ..Default::default()
+            }))
+    }
+    /// Returns a built-in profile by name: `desktop` or `web`.
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "desktop" => Some(Self::desktop()),
+            "web" => Some(Self::web()),
+            _ => None,
+        }
+    }
+    pub fn with_max_texture_size(mut self, size: u32) -> Self {
+        self.max_texture_size = Some(size);
+        self
+    }
+    pub fn with_texture_compression(mut self, compression: TextureCompression) -> Self {
+        self.texture_compression = compression;
+        self
+    }
+    /// Only bakes the named shaders.
+    pub fn with_shaders<I, S>(mut self, shaders: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.shaders = Some(shaders.into_iter().map(Into::into).collect());
+        self
+    }
+    pub fn with_lods(mut self, lods: Option<LodSettings>) -> Self {
+        self.lods = lods;
+        self
+    }
+}
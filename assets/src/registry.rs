@@ -0,0 +1,72 @@
+use crate::*;
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+// ---
+// Global registry of raw asset mappers, keyed by type tag.
+// ---
+
+type MapperFactory = Arc<dyn Fn() -> Box<dyn RawAssetMapper + Send> + Send + Sync>;
+
+/// The built-in mappers are registered on first use. Scenes and data mappers are not, since they require their directories to exist.
+fn registry() -> &'static RwLock<BTreeMap<String, MapperFactory>> {
+    static REGISTRY: OnceLock<RwLock<BTreeMap<String, MapperFactory>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        #[allow(unused_mut)]
+        let mut map = BTreeMap::new();
+        #[cfg(feature = "shaders")]
+        insert(&mut map, types::shaders::Mapper::new);
+        #[cfg(feature = "models")]
+        insert(&mut map, types::models::Mapper::new);
+        #[cfg(feature = "textures")]
+        insert(&mut map, types::textures::Mapper::new);
+        #[cfg(feature = "fonts")]
+        insert(&mut map, types::fonts::Mapper::new);
+        RwLock::new(map)
+    })
+}
+
+fn insert<M, F>(map: &mut BTreeMap<String, MapperFactory>, factory: F) -> String
+where
+    M: RawAssetMapper + Send + 'static,
+    F: Fn() -> M + Send + Sync + 'static,
+{
+    let tag = factory().type_tag().to_owned();
+    map.insert(tag.clone(), Arc::new(move || Box::new(factory())));
+    tag
+}
+
+/// Registers a mapper under its [RawAssetMapper::type_tag], replacing any mapper with the same tag, and returns the tag.
+/// Registered mappers are used by [Serializer::default] and [Deserializer::default]. The factory is called once for each of them.
+/// ```ignore
+/// register_mapper(types::scenes::Mapper::new);
+/// register_mapper(|| types::data::Mapper::<EnemyStats>::new("enemies"));
+/// ```
+pub fn register_mapper<M, F>(factory: F) -> String
+where
+    M: RawAssetMapper + Send + 'static,
+    F: Fn() -> M + Send + Sync + 'static,
+{
+    let mut map = registry().write().expect("Mapper registry poisoned");
+    insert(&mut map, factory)
+}
+
+/// Removes the mapper registered under `type_tag`. Returns false if there was none.
+pub fn unregister_mapper(type_tag: &str) -> bool {
+    let mut map = registry().write().expect("Mapper registry poisoned");
+    map.remove(type_tag).is_some()
+}
+
+/// Returns the tags of every registered mapper, in order.
+pub fn registered_type_tags() -> Vec<String> {
+    let map = registry().read().expect("Mapper registry poisoned");
+    map.keys().cloned().collect()
+}
+
+/// Creates one of each registered mapper, keyed by type tag.
+pub fn registered_mappers() -> Vec<(String, Box<dyn RawAssetMapper + Send>)> {
+    let map = registry().read().expect("Mapper registry poisoned");
+    map.iter()
+        .map(|(tag, factory)| (tag.clone(), factory()))
+        .collect()
+}
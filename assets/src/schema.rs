@@ -0,0 +1,147 @@
+use serde::*;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::*;
+
+// ---
+// Versioning of raw asset layouts in data.bin.
+// ---
+
+/// Marks a pack which records the schema version of each asset type. Packs without it are read as version 0 throughout.
+pub const PACK_MAGIC: &[u8; 4] = b"SNDL";
+
+/// Converts one serialized raw asset from the layout of a schema version to the layout of the next.
+pub type MigrationFn = fn(&[u8]) -> Result<Vec<u8>, bincode::Error>;
+
+/// Error type for reading packs and migrating their raw assets.
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("Unable to read pack: {0}")]
+    Pack(#[from] bincode::Error),
+    #[error("`{asset_type}` was baked with schema version {from}, but no migration from version {missing} is registered. Rebake the assets to update them to version {to}.")]
+    NoPath {
+        asset_type: String,
+        from: u32,
+        to: u32,
+        missing: u32,
+    },
+    #[error("`{asset_type}` was baked with schema version {from}, which is newer than this build's version {to}. Update the game or rebake the assets.")]
+    TooNew {
+        asset_type: String,
+        from: u32,
+        to: u32,
+    },
+    #[error("Unable to migrate `{name}` in `{asset_type}` from schema version {from}: {source}")]
+    Failed {
+        asset_type: String,
+        name: String,
+        from: u32,
+        source: bincode::Error,
+    },
+}
+
+/// The current layout version of a mapper's raw assets, and the migrations from older versions. See [RawAssetMapper::schema].
+/// Bump the version whenever the serialized layout changes, and register a migration from the previous version:
+/// ```ignore
+/// fn schema(&self) -> Schema {
+///     Schema::new(1).with_migration(0, |bytes| {
+///         let old: v0::ModelData = bincode::deserialize(bytes)?;
+///         bincode::serialize(&ModelData::from(old))
+///     })
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    pub version: u32,
+    migrations: BTreeMap<u32, MigrationFn>,
+}
+impl Schema {
+    pub fn new(version: u32) -> Self {
+        Self {
+            version,
+            migrations: BTreeMap::new(),
+        }
+    }
+    /// Registers a migration from version `from` to version `from + 1`. Older packs are migrated one version at a time.
+    pub fn with_migration(mut self, from: u32, migration: MigrationFn) -> Self {
+        self.migrations.insert(from, migration);
+        self
+    }
+    /// Migrates every raw asset in `bin_map` from version `from` to the current version.
+    /// Fails without migrating anything if a step is missing.
+    pub fn migrate(
+        &self,
+        asset_type: &str,
+        from: u32,
+        bin_map: BincodeAssetMap,
+    ) -> Result<BincodeAssetMap, MigrationError> {
+        if from > self.version {
+            return Err(MigrationError::TooNew {
+                asset_type: asset_type.to_owned(),
+                from,
+                to: self.version,
+            });
+        }
+        let steps = (from..self.version)
+            .map(|version| match self.migrations.get(&version) {
+                Some(migration) => Ok((version, *migration)),
+                None => Err(MigrationError::NoPath {
+                    asset_type: asset_type.to_owned(),
+                    from,
+                    to: self.version,
+                    missing: version,
+                }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        bin_map
+            .into_iter()
+            .map(|(name, mut bytes)| {
+                for (version, migration) in &steps {
+                    bytes = match migration(&bytes) {
+                        Ok(bytes) => bytes,
+                        Err(source) => {
+                            return Err(MigrationError::Failed {
+                                asset_type: asset_type.to_owned(),
+                                name,
+                                from: *version,
+                                source,
+                            })
+                        }
+                    };
+                }
+                Ok((name, bytes))
+            })
+            .collect()
+    }
+}
+
+/// The contents of data.bin: every raw asset map, and the schema version each was baked with.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pack {
+    /// Asset types missing from this map are at version 0.
+    pub versions: BTreeMap<String, u32>,
+    pub maps: BincodeAssetTypeMap,
+}
+impl Pack {
+    /// Reads a pack, including packs baked before schema versions were recorded.
+    pub fn from_bin(bin: &[u8]) -> Result<Self, MigrationError> {
+        match bin.strip_prefix(PACK_MAGIC) {
+            Some(bin) => Ok(bincode::deserialize(bin)?),
+            None => Ok(Self {
+                versions: BTreeMap::new(),
+                maps: bincode::deserialize(bin)?,
+            }),
+        }
+    }
+    pub fn to_bin(&self) -> Vec<u8> {
+        let mut bin = PACK_MAGIC.to_vec();
+        bin.extend(bincode::serialize(self).expect("Unable to serialize Pack"));
+        bin
+    }
+    /// Returns the schema version `asset_type` was baked with.
+    pub fn version(&self, asset_type: &str) -> u32 {
+        self.versions.get(asset_type).copied().unwrap_or(0)
+    }
+}
@@ -1,13 +1,23 @@
 use crate::*;
-use log::info;
-use std::collections::HashMap;
+use log::{info, warn};
+use std::collections::{BTreeMap, HashMap};
 use std::path::*;
 
 /// Loads asset data into a binary. Intended to be used in build scripts to statically load assets.
+/// On native targets with the `parallel` feature, mappers and the assets within them are baked on multiple threads.
+/// The output is identical regardless of thread count.
 pub struct Serializer<'a> {
-    mappers: HashMap<String, Box<dyn RawAssetMapper + 'a>>,
+    mappers: HashMap<String, Box<dyn RawAssetMapper + Send + 'a>>,
     out_path: Option<PathBuf>,
     asset_directory: Option<PathBuf>,
+    threads: Option<usize>,
+    profile: Option<BakeProfile>,
+    #[cfg(feature = "dump")]
+    dump: bool,
+    #[cfg(feature = "lint")]
+    lint: Option<LintRules>,
+    #[cfg(feature = "lint")]
+    lint_report: Option<PathBuf>,
 }
 impl<'a> Serializer<'a> {
     /// Creates a new serializer with default options.
@@ -16,15 +26,27 @@ impl<'a> Serializer<'a> {
             mappers: HashMap::new(),
             out_path: None,
             asset_directory: None,
+            threads: None,
+            profile: None,
+            #[cfg(feature = "dump")]
+            dump: false,
+            #[cfg(feature = "lint")]
+            lint: None,
+            #[cfg(feature = "lint")]
+            lint_report: None,
         }
     }
-    /// Adds an asset map to be serialized.
-    pub fn with_mapper<S>(mut self, asset_type_name: S, mapper: impl RawAssetMapper + 'a) -> Self
-    where
-        S: Into<String>,
-    {
+    /// Adds an asset map to be serialized, under its [RawAssetMapper::type_tag].
+    pub fn with_mapper(mut self, mapper: impl RawAssetMapper + Send + 'a) -> Self {
         self.mappers
-            .insert(asset_type_name.into(), Box::new(mapper));
+            .insert(mapper.type_tag().to_owned(), Box::new(mapper));
+        self
+    }
+    /// Adds every mapper in the registry under its type tag. See [register_mapper].
+    pub fn with_registered_mappers(mut self) -> Self {
+        for (type_tag, mapper) in registered_mappers() {
+            self.mappers.insert(type_tag, mapper);
+        }
         self
     }
     /// Sets the output directory. Data will be serialized to out_dir/data.bin.
@@ -45,27 +67,136 @@ impl<'a> Serializer<'a> {
         self.asset_directory = Some(path.into());
         self
     }
+    /// Sets the number of threads used to bake assets. 1 bakes one asset at a time on a single worker thread.
+    /// Defaults to one thread per core. Ignored without the `parallel` feature or on wasm32.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+    /// Bakes with the options of a [BakeProfile], e.g. [BakeProfile::web].
+    /// Without one, the profile named by the [PROFILE_ENV_VAR] environment variable is used, if any.
+    /// Otherwise each mapper uses its own defaults.
+    pub fn with_profile(mut self, profile: BakeProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+    /// Determines if a human-readable dump of the baked data is written to out_path/data.json, for debugging.
+    /// Structured raw assets are written as JSON, and byte buffers as [sundile_graphics::BlobSummary]s.
+    /// The dump can be read back with [Deserializer::deserialize_dump].
+    #[cfg(feature = "dump")]
+    pub fn with_dump(mut self, enabled: bool) -> Self {
+        self.dump = enabled;
+        self
+    }
+    /// Lints the asset directory before baking. Issues are logged, and [Serializer::serialize] panics if any [LintLevel::Deny] rule is broken.
+    #[cfg(feature = "lint")]
+    pub fn with_lint(mut self, rules: LintRules) -> Self {
+        self.lint = Some(rules);
+        self
+    }
+    /// Writes the lint report to the given path as JSON, whether or not linting passes.
+    #[cfg(feature = "lint")]
+    pub fn with_lint_report<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.lint_report = Some(path.into());
+        self
+    }
     /// Iterates over the given compilers, loads and serializes the data, outputs that data to out_path/data.bin, and returns the binary.
+    /// The binary is a [Pack], which records each mapper's [RawAssetMapper::schema] version.
     // TODO: Should this function be responsible for caching or should we shunt that to the individual asset compilers?
     pub fn serialize(self) -> Vec<u8> {
         info!("Serializing assets...");
 
-        let out_path = self.out_path.unwrap_or("./".into()).join("data.bin");
+        let out_dir = self.out_path.unwrap_or("./".into());
+        let out_path = out_dir.join("data.bin");
 
         let in_path = self.asset_directory.unwrap_or("./assets/".into());
 
-        let mut out_map = BincodeAssetTypeMap::new();
-        for (name, mut mapper) in self.mappers {
+        #[cfg(feature = "lint")]
+        if let Some(rules) = &self.lint {
+            let mappers = self
+                .mappers
+                .values()
+                .map(|mapper| mapper.as_ref() as &dyn RawAssetMapper);
+            let report = rules
+                .check(&in_path, mappers)
+                .expect("Unable to lint assets");
+            report.log();
+            if let Some(path) = &self.lint_report {
+                report
+                    .write_json(path)
+                    .expect("Unable to write lint report");
+            }
+            if report.has_errors() {
+                panic!(
+                    "Asset lint failed with {} errors and {} warnings",
+                    report.errors().count(),
+                    report.warnings().count()
+                );
+            }
+        }
+
+        let profile = self.profile.or_else(|| {
+            let name = std::env::var(PROFILE_ENV_VAR).ok()?;
+            let profile = BakeProfile::named(&name);
+            if profile.is_none() {
+                warn!("Unknown bake profile `{}` in {}", name, PROFILE_ENV_VAR);
+            }
+            profile
+        });
+        if let Some(profile) = &profile {
+            info!("Baking with the `{}` profile", profile.name);
+        }
+
+        let mappers = self.mappers.into_iter().collect::<Vec<_>>();
+        #[cfg(feature = "dump")]
+        let dump = std::sync::Mutex::new(DumpAssetTypeMap::new());
+        let bake = |(name, mut mapper): (String, Box<dyn RawAssetMapper + Send + 'a>)| {
+            if let Some(profile) = &profile {
+                mapper.apply_profile(profile);
+            }
             mapper.load(&in_path);
+            #[cfg(feature = "dump")]
+            let dump_map = self.dump.then(|| mapper.dump());
+            let version = mapper.schema().version;
             let bin_map = mapper.to_bin_map();
-            out_map.insert(name.to_owned(), bin_map);
+            #[cfg(feature = "dump")]
+            if let Some(dump_map) = dump_map {
+                let dump_map = dump_map.unwrap_or_else(|| blob_dump(&bin_map));
+                dump.lock().unwrap().insert(name.clone(), dump_map);
+            }
+            (name, version, bin_map)
+        };
+
+        #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+        let baked = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads.unwrap_or(0))
+            .build()
+            .expect("Unable to create thread pool")
+            .install(|| crate::util::par_map(mappers, bake));
+        #[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+        let baked = crate::util::par_map(mappers, bake);
+
+        let mut pack = Pack::default();
+        for (name, version, bin_map) in baked {
+            pack.versions.insert(name.clone(), version);
+            pack.maps.insert(name, bin_map);
+        }
+        let bin = pack.to_bin();
+
+        #[cfg(feature = "dump")]
+        if self.dump {
+            let json = serde_json::to_string_pretty(&dump.into_inner().unwrap())
+                .expect("Unable to serialize dump");
+            std::fs::write(out_dir.join("data.json"), json).expect("Unable to write dump");
         }
-        let bin = bincode::serialize(&out_map).expect("Unable to serialize RawAssetTypeMap");
 
         use std::io::Write;
         std::fs::File::create(out_path)
             .expect("Unable to create file at out_path")
-            .write(&bin[..])
+            .write_all(&bin[..])
             .expect("Unable to write to bin");
 
         info!("...Done!");
@@ -73,14 +204,10 @@ impl<'a> Serializer<'a> {
     }
 }
 
+/// Uses every registered mapper. See [register_mapper].
 impl<'a> Default for Serializer<'a> {
     fn default() -> Self {
-        Self::new()
-            .with_mapper("shaders", types::shaders::Mapper::new())
-            .with_mapper("models", types::models::Mapper::new())
-            .with_mapper("textures", types::textures::Mapper::new())
-            .with_mapper("fonts", types::fonts::Mapper::new())
-        //etc
+        Self::new().with_registered_mappers()
     }
 }
 
@@ -88,30 +215,44 @@ impl<'a> Default for Serializer<'a> {
 pub struct Deserializer<'a> {
     mappers: HashMap<String, Box<dyn RawAssetMapper + 'a>>,
     panic: bool,
+    fallback_mode: Option<FallbackMode>,
 }
 impl<'a> Deserializer<'a> {
     /// Creates a new deserializer with default rules.
     pub fn new() -> Self {
         Self {
             mappers: HashMap::new(),
-            panic: true,
+            panic: false,
+            fallback_mode: None,
         }
     }
-    /// Adds an asset map to be deserialized.
-    pub fn with_mapper<S>(mut self, asset_type_name: S, mapper: impl RawAssetMapper + 'a) -> Self
-    where
-        S: Into<String>,
-    {
+    /// Adds an asset map to be deserialized, under its [RawAssetMapper::type_tag].
+    pub fn with_mapper(mut self, mapper: impl RawAssetMapper + 'a) -> Self {
         self.mappers
-            .insert(asset_type_name.into(), Box::new(mapper));
+            .insert(mapper.type_tag().to_owned(), Box::new(mapper));
+        self
+    }
+    /// Adds every mapper in the registry under its type tag. See [register_mapper].
+    pub fn with_registered_mappers(mut self) -> Self {
+        for (type_tag, mapper) in registered_mappers() {
+            self.mappers.insert(type_tag, mapper);
+        }
         self
     }
     /// Determines if [Deserializer::deserialize] will panic if it cannot convert all available data.
+    /// Otherwise, asset types without a mapper are logged and skipped. Disabled by default.
     pub fn with_panic(mut self, enabled: bool) -> Self {
         self.panic = enabled;
         self
     }
+    /// Sets what happens to assets which fail to build, and the fallback mode of the deserialized [AssetTypeMap].
+    /// Defaults to [FallbackMode::default].
+    pub fn with_fallback_mode(mut self, mode: FallbackMode) -> Self {
+        self.fallback_mode = Some(mode);
+        self
+    }
     /// Parses the bin. May panic if it cannot parse the binary into an AssetTypeMap or if no mapper exists for an asset type within that binary.
+    /// Raw assets baked with an older schema are migrated first; this panics if a mapper has no migration path from that schema.
     pub fn deserialize<'f, BuilderType>(
         self,
         bin: &[u8],
@@ -121,37 +262,115 @@ impl<'a> Deserializer<'a> {
         &'f BuilderType: Into<AssetBuildTarget<'f>>,
     {
         info!("Deserializing assets...");
-        let builder = asset_builder.into();
-        let mut map_in =
-            bincode::deserialize::<BincodeAssetTypeMap>(bin).expect("Unable to read bin!");
+        let Pack { versions, maps } =
+            Pack::from_bin(bin).unwrap_or_else(|e| panic!("Unable to read bin! {}", e));
+        let result = self.build(maps, asset_builder, |name, mapper, bin_map| {
+            let from = versions.get(name).copied().unwrap_or(0);
+            let bin_map = mapper.schema().migrate(name, from, bin_map)?;
+            mapper.load_bin_map(bin_map);
+            Ok::<_, MigrationError>(())
+        });
+        let map_out = result.unwrap_or_else(|e| panic!("Unable to load bin map. {}", e));
+        info!("...Done!");
+        map_out
+    }
+
+    /// Migrates a pack baked with older schemas to the current schema of each mapper, without building any assets.
+    /// Asset types without a mapper are kept as they are.
+    pub fn migrate(self, bin: &[u8]) -> Result<Vec<u8>, MigrationError> {
+        let mut pack = Pack::from_bin(bin)?;
+        for (name, mapper) in &self.mappers {
+            if let Some(bin_map) = pack.maps.remove(name) {
+                let schema = mapper.schema();
+                let bin_map = schema.migrate(name, pack.version(name), bin_map)?;
+                pack.versions.insert(name.clone(), schema.version);
+                pack.maps.insert(name.clone(), bin_map);
+            }
+        }
+        Ok(pack.to_bin())
+    }
+
+    /// Parses a dump written with [Serializer::with_dump] and builds its assets, as [Deserializer::deserialize] does for the bin.
+    #[cfg(feature = "dump")]
+    pub fn deserialize_dump<'f, BuilderType>(
+        self,
+        dump: &str,
+        asset_builder: &'f BuilderType,
+    ) -> Result<AssetTypeMap, DumpError>
+    where
+        &'f BuilderType: Into<AssetBuildTarget<'f>>,
+    {
+        let map_in = serde_json::from_str::<DumpAssetTypeMap>(dump)?;
+        self.build(map_in, asset_builder, |_, mapper, dump_map| {
+            mapper.load_dump_map(dump_map)
+        })
+    }
+
+    /// Converts a dump written with [Serializer::with_dump] back into the bin written alongside it, without building any assets.
+    #[cfg(feature = "dump")]
+    pub fn dump_to_bin(self, dump: &str) -> Result<Vec<u8>, DumpError> {
+        let mut map_in = serde_json::from_str::<DumpAssetTypeMap>(dump)?;
+        let mut pack = Pack::default();
+        for (name, mut mapper) in self.mappers {
+            if let Some(dump_map) = map_in.remove(&name) {
+                mapper.load_dump_map(dump_map)?;
+                pack.versions.insert(name.clone(), mapper.schema().version);
+                pack.maps.insert(name, mapper.to_bin_map());
+            }
+        }
+        if !map_in.is_empty() {
+            skip_unknown(map_in.keys(), self.panic);
+        }
+        Ok(pack.to_bin())
+    }
+
+    /// Loads each mapper's data from `map_in` and builds the assets.
+    fn build<'f, BuilderType, T, E, F>(
+        self,
+        mut map_in: BTreeMap<String, T>,
+        asset_builder: &'f BuilderType,
+        load: F,
+    ) -> Result<AssetTypeMap, E>
+    where
+        &'f BuilderType: Into<AssetBuildTarget<'f>>,
+        F: Fn(&str, &mut dyn RawAssetMapper, T) -> Result<(), E>,
+    {
+        let mut builder: AssetBuildTarget = asset_builder.into();
+        if let Some(mode) = self.fallback_mode {
+            builder.fallback_mode = mode;
+        }
         let mut map_out = AssetTypeMap::new();
+        map_out.set_fallback_mode(builder.fallback_mode);
 
         for (name, mut mapper) in self.mappers {
-            let bin_map = match map_in.remove(&name) {
-                Some(bin_map) => bin_map,
+            let data = match map_in.remove(&name) {
+                Some(data) => data,
                 None => continue,
             };
-            mapper.load_bin_map(bin_map);
+            load(&name, mapper.as_mut(), data)?;
             map_out.insert_asset_map(mapper.to_asset_map(&builder));
         }
 
-        if !map_in.is_empty() && self.panic {
-            panic!("Binary not fully read.")
+        if !map_in.is_empty() {
+            skip_unknown(map_in.keys(), self.panic);
         }
+        Ok(map_out)
+    }
+}
 
-        info!("...Done!");
-        map_out
+/// Lists asset types which have no mapper, and panics if `panic` is set.
+fn skip_unknown<'s>(type_tags: impl Iterator<Item = &'s String>, panic: bool) {
+    let type_tags = type_tags.map(String::as_str).collect::<Vec<_>>().join(", ");
+    match panic {
+        true => panic!("Binary not fully read. No mapper for: {}", type_tags),
+        false => warn!("Skipping asset types with no mapper: {}", type_tags),
     }
 }
 
+/// Uses every registered mapper. See [register_mapper].
 impl<'a> Default for Deserializer<'a> {
     fn default() -> Self {
-        Self::new()
-            .with_mapper("shaders", types::shaders::Mapper::new())
-            .with_mapper("models", types::models::Mapper::new())
-            .with_mapper("textures", types::textures::Mapper::new())
-            .with_mapper("fonts", types::fonts::Mapper::new())
-        //etc
+        Self::new().with_registered_mappers()
     }
 }
 
@@ -0,0 +1,188 @@
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::*;
+use thiserror::Error;
+
+use crate::*;
+
+/// File extensions picked up by [Mapper], in load order.
+pub const EXTENSIONS: [&str; 3] = ["ron", "json", "toml"];
+
+/// Error type for structured data assets.
+#[derive(Error, Debug)]
+pub enum DataError {
+    #[error("{path}:{line}:{column}: {message}")]
+    Parse {
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    #[error("{0}: unsupported data format")]
+    UnsupportedFormat(String),
+    #[error("{path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+}
+
+/// Text formats a data asset may be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    Ron,
+    Json,
+    Toml,
+}
+impl DataFormat {
+    /// Determines the format from a file extension, e.g. "ron".
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ron" => Some(Self::Ron),
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a data asset from a string. `path` is only used for error reporting.
+pub fn from_str<T>(source: &str, format: DataFormat, path: &Path) -> Result<T, DataError>
+where
+    T: DeserializeOwned,
+{
+    let path = path.display().to_string();
+    match format {
+        DataFormat::Ron => ron::from_str(source).map_err(|e| DataError::Parse {
+            path,
+            line: e.position.line,
+            column: e.position.col,
+            message: e.code.to_string(),
+        }),
+        DataFormat::Json => serde_json::from_str(source).map_err(|e| DataError::Parse {
+            path,
+            line: e.line(),
+            column: e.column(),
+            message: e.to_string(),
+        }),
+        DataFormat::Toml => toml::from_str(source).map_err(|e| {
+            let (line, column) = e
+                .span()
+                .map(|span| line_col(source, span.start))
+                .unwrap_or((0, 0));
+            DataError::Parse {
+                path,
+                line,
+                column,
+                message: e.message().to_string(),
+            }
+        }),
+    }
+}
+
+/// Reads and parses a data asset, choosing the format from the file extension.
+pub fn load_file<T>(path: &Path) -> Result<T, DataError>
+where
+    T: DeserializeOwned,
+{
+    let format = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(DataFormat::from_extension)
+        .ok_or_else(|| DataError::UnsupportedFormat(path.display().to_string()))?;
+    let source = std::fs::read_to_string(path).map_err(|source| DataError::Io {
+        path: path.display().to_string(),
+        source,
+    })?;
+    from_str(&source, format, path)
+}
+
+/// Converts a byte offset into a 1-based (line, column) pair.
+fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+    (line, column)
+}
+
+/// Generic mapper for structured game data such as tuning values, enemy stats or level layouts.
+/// Loads every .ron, .json and .toml file in asset_dir/subdir, stores them as bincode and inserts them into the [AssetTypeMap] as `T`.
+/// Since the data is stored with bincode, `T` should not rely on `deserialize_any` (e.g. untagged enums or `#[serde(flatten)]`).
+/// The subdirectory is also the mapper's type tag:
+/// ```ignore
+/// register_mapper(|| types::data::Mapper::<EnemyStats>::new("enemies"));
+/// ```
+pub struct Mapper<T> {
+    subdir: String,
+    map: HashMap<String, T>,
+}
+impl<T> Mapper<T> {
+    /// Creates a new mapper that loads from asset_dir/subdir.
+    pub fn new<S>(subdir: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            subdir: subdir.into(),
+            map: HashMap::new(),
+        }
+    }
+}
+
+impl<T> RawAssetMapper for Mapper<T>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    fn type_tag(&self) -> &str {
+        &self.subdir
+    }
+    fn extensions(&self) -> &[&str] {
+        &EXTENSIONS
+    }
+    /// Parses every data file in the subdirectory. Panics with the file and line of the first schema error.
+    fn load(&mut self, asset_dir: &PathBuf) {
+        let dir = asset_dir.join(&self.subdir);
+        for ext in EXTENSIONS {
+            let mut paths = crate::util::find_ext_recursive(&dir, ext)
+                .unwrap_or_else(|e| panic!("Failed to traverse {}: {}", dir.display(), e));
+            paths.sort();
+            let loaded = crate::util::par_map(paths, |path| {
+                let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+                (name, load_file(&path))
+            });
+            for (name, value) in loaded {
+                let value = value.unwrap_or_else(|e| panic!("{}", e));
+                if self.map.insert(name.clone(), value).is_some() {
+                    warn!("Data asset `{}` defined more than once in {}", name, dir.display());
+                }
+            }
+        }
+    }
+    fn to_asset_map(self: Box<Self>, _: &AssetBuildTarget) -> AssetMap {
+        AssetMap::from_map(self.map)
+    }
+    fn load_bin_map(&mut self, bin_map: BincodeAssetMap) {
+        self.map.clear();
+        for (name, vec) in bin_map {
+            let data = bincode::deserialize(&vec[..]).expect("Unable to deserialize!");
+            self.map.insert(name, data);
+        }
+    }
+    fn to_bin_map(self: Box<Self>) -> BincodeAssetMap {
+        let mut out = BincodeAssetMap::new();
+        for (name, data) in self.map {
+            out.insert(name, bincode::serialize(&data).expect("Unable to serialize!"));
+        }
+        out
+    }
+    #[cfg(feature = "dump")]
+    fn dump(&self) -> Option<DumpAssetMap> {
+        crate::util::generic_dump(&self.map)
+    }
+    #[cfg(feature = "dump")]
+    fn load_dump_map(&mut self, dump: DumpAssetMap) -> Result<(), DumpError> {
+        crate::util::generic_load_dump_map(&mut self.map, dump)
+    }
+}
@@ -32,6 +32,12 @@ impl Mapper {
 }
 
 impl RawAssetMapper for Mapper {
+    fn type_tag(&self) -> &str {
+        "fonts"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["ttf"]
+    }
     fn load(&mut self, asset_dir: &PathBuf) {
         crate::util::generic_load::<Font, Font>(&mut self.map, asset_dir, "fonts", "ttf");
     }
@@ -44,4 +50,12 @@ impl RawAssetMapper for Mapper {
     fn to_bin_map(self: Box<Self>) -> BincodeAssetMap {
         crate::util::generic_to_bin_map::<Font, Font>(self.map)
     }
+    #[cfg(feature = "dump")]
+    fn dump(&self) -> Option<DumpAssetMap> {
+        crate::util::generic_dump(&self.map)
+    }
+    #[cfg(feature = "dump")]
+    fn load_dump_map(&mut self, dump: DumpAssetMap) -> Result<(), DumpError> {
+        crate::util::generic_load_dump_map(&mut self.map, dump)
+    }
 }
\ No newline at end of file
@@ -1,8 +1,12 @@
 // pub mod audio;
+#[cfg(feature = "data")]
+pub mod data;
 #[cfg(feature = "fonts")]
 pub mod fonts;
 #[cfg(feature = "models")]
 pub mod models;
+#[cfg(feature = "scenes")]
+pub mod scenes;
 #[cfg(feature = "shaders")]
 pub mod shaders;
 #[cfg(feature = "text")]
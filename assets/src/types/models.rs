@@ -1,10 +1,11 @@
 use serde::*;
-use std::fs::*;
-use std::io::Read;
+use std::collections::HashMap;
 use std::path::*;
 use std::rc::Rc;
 
+use crate::types::textures::process_texture;
 use crate::*;
+use log::warn;
 use sundile_graphics::*;
 
 #[derive(Serialize, Deserialize)]
@@ -25,58 +26,22 @@ impl RawAsset<Model> for ModelData {
         )
         .expect("Failed to load .obj");
 
-        let obj_materials = obj_materials.expect("Failed to unwrap materials");
+        let obj_materials = obj_materials.unwrap_or_else(|e| {
+            warn!("Unable to load materials for `{}`. {}", path.display(), e);
+            vec![]
+        });
 
         let dir = path.parent().unwrap();
-        let mut material_builders = Vec::new();
-        for mat in obj_materials {
-            //TODO: Probably compress these.
-            //FIXME: This only takes diffuse and normal textures.
-            //There are so many other kinds of texture.
-            //Fix this so that it can deal with all of them.
-            let mut diffuse_texture = Vec::<u8>::new();
-            let mut file = File::open(dir.join(mat.diffuse_texture)).unwrap();
-            file.read_to_end(&mut diffuse_texture).unwrap();
-
-            let mut normal_texture = Vec::<u8>::new();
-            let mut file = File::open(dir.join(mat.normal_texture)).unwrap();
-            file.read_to_end(&mut normal_texture).unwrap();
-
-            material_builders.push(MaterialBuilder::new(
-                Some(mat.name),
-                diffuse_texture,
-                normal_texture,
-            ));
-        }
-
-        let mut mesh_builders = Vec::new();
-        for m in obj_models {
-            let mut vertices = Vec::new();
-            for i in 0..m.mesh.positions.len() / 3 {
-                vertices.push(ModelVertex {
-                    position: [
-                        m.mesh.positions[i * 3],
-                        m.mesh.positions[i * 3 + 1],
-                        m.mesh.positions[i * 3 + 2],
-                    ],
-                    tex_coords: [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]],
-                    normal: [
-                        m.mesh.normals[i * 3],
-                        m.mesh.normals[i * 3 + 1],
-                        m.mesh.normals[i * 3 + 2],
-                    ],
-                    tangent: [0.0; 3], //calculated below
-                    bitangent: [0.0; 3],
-                });
-            }
+        //TODO: Probably compress these.
+        let material_builders = obj_materials
+            .iter()
+            .map(|mat| MaterialBuilder::from_obj(mat, dir))
+            .collect();
 
-            mesh_builders.push(
-                MeshBuilder::new(vertices, m.mesh.indices)
-                    .with_material_id(m.mesh.material_id.unwrap_or(0))
-                    .with_name(m.name)
-                    .with_tangents(true),
-            );
-        }
+        let mesh_builders = obj_models
+            .iter()
+            .map(|m| MeshBuilder::from_obj(m.name.clone(), &m.mesh).optimize())
+            .collect();
 
         Self {
             mesh_builders,
@@ -88,9 +53,21 @@ impl RawAsset<Model> for ModelData {
         let (device, queue, texture_layout) =
             (builder.device, builder.queue, builder.texture_layout);
 
+        let fallback_mode = builder.fallback_mode;
         let mut materials = vec![];
         for builder in self.material_builders {
-            materials.push(Rc::new(builder.build(device, queue, texture_layout)));
+            match builder.build_with_fallback(device, queue, texture_layout, fallback_mode) {
+                Ok(material) => materials.push(Rc::new(material)),
+                Err(e) => panic!("Unable to create material! {}", e),
+            }
+        }
+        if materials.is_empty() {
+            materials.push(Rc::new(Material::untextured(
+                MaterialFactors::default(),
+                device,
+                queue,
+                texture_layout,
+            )));
         }
 
         let mut meshes = vec![];
@@ -106,19 +83,307 @@ impl RawAsset<Model> for ModelData {
     }
 }
 
-pub type Mapper = std::collections::HashMap<String, ModelData>;
+/// Layouts of [ModelData] from schema version 0. See [Mapper::schema].
+/// Copied rather than imported, so changes to the live types can't break decoding of old packs.
+mod v0 {
+    use serde::*;
+
+    /// The original layout, before material colors, mesh bounds and levels of detail.
+    #[derive(Deserialize)]
+    pub struct ModelData {
+        pub material_builders: Vec<MaterialBuilder>,
+        pub mesh_builders: Vec<MeshBuilder>,
+    }
+    #[derive(Deserialize)]
+    pub struct MaterialBuilder {
+        pub diffuse_texture: Vec<u8>,
+        pub normal_texture: Vec<u8>,
+        pub label: Option<String>,
+    }
+    #[derive(Deserialize)]
+    pub struct MeshBuilder {
+        pub vertices: Vec<ModelVertex>,
+        pub indices: Vec<u32>,
+        pub name: Option<String>,
+        pub material_id: Option<usize>,
+        pub calculate_tangents: bool,
+    }
+    /// Also used by version 1.
+    #[derive(Serialize, Deserialize)]
+    pub struct ModelVertex {
+        pub position: [f32; 3],
+        pub tex_coords: [f32; 2],
+        pub normal: [f32; 3],
+        pub tangent: [f32; 3],
+        pub bitangent: [f32; 3],
+    }
+}
+
+/// Layouts of [ModelData] from schema version 1, before physically based materials replaced Blinn-Phong colors.
+mod v1 {
+    use super::v0::ModelVertex;
+    use serde::*;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct ModelData {
+        pub material_builders: Vec<MaterialBuilder>,
+        pub mesh_builders: Vec<MeshBuilder>,
+    }
+    #[derive(Serialize, Deserialize)]
+    pub struct MaterialBuilder {
+        #[serde(with = "sundile_graphics::blob::option")]
+        pub diffuse_texture: Option<Vec<u8>>,
+        #[serde(with = "sundile_graphics::blob::option")]
+        pub normal_texture: Option<Vec<u8>>,
+        pub colors: MaterialColors,
+        pub label: Option<String>,
+    }
+    #[derive(Serialize, Deserialize)]
+    pub struct MaterialColors {
+        pub ambient: [f32; 3],
+        pub diffuse: [f32; 3],
+        pub specular: [f32; 3],
+        pub shininess: f32,
+    }
+    #[derive(Serialize, Deserialize)]
+    pub struct MeshBuilder {
+        pub vertices: Vec<ModelVertex>,
+        pub indices: Vec<u32>,
+        pub name: Option<String>,
+        pub material_id: Option<usize>,
+        pub calculate_tangents: bool,
+        pub bounds: Option<MeshBounds>,
+        pub lods: Vec<MeshLodData>,
+    }
+    #[derive(Serialize, Deserialize)]
+    pub struct MeshBounds {
+        pub aabb: Aabb,
+        pub sphere: BoundingSphere,
+    }
+    #[derive(Serialize, Deserialize)]
+    pub struct Aabb {
+        pub min: [f32; 3],
+        pub max: [f32; 3],
+    }
+    #[derive(Serialize, Deserialize)]
+    pub struct BoundingSphere {
+        pub center: [f32; 3],
+        pub radius: f32,
+    }
+    #[derive(Serialize, Deserialize)]
+    pub struct MeshLodData {
+        pub indices: Vec<u32>,
+        pub error: f32,
+    }
+}
+
+/// Materials get white colors. Bounds are computed when the meshes are generated, and levels of detail are not generated.
+impl From<v0::ModelData> for v1::ModelData {
+    fn from(old: v0::ModelData) -> Self {
+        let material_builders = old
+            .material_builders
+            .into_iter()
+            .map(|mat| v1::MaterialBuilder {
+                diffuse_texture: Some(mat.diffuse_texture),
+                normal_texture: Some(mat.normal_texture),
+                colors: v1::MaterialColors {
+                    ambient: [1.0; 3],
+                    diffuse: [1.0; 3],
+                    specular: [1.0; 3],
+                    shininess: 32.0,
+                },
+                label: mat.label,
+            })
+            .collect();
+        let mesh_builders = old
+            .mesh_builders
+            .into_iter()
+            .map(|mesh| v1::MeshBuilder {
+                vertices: mesh.vertices,
+                indices: mesh.indices,
+                name: mesh.name,
+                material_id: mesh.material_id,
+                calculate_tangents: mesh.calculate_tangents,
+                bounds: None,
+                lods: vec![],
+            })
+            .collect();
+        Self {
+            material_builders,
+            mesh_builders,
+        }
+    }
+}
+
+/// Blinn-Phong colors are approximated with [MaterialFactors::from_blinn_phong]. Meshes keep their bounds and levels of detail.
+impl From<v1::ModelData> for ModelData {
+    fn from(old: v1::ModelData) -> Self {
+        let material_builders = old
+            .material_builders
+            .into_iter()
+            .map(|mat| {
+                MaterialBuilder::new(mat.label)
+                    .with_base_color_texture(mat.diffuse_texture)
+                    .with_normal_texture(mat.normal_texture)
+                    .with_factors(MaterialFactors::from_blinn_phong(
+                        mat.colors.diffuse,
+                        mat.colors.shininess,
+                    ))
+            })
+            .collect();
+        let mesh_builders = old
+            .mesh_builders
+            .into_iter()
+            .map(|mesh| {
+                let vertices = mesh
+                    .vertices
+                    .into_iter()
+                    .map(|v| ModelVertex {
+                        position: v.position,
+                        tex_coords: v.tex_coords,
+                        normal: v.normal,
+                        tangent: v.tangent,
+                        bitangent: v.bitangent,
+                    })
+                    .collect();
+                let mut builder =
+                    MeshBuilder::new(vertices, mesh.indices).with_tangents(mesh.calculate_tangents);
+                if let Some(name) = mesh.name {
+                    builder = builder.with_name(name);
+                }
+                if let Some(material_id) = mesh.material_id {
+                    builder = builder.with_material_id(material_id);
+                }
+                if let Some(bounds) = mesh.bounds {
+                    builder = builder.with_bounds(MeshBounds {
+                        aabb: Aabb {
+                            min: bounds.aabb.min,
+                            max: bounds.aabb.max,
+                        },
+                        sphere: BoundingSphere {
+                            center: bounds.sphere.center,
+                            radius: bounds.sphere.radius,
+                        },
+                    });
+                }
+                builder.lods = mesh
+                    .lods
+                    .into_iter()
+                    .map(|lod| MeshLodData {
+                        indices: lod.indices,
+                        error: lod.error,
+                    })
+                    .collect();
+                builder
+            })
+            .collect();
+        Self {
+            material_builders,
+            mesh_builders,
+        }
+    }
+}
+
+pub struct Mapper {
+    map: HashMap<String, ModelData>,
+    lods: Option<LodSettings>,
+    max_texture_size: Option<u32>,
+    texture_compression: TextureCompression,
+}
+impl Mapper {
+    /// Creates a mapper which generates levels of detail with the default [LodSettings].
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            lods: Some(LodSettings::default()),
+            max_texture_size: None,
+            texture_compression: TextureCompression::Source,
+        }
+    }
+    /// Sets how levels of detail are generated when models are loaded from disk, or disables them with None.
+    pub fn with_lods(mut self, lods: Option<LodSettings>) -> Self {
+        self.lods = lods;
+        self
+    }
+}
 impl RawAssetMapper for Mapper {
+    fn type_tag(&self) -> &str {
+        "models"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["obj"]
+    }
+    /// Material textures are processed like the textures mapper's, with [process_texture].
     fn load(&mut self, asset_dir: &PathBuf) {
-        crate::util::generic_load(self, asset_dir, "models", "obj");
+        crate::util::generic_load(&mut self.map, asset_dir, "models", "obj");
+        let lods = self.lods;
+        let (max_size, compression) = (self.max_texture_size, self.texture_compression);
+        let process_textures = max_size.is_some() || compression != TextureCompression::Source;
+        if lods.is_none() && !process_textures {
+            return;
+        }
+        let mut models = self.map.drain().collect::<Vec<_>>();
+        models.sort_by(|(a, _), (b, _)| a.cmp(b));
+        self.map
+            .extend(crate::util::par_map(models, |(name, mut model)| {
+                if let Some(settings) = &lods {
+                    model.mesh_builders = model
+                        .mesh_builders
+                        .into_iter()
+                        .map(|mesh| mesh.generate_lods(settings))
+                        .collect();
+                }
+                if process_textures {
+                    model.material_builders = model
+                        .material_builders
+                        .into_iter()
+                        .map(|material| {
+                            // Empty textures couldn't be read, and are left for the placeholder.
+                            material.map_textures(|data| match data.is_empty() {
+                                true => data,
+                                false => process_texture(data, max_size, compression),
+                            })
+                        })
+                        .collect();
+                }
+                (name, model)
+            }));
     }
     fn to_asset_map(self: Box<Self>, builder: &AssetBuildTarget) -> AssetMap {
-        crate::util::generic_to_asset_map(*self, builder)
+        crate::util::generic_to_asset_map(self.map, builder)
     }
     fn load_bin_map(&mut self, bin_map: BincodeAssetMap) {
-        crate::util::generic_load_bin_map(self, bin_map);
+        crate::util::generic_load_bin_map(&mut self.map, bin_map);
     }
     fn to_bin_map(self: Box<Self>) -> BincodeAssetMap {
-        crate::util::generic_to_bin_map(*self)
+        crate::util::generic_to_bin_map(self.map)
+    }
+    fn apply_profile(&mut self, profile: &BakeProfile) {
+        self.lods = profile.lods;
+        self.max_texture_size = profile.max_texture_size;
+        self.texture_compression = profile.texture_compression;
+    }
+    #[cfg(feature = "dump")]
+    fn dump(&self) -> Option<DumpAssetMap> {
+        crate::util::generic_dump(&self.map)
+    }
+    #[cfg(feature = "dump")]
+    fn load_dump_map(&mut self, dump: DumpAssetMap) -> Result<(), DumpError> {
+        crate::util::generic_load_dump_map(&mut self.map, dump)
+    }
+    /// Version 1 added material colors, mesh bounds and levels of detail.
+    /// Version 2 replaced the colors with metallic-roughness [MaterialFactors], and the diffuse and normal textures
+    /// with base color, metallic-roughness, normal, occlusion and emissive texture slots.
+    fn schema(&self) -> Schema {
+        Schema::new(2)
+            .with_migration(0, |bytes| {
+                let old: v0::ModelData = bincode::deserialize(bytes)?;
+                bincode::serialize(&v1::ModelData::from(old))
+            })
+            .with_migration(1, |bytes| {
+                let old: v1::ModelData = bincode::deserialize(bytes)?;
+                bincode::serialize(&ModelData::from(old))
+            })
     }
 }
 
@@ -0,0 +1,207 @@
+use serde::*;
+use std::path::*;
+
+use sundile_graphics::{Light, ShadowSettings};
+
+use crate::types::data;
+use crate::*;
+
+/// A scene layout, written as a .ron, .json or .toml file in the `scenes` directory.
+/// Loaded by name with `Game::set_scene`, next to scenes defined as functions.
+/// ```ron
+/// (
+///     models: [(model: "cube", position: (0.0, 0.0, -5.0), rotation: (0.0, 45.0, 0.0), scale: (1.0, 2.0, 1.0), tint: (1.0, 0.5, 0.5, 1.0))],
+///     texts: [(text: "title", x: 0.5, y: 0.1, relative_position: true, font: Some((name: "bold", size: 48.0)))],
+///     lights: [
+///         (name: "sun", kind: Directional, direction: (0.0, -1.0, -0.5), color: (1.0, 1.0, 1.0, 1.0), shadows: Some((resolution: 2048))),
+///         (name: "lamp", position: (0.0, 2.0, -5.0), color: (1.0, 0.9, 0.8, 1.0), range: Some(10.0)),
+///     ],
+///     camera: Some((position: (0.0, 1.0, 0.0), yaw: -90.0, pitch: 0.0, fovy: Some(60.0))),
+///     ambient: Some((1.0, 1.0, 1.0, 0.1)),
+/// )
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SceneDescription {
+    #[serde(default)]
+    pub models: Vec<SceneModel>,
+    #[serde(default)]
+    pub texts: Vec<SceneText>,
+    /// Replace the renderer's lights when the scene is opened, if any are given.
+    #[serde(default)]
+    pub lights: Vec<SceneLight>,
+    #[serde(default)]
+    pub camera: Option<SceneCamera>,
+    /// Ambient color in RGBA, where A indicates strength.
+    #[serde(default)]
+    pub ambient: Option<[f32; 4]>,
+}
+
+/// An instance of a [sundile_graphics::Model] asset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneModel {
+    /// Name of the model asset.
+    pub model: String,
+    pub position: [f32; 3],
+    /// Euler angles about the X, Y and Z axes, in degrees.
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    #[serde(default = "SceneModel::default_scale")]
+    pub scale: [f32; 3],
+    /// RGBA color multiplied with the model's base color.
+    #[serde(default = "SceneModel::default_tint")]
+    pub tint: [f32; 4],
+    /// Custom per-instance data. See [sundile_graphics::ModelInstance::data].
+    #[serde(default)]
+    pub data: [f32; 4],
+}
+impl SceneModel {
+    fn default_scale() -> [f32; 3] {
+        [1.0; 3]
+    }
+    fn default_tint() -> [f32; 4] {
+        [1.0; 4]
+    }
+}
+
+/// An instance of a [sundile_graphics::TextBlock] asset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneText {
+    /// Name of the text asset.
+    pub text: String,
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub relative_position: bool,
+    #[serde(default)]
+    pub font: Option<SceneFont>,
+}
+
+/// Font used by a [SceneText].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneFont {
+    pub name: String,
+    pub size: f32,
+}
+
+/// A light. See [sundile_graphics::Light].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneLight {
+    /// Name of the light's handle in the renderer's scene lights.
+    pub name: String,
+    #[serde(default)]
+    pub kind: SceneLightKind,
+    #[serde(default)]
+    pub position: [f32; 3],
+    /// Used by directional and spot lights. Defaults to straight down.
+    #[serde(default = "SceneLight::default_direction")]
+    pub direction: [f32; 3],
+    /// RGBA color.
+    pub color: [f32; 4],
+    /// Distance at which the light fades out completely. Unlimited if None.
+    #[serde(default)]
+    pub range: Option<f32>,
+    /// Constant, linear and quadratic attenuation factors. No falloff if None.
+    #[serde(default)]
+    pub attenuation: Option<[f32; 3]>,
+    /// Casts shadows if set. Omitted settings take their defaults.
+    #[serde(default)]
+    pub shadows: Option<ShadowSettings>,
+}
+impl SceneLight {
+    fn default_direction() -> [f32; 3] {
+        [0.0, -1.0, 0.0]
+    }
+}
+impl From<&SceneLight> for Light {
+    fn from(light: &SceneLight) -> Self {
+        let mut result = match light.kind {
+            SceneLightKind::Directional => Light::directional(light.direction, light.color),
+            SceneLightKind::Point => Light::point(light.position, light.color),
+            SceneLightKind::Spot {
+                inner_angle,
+                outer_angle,
+            } => Light::spot(
+                light.position,
+                light.direction,
+                cgmath::Deg(inner_angle),
+                cgmath::Deg(outer_angle),
+                light.color,
+            ),
+        };
+        result.range = light.range;
+        result.shadows = light.shadows;
+        if let Some([constant, linear, quadratic]) = light.attenuation {
+            result = result.with_attenuation(constant, linear, quadratic);
+        }
+        result
+    }
+}
+
+/// How a [SceneLight] casts light. Angles are in degrees.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum SceneLightKind {
+    Directional,
+    #[default]
+    Point,
+    Spot {
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+/// Camera placement. Angles are in degrees.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneCamera {
+    pub position: [f32; 3],
+    #[serde(default)]
+    pub yaw: f32,
+    #[serde(default)]
+    pub pitch: f32,
+    /// Vertical field of view. Left unchanged if None.
+    #[serde(default)]
+    pub fovy: Option<f32>,
+}
+
+/// Mapper for scene files. Loads every .ron, .json and .toml file in asset_dir/scenes.
+/// Not registered by default, since it requires the directory to exist:
+/// ```ignore
+/// register_mapper(types::scenes::Mapper::new);
+/// ```
+pub struct Mapper {
+    inner: data::Mapper<SceneDescription>,
+}
+impl Mapper {
+    pub fn new() -> Self {
+        Self {
+            inner: data::Mapper::new("scenes"),
+        }
+    }
+}
+impl RawAssetMapper for Mapper {
+    fn type_tag(&self) -> &str {
+        self.inner.type_tag()
+    }
+    fn extensions(&self) -> &[&str] {
+        self.inner.extensions()
+    }
+    fn load(&mut self, asset_dir: &PathBuf) {
+        self.inner.load(asset_dir);
+    }
+    fn to_asset_map(self: Box<Self>, builder: &AssetBuildTarget) -> AssetMap {
+        Box::new(self.inner).to_asset_map(builder)
+    }
+    fn load_bin_map(&mut self, bin_map: BincodeAssetMap) {
+        self.inner.load_bin_map(bin_map);
+    }
+    fn to_bin_map(self: Box<Self>) -> BincodeAssetMap {
+        Box::new(self.inner).to_bin_map()
+    }
+    #[cfg(feature = "dump")]
+    fn dump(&self) -> Option<DumpAssetMap> {
+        self.inner.dump()
+    }
+    #[cfg(feature = "dump")]
+    fn load_dump_map(&mut self, dump: DumpAssetMap) -> Result<(), DumpError> {
+        self.inner.load_dump_map(dump)
+    }
+}
@@ -39,29 +39,76 @@ impl RawAsset<wgpu::ShaderModule> for ShaderData {
     }
 
     /// Converts the SPIR-V binary to a shader module.
+    /// Shaders which fail to compile are replaced with [sundile_graphics::error_shader], unless the builder is in [FallbackMode::Strict].
     fn to_asset(self, asset_builder: &AssetBuildTarget) -> wgpu::ShaderModule {
-        asset_builder
-            .device
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: None,
-                source: wgpu::ShaderSource::Wgsl(self.data.into()),
-            })
+        let device = asset_builder.device;
+        let descriptor = wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(self.data.into()),
+        };
+        // Error scopes resolve asynchronously on the web, so compile errors are only caught natively.
+        #[cfg(not(target_arch = "wasm32"))]
+        if asset_builder.fallback_mode.is_placeholder() {
+            device.push_error_scope(wgpu::ErrorFilter::Validation);
+            let module = device.create_shader_module(descriptor);
+            return match futures::executor::block_on(device.pop_error_scope()) {
+                None => module,
+                Some(e) => {
+                    use log::warn;
+                    warn!("Unable to compile shader; using the error shader. {}", e);
+                    sundile_graphics::error_shader(device)
+                }
+            };
+        }
+        device.create_shader_module(descriptor)
     }
 }
 
-pub type Mapper = HashMap<String, ShaderData>;
+pub struct Mapper {
+    map: HashMap<String, ShaderData>,
+    /// Shaders to keep when loading. Set by [BakeProfile::shaders].
+    include: Option<Vec<String>>,
+}
+impl Mapper {
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            include: None,
+        }
+    }
+}
 
 impl RawAssetMapper for Mapper {
+    fn type_tag(&self) -> &str {
+        "shaders"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["wgsl"]
+    }
     fn load(&mut self, asset_dir: &PathBuf) {
-        crate::util::generic_load(self, asset_dir, "shaders", "wgsl");
+        crate::util::generic_load(&mut self.map, asset_dir, "shaders", "wgsl");
+        if let Some(include) = &self.include {
+            self.map.retain(|name, _| include.contains(name));
+        }
     }
     fn to_asset_map(self: Box<Self>, builder: &AssetBuildTarget) -> AssetMap {
-        crate::util::generic_to_asset_map(*self, builder)
+        crate::util::generic_to_asset_map(self.map, builder)
     }
     fn load_bin_map(&mut self, bin_map: BincodeAssetMap) {
-        crate::util::generic_load_bin_map(self, bin_map);
+        crate::util::generic_load_bin_map(&mut self.map, bin_map);
     }
     fn to_bin_map(self: Box<Self>) -> BincodeAssetMap {
-        crate::util::generic_to_bin_map(*self)
+        crate::util::generic_to_bin_map(self.map)
+    }
+    fn apply_profile(&mut self, profile: &BakeProfile) {
+        self.include = profile.shaders.clone();
+    }
+    #[cfg(feature = "dump")]
+    fn dump(&self) -> Option<DumpAssetMap> {
+        crate::util::generic_dump(&self.map)
+    }
+    #[cfg(feature = "dump")]
+    fn load_dump_map(&mut self, dump: DumpAssetMap) -> Result<(), DumpError> {
+        crate::util::generic_load_dump_map(&mut self.map, dump)
     }
 }
@@ -6,6 +6,8 @@ use crate::*;
 use sundile_graphics::*;
 
 pub type TextureData = Vec<u8>;
+/// Quality of resized JPEG textures, out of 100.
+const JPEG_QUALITY: u8 = 90;
 
 impl RawAsset<TextureWrapper> for TextureData {
     /// Loads in the texture file as raw bytes.
@@ -17,25 +19,98 @@ impl RawAsset<TextureWrapper> for TextureData {
     }
 
     /// Creates a [TextureWrapper] from the serialized bytes.
+    /// Falls back to [TextureWrapper::placeholder] if the bytes cannot be decoded, unless the builder is in [FallbackMode::Strict].
     fn to_asset(self, asset_builder: &AssetBuildTarget) -> TextureWrapper {
-        TextureWrapper::from_bytes(asset_builder.device, asset_builder.queue, &self[..], "statically loaded texture", false).expect("Unable to create texture!")
+        match TextureWrapper::from_bytes(asset_builder.device, asset_builder.queue, &self[..], "statically loaded texture", false) {
+            Ok(texture) => texture,
+            Err(e) if asset_builder.fallback_mode.is_placeholder() => {
+                log::warn!("Unable to create texture; using a placeholder. {}", e);
+                TextureWrapper::placeholder(asset_builder.device, asset_builder.queue)
+            }
+            Err(e) => panic!("Unable to create texture! {}", e),
+        }
+    }
+}
+
+/// Scales a texture down to fit within `max_size` and re-encodes it as needed, keeping its source format. See [BakeProfile].
+/// Textures which cannot be decoded or encoded are returned unchanged, to be replaced by a placeholder when built if they're invalid.
+pub fn process_texture(data: TextureData, max_size: Option<u32>, compression: TextureCompression) -> TextureData {
+    use image::{codecs::jpeg, codecs::png, ImageEncoder, ImageFormat};
+
+    let format = image::guess_format(&data).ok();
+    let image = match image::load_from_memory(&data) {
+        Ok(image) => image,
+        Err(e) => {
+            log::warn!("Unable to decode texture for baking; keeping it as is. {}", e);
+            return data;
+        }
+    };
+    let resize = max_size.filter(|max| image.width() > *max || image.height() > *max);
+    // Only PNGs are recompressed; any other format would lose quality or grow.
+    let recompress = compression == TextureCompression::Png && format == Some(ImageFormat::Png);
+    if resize.is_none() && !recompress {
+        return data;
+    }
+    let image = match resize {
+        Some(max) => image.resize(max, max, image::imageops::FilterType::Triangle),
+        None => image,
+    };
+    let mut out = vec![];
+    let encoded = match format {
+        Some(ImageFormat::Jpeg) => jpeg::JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY)
+            .write_image(image.as_bytes(), image.width(), image.height(), image.color()),
+        _ => {
+            let compression = match compression {
+                TextureCompression::Source => png::CompressionType::Default,
+                TextureCompression::Png => png::CompressionType::Best,
+            };
+            png::PngEncoder::new_with_quality(&mut out, compression, png::FilterType::Adaptive)
+                .write_image(image.as_bytes(), image.width(), image.height(), image.color())
+        }
+    };
+    match encoded {
+        // Recompression without resizing is only worth keeping if it made the texture smaller.
+        Ok(()) if resize.is_some() || out.len() < data.len() => out,
+        Ok(()) => data,
+        Err(e) => {
+            log::warn!("Unable to encode texture for baking; keeping it as is. {}", e);
+            data
+        }
     }
 }
 
 ///TODO: Figure out a way to press this into a [sundile_graphics::TextureAtlas].
 pub struct Mapper {
-    map: HashMap<String, TextureData>
+    map: HashMap<String, TextureData>,
+    max_size: Option<u32>,
+    compression: TextureCompression,
 }
 impl Mapper {
     pub fn new() -> Self {
         Self {
-            map: HashMap::new()
+            map: HashMap::new(),
+            max_size: None,
+            compression: TextureCompression::Source,
         }
     }
 }
 impl RawAssetMapper for Mapper {
+    fn type_tag(&self) -> &str {
+        "textures"
+    }
+    fn extensions(&self) -> &[&str] {
+        &["png"]
+    }
     fn load(&mut self, asset_dir: &PathBuf) {
         crate::util::generic_load::<TextureData, TextureWrapper>(&mut self.map, asset_dir, "textures", "png");
+        if self.max_size.is_some() || self.compression != TextureCompression::Source {
+            let (max_size, compression) = (self.max_size, self.compression);
+            let mut textures = self.map.drain().collect::<Vec<_>>();
+            textures.sort_by(|(a, _), (b, _)| a.cmp(b));
+            self.map.extend(crate::util::par_map(textures, |(name, data)| {
+                (name, process_texture(data, max_size, compression))
+            }));
+        }
     }
     fn to_asset_map(self: Box<Self>, builder: &AssetBuildTarget) -> AssetMap {
         //TODO: Compress this into a TextureAtlas
@@ -47,4 +122,18 @@ impl RawAssetMapper for Mapper {
     fn to_bin_map(self: Box<Self>) -> BincodeAssetMap {
         crate::util::generic_to_bin_map::<TextureData, TextureWrapper>(self.map)
     }
+    fn apply_profile(&mut self, profile: &BakeProfile) {
+        self.max_size = profile.max_texture_size;
+        self.compression = profile.texture_compression;
+    }
+    /// Dumps the image files themselves, so that their format and dimensions are shown.
+    #[cfg(feature = "dump")]
+    fn dump(&self) -> Option<DumpAssetMap> {
+        Some(crate::blob_dump(&self.map))
+    }
+    #[cfg(feature = "dump")]
+    fn load_dump_map(&mut self, dump: DumpAssetMap) -> Result<(), DumpError> {
+        self.map = crate::load_blob_dump(dump)?.into_iter().collect();
+        Ok(())
+    }
 }
\ No newline at end of file
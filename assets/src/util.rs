@@ -1,4 +1,6 @@
 use crate::internal_types::*;
+#[cfg(feature = "dump")]
+use crate::{DumpAssetMap, DumpError};
 use serde::de::DeserializeOwned;
 use serde::*;
 use std::any::Any;
@@ -23,24 +25,47 @@ pub fn find_ext_recursive<'a>(
     Ok(res)
 }
 
+/// Applies `f` to every item, returning the results in the same order as the input.
+/// Items are processed in parallel if the `parallel` feature is enabled on native targets, and one by one otherwise.
+pub fn par_map<I, O, F>(items: Vec<I>, f: F) -> Vec<O>
+where
+    I: Send,
+    O: Send,
+    F: Fn(I) -> O + Send + Sync,
+{
+    #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+    {
+        use rayon::prelude::*;
+        items.into_par_iter().map(f).collect()
+    }
+    #[cfg(not(all(feature = "parallel", not(target_arch = "wasm32"))))]
+    {
+        items.into_iter().map(f).collect()
+    }
+}
+
 /// Generically implements [RawAssetMapper::load]
+/// Files are loaded in parallel where available, but are always inserted in sorted path order,
+/// so if two files share a name, the same one wins on every run.
 pub fn generic_load<'a, RawAssetType, AssetType>(
     mapper: &mut HashMap<String, RawAssetType>,
     asset_dir: &PathBuf,
     subdir: &'a str,
     ext: &'a str,
 ) where
-    RawAssetType: RawAsset<AssetType>,
+    RawAssetType: RawAsset<AssetType> + Send,
     AssetType: Any,
 {
     let mut path = asset_dir.to_owned();
     path.push(subdir);
-    for path in crate::util::find_ext_recursive(&path, ext)
-        .expect(format!("Failed to traverse {}", path.display()).as_str())
-    {
+    let mut paths = crate::util::find_ext_recursive(&path, ext)
+        .expect(format!("Failed to traverse {}", path.display()).as_str());
+    paths.sort();
+    let loaded = par_map(paths, |path| {
         let name = path.file_stem().unwrap().to_str().unwrap().to_string();
-        mapper.insert(name, RawAssetType::from_disk(&path));
-    }
+        (name, RawAssetType::from_disk(&path))
+    });
+    mapper.extend(loaded);
 }
 
 /// Generically implements [RawAssetMapper::to_asset_map]
@@ -92,3 +117,38 @@ where
     out
 }
 
+/// Generically implements [RawAssetMapper::dump]
+#[cfg(feature = "dump")]
+pub fn generic_dump<RawAssetType>(mapper: &HashMap<String, RawAssetType>) -> Option<DumpAssetMap>
+where
+    RawAssetType: Serialize,
+{
+    Some(
+        mapper
+            .iter()
+            .map(|(name, data)| {
+                let value = serde_json::to_value(data).expect("Unable to dump!");
+                (name.to_owned(), value)
+            })
+            .collect(),
+    )
+}
+
+/// Generically implements [RawAssetMapper::load_dump_map]
+#[cfg(feature = "dump")]
+pub fn generic_load_dump_map<RawAssetType>(
+    mapper: &mut HashMap<String, RawAssetType>,
+    dump: DumpAssetMap,
+) -> Result<(), DumpError>
+where
+    RawAssetType: DeserializeOwned,
+{
+    mapper.clear();
+    for (name, value) in dump {
+        match serde_json::from_value(value) {
+            Ok(data) => mapper.insert(name, data),
+            Err(source) => return Err(DumpError::Asset { name, source }),
+        };
+    }
+    Ok(())
+}
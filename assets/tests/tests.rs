@@ -79,3 +79,1290 @@ fn test_all() {
 }
 
 fn expect_model_ref(_: &Model) {}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct EnemyStats {
+    name: String,
+    health: u32,
+    speed: f32,
+}
+
+#[test]
+fn test_data() {
+    use std::path::PathBuf;
+    use types::data::*;
+
+    // Each supported format parses into the same schema.
+    let goblin: EnemyStats = load_file(&PathBuf::from("./tests/assets/data/goblin.ron")).unwrap();
+    assert_eq!(goblin.health, 12);
+    let orc: EnemyStats = load_file(&PathBuf::from("./tests/assets/data/orc.json")).unwrap();
+    assert_eq!(orc.name, "Orc");
+    let troll: EnemyStats = load_file(&PathBuf::from("./tests/assets/data/troll.toml")).unwrap();
+    assert_eq!(troll.speed, 0.5);
+
+    // Schema errors report the file and line.
+    let err = load_file::<EnemyStats>(&PathBuf::from("./tests/assets/data_invalid/imp.toml"))
+        .unwrap_err();
+    match &err {
+        DataError::Parse { path, line, .. } => {
+            assert!(path.ends_with("imp.toml"));
+            assert_eq!(*line, 2);
+        }
+        _ => panic!("Expected a parse error, got {err}"),
+    }
+
+    // Values survive the trip through bincode.
+    let mut mapper = Mapper::<EnemyStats>::new("data");
+    mapper.load(&PathBuf::from("./tests/assets"));
+    let bin_map = Box::new(mapper).to_bin_map();
+    assert_eq!(bin_map.len(), 3);
+    let decoded: EnemyStats = bincode::deserialize(&bin_map["goblin"][..]).unwrap();
+    assert_eq!(decoded, goblin);
+}
+
+#[test]
+fn test_scenes() {
+    use std::path::PathBuf;
+    use types::scenes::*;
+
+    let mut mapper = Mapper::new();
+    mapper.load(&PathBuf::from("./tests/assets"));
+    let bin_map = Box::new(mapper).to_bin_map();
+    let level: SceneDescription = bincode::deserialize(&bin_map["level"][..]).unwrap();
+
+    assert_eq!(level.models.len(), 2);
+    assert_eq!(level.models[0].rotation, [0.0, 45.0, 0.0]);
+    // Omitted fields take their defaults.
+    assert_eq!(level.models[1].rotation, [0.0; 3]);
+    assert_eq!(level.models[0].scale, [1.0; 3]);
+    assert_eq!(level.models[0].tint, [1.0; 4]);
+    assert_eq!(level.models[1].scale, [1.0, 0.5, 2.0]);
+    assert_eq!(level.models[1].data, [0.0; 4]);
+    assert_eq!(level.texts[0].font.as_ref().unwrap().name, "bold");
+    assert_eq!(level.lights[0].name, "sun");
+    assert_eq!(level.lights[0].kind, SceneLightKind::Point);
+    assert_eq!(level.lights[1].direction, [0.0, -1.0, 0.0]);
+    let spot = Light::from(&level.lights[1]);
+    assert_eq!(spot.range, Some(8.0));
+    assert_eq!(spot.attenuation, [1.0, 0.0, 0.0]);
+    let shadows = spot.shadows.unwrap();
+    assert_eq!(shadows.bias, 0.01);
+    assert_eq!(shadows.resolution, ShadowSettings::default().resolution);
+    match spot.kind {
+        LightKind::Spot { outer_angle, .. } => {
+            assert!((outer_angle.0 - 30f32.to_radians()).abs() < 1e-6)
+        }
+        kind => panic!("Expected a spot light, got {:?}", kind),
+    }
+    let camera = level.camera.unwrap();
+    assert_eq!(camera.pitch, 0.0);
+    assert_eq!(camera.fovy, Some(60.0));
+    assert_eq!(level.ambient, Some([1.0, 1.0, 1.0, 0.2]));
+}
+
+#[test]
+fn test_bake_profiles() {
+    use std::path::PathBuf;
+
+    let web = BakeProfile::named("web").unwrap();
+    assert_eq!(web, BakeProfile::web());
+    assert!(BakeProfile::named("console").is_none());
+
+    // Large textures are scaled down to fit, keeping their aspect ratio.
+    let mut png = vec![];
+    image::DynamicImage::new_rgba8(2048, 512)
+        .write_to(
+            &mut std::io::Cursor::new(&mut png),
+            image::ImageOutputFormat::Png,
+        )
+        .unwrap();
+    let baked = types::textures::process_texture(
+        png.clone(),
+        web.max_texture_size,
+        web.texture_compression,
+    );
+    let image = image::load_from_memory(&baked).unwrap();
+    assert_eq!((image.width(), image.height()), (1024, 256));
+    // Small textures are left alone without recompression.
+    let baked =
+        types::textures::process_texture(png.clone(), Some(4096), TextureCompression::Source);
+    assert_eq!(baked, png);
+    // JPEGs stay JPEGs, and are only re-encoded to resize them.
+    let mut jpeg = vec![];
+    image::DynamicImage::new_rgb8(2048, 512)
+        .write_to(
+            &mut std::io::Cursor::new(&mut jpeg),
+            image::ImageOutputFormat::Jpeg(90),
+        )
+        .unwrap();
+    let baked = types::textures::process_texture(
+        jpeg.clone(),
+        web.max_texture_size,
+        web.texture_compression,
+    );
+    assert_eq!(
+        image::guess_format(&baked).unwrap(),
+        image::ImageFormat::Jpeg
+    );
+    let baked = types::textures::process_texture(jpeg.clone(), None, web.texture_compression);
+    assert_eq!(baked, jpeg);
+
+    // Material textures embedded in models are scaled down too.
+    let dir = std::env::temp_dir().join("sundile_profile_models");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("models")).unwrap();
+    image::DynamicImage::new_rgb8(2048, 512)
+        .save(dir.join("models/quad-diffuse.jpg"))
+        .unwrap();
+    std::fs::write(
+        dir.join("models/quad.mtl"),
+        "newmtl quad\nmap_Kd quad-diffuse.jpg\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("models/quad.obj"),
+        "mtllib quad.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nusemtl quad\nf 1 2 3\n",
+    )
+    .unwrap();
+    let mut models = types::models::Mapper::new();
+    models.apply_profile(&web);
+    models.load(&dir);
+    let bin_map = Box::new(models).to_bin_map();
+    let model: types::models::ModelData = bincode::deserialize(&bin_map["quad"]).unwrap();
+    let mut sizes = vec![];
+    for material in model.material_builders {
+        material.map_textures(|data| {
+            let image = image::load_from_memory(&data).unwrap();
+            sizes.push((image.width(), image.height()));
+            assert_eq!(
+                image::guess_format(&data).unwrap(),
+                image::ImageFormat::Jpeg
+            );
+            data
+        });
+    }
+    assert_eq!(sizes, [(1024, 256)]);
+
+    // Shaders not listed in the profile are left out.
+    let profile = BakeProfile::new("no shaders").with_shaders(["missing"]);
+    let mut shaders = types::shaders::Mapper::new();
+    shaders.apply_profile(&profile);
+    shaders.load(&PathBuf::from("./tests/assets"));
+    assert!(Box::new(shaders).to_bin_map().is_empty());
+}
+
+#[test]
+fn test_lint() {
+    use std::path::PathBuf;
+    use types::data::*;
+
+    let dir = std::env::temp_dir().join("sundile_lint");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("textures")).unwrap();
+    std::fs::create_dir_all(dir.join("models")).unwrap();
+    for (path, width, height) in [
+        ("textures/wall.png", 64, 64),
+        ("textures/Big Sprite.png", 300, 100),
+        ("models/Quad Albedo.jpg", 300, 100),
+    ] {
+        image::DynamicImage::new_rgb8(width, height)
+            .save(dir.join(path))
+            .unwrap();
+    }
+    std::fs::write(
+        dir.join("models/quad.obj"),
+        "mtllib quad.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("models/quad.mtl"), "newmtl Quad\n").unwrap();
+
+    let rules = LintRules::new()
+        .with_max_texture_size(256, LintLevel::Deny)
+        .with_power_of_two(LintLevel::Warn)
+        .with_max_triangles(1, LintLevel::Warn)
+        .with_referenced(["wall", "quad"], LintLevel::Warn)
+        .with_naming(NamingConvention::SnakeCase, LintLevel::Warn);
+    let mappers: [&dyn RawAssetMapper; 2] = [
+        &types::textures::Mapper::new(),
+        &types::models::Mapper::new(),
+    ];
+    let report = rules.check(&dir, mappers).unwrap();
+    let kinds = |name: &str| {
+        report
+            .issues
+            .iter()
+            .filter(|issue| issue.name == name)
+            .map(|issue| issue.kind)
+            .collect::<Vec<_>>()
+    };
+    assert!(kinds("wall").is_empty());
+    assert_eq!(kinds("quad"), [LintKind::TriangleBudget]);
+    // Files which are only used by models are not assets, so only the texture rules apply to them.
+    assert_eq!(
+        kinds("Quad Albedo"),
+        [LintKind::TextureSize, LintKind::PowerOfTwo]
+    );
+    assert_eq!(
+        kinds("Big Sprite"),
+        [
+            LintKind::Unused,
+            LintKind::Naming,
+            LintKind::TextureSize,
+            LintKind::PowerOfTwo
+        ]
+    );
+    assert!(report.has_errors());
+    assert_eq!(report.errors().count(), 2);
+    assert_eq!(report.issues[0].asset_type, "models");
+
+    // The report round-trips through JSON, and rules can be read from data files.
+    let json: LintReport = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+    assert_eq!(json, report);
+    let rules_path = dir.join("lint.ron");
+    std::fs::write(&rules_path, "(max_triangles: Some((value: 1)))").unwrap();
+    let rules: LintRules = load_file(&PathBuf::from(&rules_path)).unwrap();
+    assert_eq!(rules.max_triangles.unwrap().level, LintLevel::Warn);
+    assert!(rules.naming.is_none());
+}
+
+#[test]
+fn test_dump() {
+    let out_path = std::env::temp_dir().join("sundile_dump");
+    std::fs::create_dir_all(&out_path).unwrap();
+    let bin = Serializer::default()
+        .with_mapper(types::data::Mapper::<EnemyStats>::new("data"))
+        .with_asset_directory("./tests/assets")
+        .with_out_path(&out_path)
+        .with_dump(true)
+        .serialize();
+    let json = std::fs::read_to_string(out_path.join("data.json")).unwrap();
+
+    // Structured assets are readable, and byte buffers are summarized.
+    let dump: DumpAssetTypeMap = serde_json::from_str(&json).unwrap();
+    assert_eq!(dump["data"]["goblin"]["health"], 12);
+    assert!(dump["shaders"]["passthrough"]["data"]
+        .as_str()
+        .unwrap()
+        .contains("fn "));
+    let texture: sundile_graphics::BlobSummary =
+        serde_json::from_value(dump["textures"]["blank"].clone()).unwrap();
+    assert!(texture.image.unwrap().starts_with("Png"));
+    assert_eq!(texture.hash.len(), 16);
+
+    // The dump reads back into the same bin.
+    let round_trip = Deserializer::default()
+        .with_mapper(types::data::Mapper::<EnemyStats>::new("data"))
+        .dump_to_bin(&json)
+        .unwrap();
+    assert_eq!(round_trip, bin);
+}
+
+#[derive(Debug)]
+struct Blob(Vec<u8>);
+impl AssetSize for Blob {
+    fn asset_size(&self) -> u64 {
+        self.0.len() as u64
+    }
+}
+
+#[test]
+fn test_memory() {
+    let mut map = AssetTypeMap::new();
+    map.register_asset_size::<Blob>();
+    map.try_insert_asset("a", Blob(vec![0; 100])).unwrap();
+    map.try_insert_asset("b", Blob(vec![0; 200])).unwrap();
+    map.try_insert_asset("c", Blob(vec![0; 300])).unwrap();
+    assert_eq!(map.memory_usage(), 600);
+    assert_eq!(map.try_asset_size::<Blob>("b").unwrap(), 200);
+    assert_eq!(map.memory_report()[0].name, "c");
+
+    // Referenced and pinned assets survive garbage collection.
+    let a = map.try_get_asset::<Blob>("a").unwrap();
+    map.try_pin_asset::<Blob>("b", true).unwrap();
+    let collected = map.collect_garbage();
+    assert_eq!(collected.len(), 1);
+    assert_eq!(collected[0].name, "c");
+    drop(a);
+
+    // Unloading works even while referenced.
+    let b = map.try_get_asset::<Blob>("b").unwrap();
+    map.try_unload_asset::<Blob>("b").unwrap();
+    assert_eq!(b.0.len(), 200);
+    assert_eq!(
+        map.try_get_asset::<Blob>("b").unwrap_err(),
+        AssetError::AssetNotFound("b".into())
+    );
+
+    // Least recently used assets are evicted first.
+    map.try_insert_asset("d", Blob(vec![0; 100])).unwrap();
+    map.try_insert_asset("e", Blob(vec![0; 100])).unwrap();
+    map.try_get_asset::<Blob>("a").unwrap();
+    map.set_memory_budget(Some(MemoryBudget::evict_lru(250)));
+    assert_eq!(map.memory_usage(), 200);
+    let mut remaining = map.try_list_assets::<Blob>().unwrap();
+    remaining.sort();
+    assert_eq!(remaining, vec!["a", "e"]);
+
+    // A warning budget never evicts.
+    map.set_memory_budget(Some(MemoryBudget::warn(0)));
+    map.try_insert_asset("f", Blob(vec![0; 100])).unwrap();
+    assert_eq!(map.memory_usage(), 300);
+
+    // The usage is kept up to date as assets are replaced and taken.
+    map.try_insert_asset("f", Blob(vec![0; 50])).unwrap();
+    map.try_take_asset::<Blob>("e").unwrap();
+    assert_eq!(map.memory_usage(), 150);
+    let reported = map
+        .memory_report()
+        .iter()
+        .map(|info| info.size)
+        .sum::<u64>();
+    assert_eq!(reported, 150);
+
+    // Copies overridden by a pack still count, and are evicted like any other asset.
+    let mut map = AssetTypeMap::new();
+    map.register_asset_size::<Blob>();
+    for (pack, priority) in [("base", 0), ("mod", 10)] {
+        let mut assets = AssetTypeMap::new();
+        assets
+            .try_insert_asset("shared", Blob(vec![0; 100]))
+            .unwrap();
+        map.mount(pack, priority, assets).unwrap();
+    }
+    assert_eq!(map.memory_usage(), 200);
+    let report = map.memory_report();
+    let overridden = report
+        .iter()
+        .find(|info| info.overridden.is_some())
+        .unwrap();
+    assert_eq!(overridden.overridden.as_ref().unwrap().pack, "base");
+    map.set_memory_budget(Some(MemoryBudget::evict_lru(100)));
+    assert_eq!(map.memory_usage(), 100);
+    assert!(map
+        .memory_report()
+        .iter()
+        .all(|info| info.overridden.is_none()));
+    assert_eq!(map.try_get_asset::<Blob>("shared").unwrap().0.len(), 100);
+    map.unmount("mod").unwrap();
+    assert_eq!(map.memory_usage(), 0);
+}
+
+#[test]
+fn test_events() {
+    use AssetEventKind::*;
+
+    let mut map = AssetTypeMap::new();
+    let all = map.subscribe();
+    let blobs = map.subscribe_to::<Blob>();
+
+    map.try_insert_asset("a", 1i32).unwrap();
+    map.try_insert_asset("a", 2i32).unwrap();
+    map.try_insert_asset("b", Blob(vec![])).unwrap();
+    *map.try_get_asset_mut::<i32>("a").unwrap() += 1;
+    assert_eq!(*map.try_get_asset::<i32>("a").unwrap(), 3);
+    map.try_take_asset::<i32>("a").unwrap();
+
+    let kinds: Vec<_> = all.try_iter().map(|e| (e.kind, e.name)).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (Inserted, "a".to_string()),
+            (Replaced, "a".to_string()),
+            (Inserted, "b".to_string()),
+            (Removed, "a".to_string()),
+        ]
+    );
+    let event = blobs.try_recv().unwrap();
+    assert!(event.is::<Blob>());
+    assert!(blobs.try_recv().is_err());
+
+    // Replacing a whole map reports each asset.
+    map.insert_map(std::collections::HashMap::from([("c", Blob(vec![]))]));
+    let kinds: Vec<_> = blobs.try_iter().map(|e| (e.kind, e.name)).collect();
+    assert_eq!(kinds.len(), 2);
+    assert!(kinds.contains(&(Inserted, "c".to_string())));
+    assert!(kinds.contains(&(Removed, "b".to_string())));
+
+    // Dropped receivers are unsubscribed.
+    drop(all);
+    drop(blobs);
+    map.try_insert_asset("d", 4i32).unwrap();
+}
+
+#[test]
+fn test_packs() {
+    fn pack(value: i32) -> AssetTypeMap {
+        let mut map = AssetTypeMap::new();
+        map.try_insert_asset("shared", value).unwrap();
+        map.try_insert_asset(&format!("only_{value}"), value)
+            .unwrap();
+        map
+    }
+
+    let mut map = AssetTypeMap::new();
+    map.mount("base", 0, pack(0)).unwrap();
+    map.mount("mod", 10, pack(10)).unwrap();
+    map.mount("patch", 5, pack(5)).unwrap();
+    assert_eq!(
+        map.mount("base", 0, pack(0)).unwrap_err(),
+        AssetError::PackAlreadyMounted("base".into())
+    );
+
+    // The highest priority wins regardless of mount order.
+    assert_eq!(*map.try_get_asset::<i32>("shared").unwrap(), 10);
+    assert_eq!(*map.try_get_asset::<i32>("only_0").unwrap(), 0);
+    let origin = map.try_asset_origin::<i32>("shared").unwrap().unwrap();
+    assert_eq!(origin.pack, "mod");
+
+    let sources = map.list_asset_sources();
+    let shared = sources.iter().find(|s| s.name == "shared").unwrap();
+    let overridden: Vec<_> = shared.overridden.iter().map(|o| o.pack.as_str()).collect();
+    assert_eq!(overridden, vec!["patch", "base"]);
+
+    // Unmounting restores the next pack down.
+    map.unmount("mod").unwrap();
+    assert_eq!(*map.try_get_asset::<i32>("shared").unwrap(), 5);
+    assert_eq!(
+        map.try_get_asset::<i32>("only_10").unwrap_err(),
+        AssetError::AssetNotFound("only_10".into())
+    );
+    map.unmount("patch").unwrap();
+    assert_eq!(*map.try_get_asset::<i32>("shared").unwrap(), 0);
+    assert_eq!(
+        map.unmount("patch").unwrap_err(),
+        AssetError::PackNotFound("patch".into())
+    );
+
+    // Runtime inserts have no origin.
+    map.try_insert_asset("shared", 99).unwrap();
+    assert_eq!(map.try_asset_origin::<i32>("shared").unwrap(), None);
+
+    // Removing an asset frees the copies it overrode, which could no longer be restored.
+    fn mount_shadowed(map: &mut AssetTypeMap) -> std::rc::Rc<i32> {
+        let hidden = std::rc::Rc::new(0);
+        let mut base = pack(0);
+        base.try_insert_asset_ref("shared", hidden.clone()).ok();
+        map.mount("base", 0, base).unwrap();
+        map.mount("mod", 10, pack(10)).unwrap();
+        assert_eq!(std::rc::Rc::strong_count(&hidden), 2);
+        hidden
+    }
+    let mut map = AssetTypeMap::new();
+    let hidden = mount_shadowed(&mut map);
+    map.try_take_asset::<i32>("shared").unwrap();
+    assert_eq!(std::rc::Rc::strong_count(&hidden), 1);
+    map.unmount("mod").unwrap();
+    assert!(map.try_get_asset::<i32>("shared").is_err());
+
+    let mut map = AssetTypeMap::new();
+    let hidden = mount_shadowed(&mut map);
+    map.try_unload_asset::<i32>("shared").unwrap();
+    assert_eq!(std::rc::Rc::strong_count(&hidden), 1);
+
+    // So do garbage collection and budget eviction.
+    let mut map = AssetTypeMap::new();
+    let hidden = mount_shadowed(&mut map);
+    map.try_pin_asset::<i32>("only_0", true).unwrap();
+    map.try_pin_asset::<i32>("only_10", true).unwrap();
+    map.collect_garbage();
+    assert_eq!(std::rc::Rc::strong_count(&hidden), 1);
+    map.unmount("mod").unwrap();
+    assert_eq!(map.try_list_assets::<i32>().unwrap(), vec!["only_0"]);
+
+    let mut map = AssetTypeMap::new();
+    map.register_asset_size::<Blob>();
+    let hidden = std::rc::Rc::new(Blob(vec![0; 100]));
+    for (name, priority, blob) in [
+        ("base", 0, hidden.clone()),
+        ("mod", 10, Blob(vec![0; 100]).into()),
+    ] {
+        let mut assets = AssetTypeMap::new();
+        assets.try_insert_asset_ref("shared", blob).ok();
+        map.mount(name, priority, assets).unwrap();
+    }
+    map.set_memory_budget(Some(MemoryBudget::evict_lru(0)));
+    assert!(map.try_get_asset::<Blob>("shared").is_err());
+    assert_eq!(std::rc::Rc::strong_count(&hidden), 1);
+}
+
+#[test]
+fn test_fallback() {
+    let mut map = AssetTypeMap::new();
+    map.set_fallback_mode(FallbackMode::Placeholder);
+    map.try_insert_asset("present", 1).unwrap();
+
+    // Without a fallback the lookup still fails.
+    assert_eq!(
+        map.try_get_asset_or_fallback::<i32>("missing").unwrap_err(),
+        AssetError::AssetNotFound("missing".into())
+    );
+
+    map.set_fallback(-1);
+    assert_eq!(*map.try_get_asset_or_fallback::<i32>("present").unwrap(), 1);
+    assert_eq!(
+        *map.try_get_asset_or_fallback::<i32>("missing").unwrap(),
+        -1
+    );
+    assert_eq!(
+        *map.try_get_asset_or_fallback::<i32>("missing").unwrap(),
+        -1
+    );
+    assert_eq!(
+        map.try_get_asset_or_fallback::<u8>("missing").unwrap_err(),
+        AssetError::AssetMapNotFound
+    );
+    // Fallbacks are not assets.
+    assert!(map.try_get_asset::<i32>("missing").is_err());
+
+    map.set_fallback_mode(FallbackMode::Strict);
+    assert_eq!(
+        map.try_get_asset_or_fallback::<i32>("missing").unwrap_err(),
+        AssetError::AssetNotFound("missing".into())
+    );
+    assert_eq!(*map.try_get_asset_or_fallback::<i32>("present").unwrap(), 1);
+}
+
+#[test]
+fn test_font_fallback() {
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    let target = futures::executor::block_on(RenderTarget::offscreen(4, 4, false, false, None));
+    let font = |data: Vec<u8>| Rc::new(Font { data });
+    let regular = std::fs::read("../core/assets/fonts/UBUNTUMONO-R.TTF").unwrap();
+    let fonts = || {
+        Some(HashMap::from([
+            ("broken".to_string(), font(vec![0; 16])),
+            (DEFAULT_FONT_NAME.to_string(), font(regular.clone())),
+        ]))
+    };
+
+    // Broken fonts are replaced with the fallback font, which is registered once.
+    let mut glyphs = GlyphRenderer::new(
+        &target,
+        fonts(),
+        Some(font(regular.clone())),
+        FallbackMode::Placeholder,
+    );
+    let fallback = glyphs.try_font("broken").unwrap();
+    assert_ne!(Some(fallback), glyphs.try_font(DEFAULT_FONT_NAME));
+    assert_eq!(
+        glyphs.add_font("also broken", &Font { data: vec![] }),
+        fallback
+    );
+
+    // Without a fallback font, broken fonts are skipped.
+    let glyphs = GlyphRenderer::new(&target, fonts(), None, FallbackMode::Placeholder);
+    assert_eq!(glyphs.try_font("broken"), None);
+
+    // Strict mode applies while the fonts are parsed.
+    let strict = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        GlyphRenderer::new(
+            &target,
+            fonts(),
+            Some(font(regular.clone())),
+            FallbackMode::Strict,
+        )
+    }));
+    assert!(strict.is_err());
+}
+
+#[test]
+fn test_obj_import() {
+    let dir = std::env::temp_dir().join("sundile_obj_import");
+    std::fs::create_dir_all(&dir).unwrap();
+    // A unit square facing +z, with no texture coordinates or normals, and a material without textures.
+    std::fs::write(
+        dir.join("square.obj"),
+        "mtllib square.mtl\nusemtl flat\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\nf 1 3 4\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("square.mtl"),
+        "newmtl flat\nKa 0.1 0.1 0.1\nKd 0.5 0.25 0\nKs 1 1 1\nNs 64\nd 0.5\n\nnewmtl pbr\nKd 1 1 1\nPr 0.75\nPm 1\nKe 0.5 0 0.25\n",
+    )
+    .unwrap();
+
+    let (models, materials) = tobj::load_obj(
+        dir.join("square.obj"),
+        &tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let mesh = MeshBuilder::from_obj("square".into(), &models[0].mesh);
+    assert_eq!(mesh.vertices.len(), 4);
+    for vertex in &mesh.vertices {
+        assert_eq!(vertex.tex_coords, [0.0; 2]);
+        assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+    }
+
+    // Blinn-Phong materials are approximated.
+    let materials = materials.unwrap();
+    let factors = MaterialFactors::from(&materials[0]);
+    assert_eq!(factors.base_color, [0.5, 0.25, 0.0, 0.5]);
+    assert_eq!(factors.roughness, (2.0f32 / 66.0).sqrt());
+    assert_eq!(factors.metallic, 0.0);
+    assert_eq!(factors.emissive, [0.0; 3]);
+
+    // The PBR extension is read directly.
+    let factors = MaterialFactors::from(&materials[1]);
+    assert_eq!(factors.base_color, [1.0; 4]);
+    assert_eq!(factors.roughness, 0.75);
+    assert_eq!(factors.metallic, 1.0);
+    assert_eq!(factors.emissive, [0.5, 0.0, 0.25]);
+}
+
+#[test]
+fn test_mesh_optimize() {
+    // A 16x16 grid of quads with unshared vertices, emitted in a cache-unfriendly column-major order.
+    let vertex = |x: u32, y: u32| ModelVertex {
+        position: [x as f32, y as f32, 0.0],
+        tex_coords: [0.0; 2],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [0.0; 3],
+        bitangent: [0.0; 3],
+    };
+    let mut vertices = vec![];
+    for x in 0..16 {
+        for y in 0..16 {
+            for (dx, dy) in [(0, 0), (1, 0), (1, 1), (0, 0), (1, 1), (0, 1)] {
+                vertices.push(vertex(x + dx, y + dy));
+            }
+        }
+    }
+    let indices = (0..vertices.len() as u32).collect::<Vec<_>>();
+    let triangles = |vertices: &[ModelVertex], indices: &[u32]| {
+        let mut triangles = indices
+            .chunks(3)
+            .map(|c| {
+                c.iter()
+                    .map(|i| vertices[*i as usize].position)
+                    .collect::<Vec<_>>()
+            })
+            .map(|t| format!("{t:?}"))
+            .collect::<Vec<_>>();
+        triangles.sort();
+        triangles
+    };
+
+    let mesh = MeshBuilder::new(vertices.clone(), indices.clone()).optimize();
+    assert_eq!(mesh.vertices.len(), 17 * 17);
+    assert_eq!(
+        triangles(&vertices, &indices),
+        triangles(&mesh.vertices, &mesh.indices)
+    );
+    let (welded_vertices, welded_indices) = weld_vertices(&vertices, &indices);
+    assert_eq!(welded_vertices.len(), 17 * 17);
+    assert!(
+        average_cache_miss_ratio(&mesh.indices, 16) < average_cache_miss_ratio(&welded_indices, 16)
+    );
+
+    let bounds = mesh.bounds();
+    assert_eq!(bounds.aabb.min, [0.0; 3]);
+    assert_eq!(bounds.aabb.max, [16.0, 16.0, 0.0]);
+    assert_eq!(bounds.sphere.center, [8.0, 8.0, 0.0]);
+    assert!((bounds.sphere.radius - 128f32.sqrt()).abs() < 1e-5);
+}
+
+#[test]
+fn test_mesh_lods() {
+    // A 32x32 grid, finely tessellated so that it simplifies well.
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    for y in 0..=32 {
+        for x in 0..=32 {
+            vertices.push(ModelVertex {
+                position: [x as f32, y as f32, 0.0],
+                tex_coords: [0.0; 2],
+                normal: [0.0, 0.0, 1.0],
+                tangent: [0.0; 3],
+                bitangent: [0.0; 3],
+            });
+        }
+    }
+    for y in 0..32 {
+        for x in 0..32 {
+            let i = y * 33 + x;
+            indices.extend([i, i + 1, i + 34, i, i + 34, i + 33]);
+        }
+    }
+
+    let settings = LodSettings {
+        error_threshold: 0.1,
+        ..Default::default()
+    };
+    let mesh = MeshBuilder::new(vertices, indices)
+        .optimize()
+        .generate_lods(&settings);
+    let radius = mesh.bounds().sphere.radius;
+    assert!(!mesh.lods.is_empty());
+    assert!(mesh.lods.len() <= settings.levels);
+    let max_error = settings.error_threshold * radius * 2f32.powi(settings.levels as i32 - 1);
+    let mut triangles = mesh.indices.len();
+    for lod in &mesh.lods {
+        assert!(lod.indices.len() < triangles);
+        assert!(lod
+            .indices
+            .iter()
+            .all(|i| (*i as usize) < mesh.vertices.len()));
+        assert!(lod.error <= max_error);
+        triangles = lod.indices.len();
+    }
+
+    // Changing the geometry discards levels of detail.
+    let mesh = mesh.optimize();
+    assert!(mesh.lods.is_empty());
+}
+
+#[test]
+fn test_deterministic_serialize() {
+    let serialize = |threads: usize| {
+        let out_path = std::env::temp_dir().join(format!("sundile_serialize_{threads}"));
+        std::fs::create_dir_all(&out_path).unwrap();
+        Serializer::default()
+            .with_mapper(types::data::Mapper::<EnemyStats>::new("data"))
+            .with_asset_directory("./tests/assets")
+            .with_out_path(&out_path)
+            .with_threads(threads)
+            .serialize()
+    };
+
+    let single = serialize(1);
+    assert_eq!(single, serialize(4));
+    assert_eq!(single, serialize(0));
+}
+
+#[test]
+fn test_migrations() {
+    // A pack baked before schema versions were recorded, in the original model layout.
+    #[derive(serde::Serialize)]
+    struct LegacyVertex {
+        position: [f32; 3],
+        tex_coords: [f32; 2],
+        normal: [f32; 3],
+        tangent: [f32; 3],
+        bitangent: [f32; 3],
+    }
+    #[derive(serde::Serialize)]
+    struct LegacyMesh {
+        vertices: Vec<LegacyVertex>,
+        indices: Vec<u32>,
+        name: Option<String>,
+        material_id: Option<usize>,
+        calculate_tangents: bool,
+    }
+    #[derive(serde::Serialize)]
+    struct LegacyModel {
+        material_builders: Vec<()>,
+        mesh_builders: Vec<LegacyMesh>,
+    }
+    let vertex = |x: f32| LegacyVertex {
+        position: [x, 0.0, 0.0],
+        tex_coords: [0.0; 2],
+        normal: [0.0, 1.0, 0.0],
+        tangent: [0.0; 3],
+        bitangent: [0.0; 3],
+    };
+    let legacy = LegacyModel {
+        material_builders: vec![],
+        mesh_builders: vec![LegacyMesh {
+            vertices: vec![vertex(0.0), vertex(1.0), vertex(2.0)],
+            indices: vec![0, 1, 2],
+            name: Some("triangle".into()),
+            material_id: None,
+            calculate_tangents: true,
+        }],
+    };
+    let mut maps = BincodeAssetTypeMap::new();
+    maps.insert(
+        "models".into(),
+        [("triangle".to_string(), bincode::serialize(&legacy).unwrap())].into(),
+    );
+    let legacy_bin = bincode::serialize(&maps).unwrap();
+
+    let bin = Deserializer::new()
+        .with_mapper(types::models::Mapper::new())
+        .migrate(&legacy_bin)
+        .unwrap();
+    let pack = Pack::from_bin(&bin).unwrap();
+    assert_eq!(pack.version("models"), 2);
+    let model: types::models::ModelData =
+        bincode::deserialize(&pack.maps["models"]["triangle"]).unwrap();
+    assert_eq!(model.mesh_builders[0].indices, vec![0, 1, 2]);
+    assert_eq!(model.mesh_builders[0].vertices[2].position, [2.0, 0.0, 0.0]);
+    assert!(model.mesh_builders[0].lods.is_empty());
+
+    // Version 1 added bounds and levels of detail, which are kept.
+    // Bounds are (min, max, center, radius), laid out as bincode writes the bounding box and sphere.
+    type V1Bounds = ([f32; 3], [f32; 3], [f32; 3], f32);
+    #[derive(serde::Serialize)]
+    struct V1Mesh {
+        vertices: Vec<LegacyVertex>,
+        indices: Vec<u32>,
+        name: Option<String>,
+        material_id: Option<usize>,
+        calculate_tangents: bool,
+        bounds: Option<V1Bounds>,
+        lods: Vec<(Vec<u32>, f32)>,
+    }
+    #[derive(serde::Serialize)]
+    struct V1Model {
+        material_builders: Vec<()>,
+        mesh_builders: Vec<V1Mesh>,
+    }
+    let v1 = V1Model {
+        material_builders: vec![],
+        mesh_builders: vec![V1Mesh {
+            vertices: vec![vertex(0.0), vertex(1.0), vertex(2.0)],
+            indices: vec![0, 1, 2, 2, 1, 0],
+            name: None,
+            material_id: Some(0),
+            calculate_tangents: false,
+            bounds: Some(([0.0; 3], [2.0, 0.0, 0.0], [1.0, 0.0, 0.0], 1.0)),
+            lods: vec![(vec![0, 1, 2], 0.5)],
+        }],
+    };
+    let mut maps = BincodeAssetTypeMap::new();
+    maps.insert(
+        "models".into(),
+        [("triangle".to_string(), bincode::serialize(&v1).unwrap())].into(),
+    );
+    let pack = Pack {
+        versions: [("models".to_string(), 1)].into(),
+        maps,
+    };
+    let bin = Deserializer::new()
+        .with_mapper(types::models::Mapper::new())
+        .migrate(&pack.to_bin())
+        .unwrap();
+    let pack = Pack::from_bin(&bin).unwrap();
+    let model: types::models::ModelData =
+        bincode::deserialize(&pack.maps["models"]["triangle"]).unwrap();
+    let mesh = &model.mesh_builders[0];
+    assert_eq!(mesh.lods[0].indices, vec![0, 1, 2]);
+    assert_eq!(mesh.lods[0].error, 0.5);
+
+    // Fresh bakes record the current version, and migrating them again changes nothing.
+    let baked = Serializer::new()
+        .with_mapper(types::models::Mapper::new())
+        .with_asset_directory("./tests/assets")
+        .with_out_path(std::env::temp_dir())
+        .serialize();
+    assert_eq!(Pack::from_bin(&baked).unwrap().version("models"), 2);
+    let migrated = Deserializer::new()
+        .with_mapper(types::models::Mapper::new())
+        .migrate(&baked)
+        .unwrap();
+    assert_eq!(migrated, baked);
+
+    // Missing steps and newer packs are errors.
+    let schema = Schema::new(3).with_migration(0, |bytes| Ok(bytes.to_vec()));
+    let map = BincodeAssetMap::new();
+    assert!(schema.migrate("things", 3, map.clone()).is_ok());
+    match schema.migrate("things", 0, map.clone()) {
+        Err(MigrationError::NoPath { from, missing, .. }) => assert_eq!((from, missing), (0, 1)),
+        other => panic!("Expected NoPath, got {:?}", other),
+    }
+    assert!(matches!(
+        schema.migrate("things", 4, map),
+        Err(MigrationError::TooNew { .. })
+    ));
+}
+
+#[test]
+fn test_registry() {
+    let builtin = registered_type_tags();
+    for tag in ["fonts", "models", "shaders", "textures"] {
+        assert!(builtin.contains(&tag.to_string()));
+    }
+
+    // Registered once, used by both the serializer and the deserializer.
+    let tag = register_mapper(|| types::data::Mapper::<EnemyStats>::new("data"));
+    assert_eq!(tag, "data");
+    assert!(registered_type_tags().contains(&tag));
+    let out_path = std::env::temp_dir().join("sundile_registry");
+    std::fs::create_dir_all(&out_path).unwrap();
+    Serializer::default()
+        .with_asset_directory("./tests/assets")
+        .with_out_path(&out_path)
+        .with_dump(true)
+        .serialize();
+    let pack = Pack::from_bin(&std::fs::read(out_path.join("data.bin")).unwrap()).unwrap();
+    assert_eq!(pack.maps["data"].len(), 3);
+
+    // Unknown types are skipped instead of panicking.
+    let json = std::fs::read_to_string(out_path.join("data.json")).unwrap();
+    let mut dump: DumpAssetTypeMap = serde_json::from_str(&json).unwrap();
+    dump.insert("mystery".into(), DumpAssetMap::new());
+    let json = serde_json::to_string(&dump).unwrap();
+    let bin = Deserializer::default().dump_to_bin(&json).unwrap();
+    assert!(!Pack::from_bin(&bin).unwrap().maps.contains_key("mystery"));
+
+    assert!(unregister_mapper(&tag));
+    assert!(!unregister_mapper(&tag));
+}
+
+#[test]
+fn test_offscreen() {
+    let mut target =
+        futures::executor::block_on(RenderTarget::offscreen(64, 48, false, false, None));
+    assert!(matches!(target.read_frame(), Ok(frame) if frame.dimensions() == (64, 48)));
+
+    // Renderers draw into the target exactly as they would into a window.
+    target.begin_frame();
+    drop(target.get_render_pass(true, true));
+    target.end_frame();
+    let frame = target.read_frame().unwrap();
+    assert_eq!(frame.dimensions(), (64, 48));
+    assert_eq!(frame.get_pixel(10, 10).0, [255, 0, 255, 0]);
+
+    let path = std::env::temp_dir().join("sundile_offscreen.png");
+    target.save_frame(&path).unwrap();
+    assert_eq!(image::open(&path).unwrap().to_rgba8(), frame);
+}
+
+#[test]
+fn test_resize() {
+    let mut target =
+        futures::executor::block_on(RenderTarget::offscreen(64, 48, false, false, None));
+    target.resize(32, 80);
+    assert_eq!(target.size(), (32, 80));
+
+    // Minimized windows report a zero size, which is ignored.
+    target.resize(0, 80);
+    assert_eq!(target.size(), (32, 80));
+
+    target.begin_frame();
+    drop(target.get_render_pass(true, true));
+    target.end_frame();
+    let frame = target.read_frame().unwrap();
+    assert_eq!(frame.dimensions(), (32, 80));
+    assert_eq!(frame.get_pixel(31, 79).0, [255, 0, 255, 0]);
+}
+
+/// Draws a cleared frame through [DrawTarget] alone, as renderers do.
+fn draw_cleared_frame<T: DrawTarget>(target: &mut T) {
+    assert!(target.frame().is_none());
+    let size = target.size();
+    target.begin_frame();
+    let frame = target.frame().unwrap();
+    assert_eq!((frame.width, frame.height), size);
+    drop(frame.render_pass(true, true));
+    target.end_frame();
+    assert!(target.frame().is_none());
+}
+
+#[test]
+fn test_draw_target() {
+    let mut target =
+        futures::executor::block_on(RenderTarget::offscreen(32, 16, false, false, None));
+    assert_eq!(target.color_format(), OFFSCREEN_FORMAT);
+    draw_cleared_frame(&mut target);
+    let frame = target.read_frame().unwrap();
+    assert_eq!(frame.get_pixel(31, 15).0, [255, 0, 255, 0]);
+
+    // Assets build from any target. Undecodable textures become placeholders.
+    let builder = AssetBuildTarget::from(&target);
+    let _: TextureWrapper = types::textures::TextureData::new().to_asset(&builder);
+}
+
+#[test]
+fn test_headless_draw_target() {
+    // Headless targets draw into a buffer which is never shown.
+    let mut target =
+        futures::executor::block_on(HeadlessRenderTarget::new(false, None)).with_size(16, 16);
+    assert_eq!(target.size(), (16, 16));
+    draw_cleared_frame(&mut target);
+}
+
+#[test]
+fn test_lights() {
+    let target = futures::executor::block_on(RenderTarget::offscreen(4, 4, false, false, None));
+    let camera = CameraWrapper::new(target.device(), 4, 4);
+    let mut lights = LightWrapper::new(target.device());
+    let ids = (0..6)
+        .map(|i| lights.add_light(Light::point([i as f32, 0.0, 0.0], [1.0; 4])))
+        .collect::<Vec<_>>();
+    // Grows past the initial capacity.
+    lights.update(target.device(), target.queue(), &camera);
+
+    // Removing a light keeps every other handle valid, and never reuses its own.
+    assert_eq!(
+        lights.remove_light(ids[1]).unwrap().position,
+        [1.0, 0.0, 0.0]
+    );
+    assert_eq!(
+        lights.remove_light(ids[1]),
+        Err(LightError::NotFound(ids[1]))
+    );
+    assert_eq!(lights.get_light(ids[5]).unwrap().position, [5.0, 0.0, 0.0]);
+    let sun = lights.add_light(Light::directional([0.0, -1.0, 0.0], [1.0; 4]));
+    assert!(!ids.contains(&sun));
+    assert_eq!(lights.len(), 6);
+
+    lights
+        .update_light(ids[5], Light::point([0.0; 3], [1.0; 4]).with_range(2.0))
+        .unwrap();
+    assert_eq!(lights.get_light(ids[5]).unwrap().range, Some(2.0));
+    lights.update(target.device(), target.queue(), &camera);
+
+    lights.clear_lights();
+    assert!(lights.get_light(sun).is_none());
+    lights.update(target.device(), target.queue(), &camera);
+}
+
+#[test]
+fn test_shadow_layout() {
+    // Largest tiles are packed first, and tiles that don't fit are left out.
+    let tiles = pack_shadow_tiles(&[512, 1024, 512, 1024, 1024], 2048);
+    assert_eq!(
+        tiles[1],
+        Some(ShadowTile {
+            x: 0,
+            y: 0,
+            size: 1024
+        })
+    );
+    assert_eq!(
+        tiles[3],
+        Some(ShadowTile {
+            x: 1024,
+            y: 0,
+            size: 1024
+        })
+    );
+    assert_eq!(
+        tiles[4],
+        Some(ShadowTile {
+            x: 0,
+            y: 1024,
+            size: 1024
+        })
+    );
+    assert_eq!(
+        tiles[0],
+        Some(ShadowTile {
+            x: 1024,
+            y: 1024,
+            size: 512
+        })
+    );
+    assert_eq!(
+        tiles[2],
+        Some(ShadowTile {
+            x: 1536,
+            y: 1024,
+            size: 512
+        })
+    );
+    assert_eq!(pack_shadow_tiles(&[4096], 2048), vec![None]);
+
+    let splits = cascade_splits(0.1, 50.0, 3);
+    assert_eq!(splits.len(), 3);
+    assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!((splits[2] - 50.0).abs() < 1e-3);
+
+    // Lights whose shadow maps don't fit in the atlas still light the scene.
+    let target = futures::executor::block_on(RenderTarget::offscreen(4, 4, false, false, None));
+    let camera = CameraWrapper::new(target.device(), 4, 4);
+    let mut lights = LightWrapper::new(target.device());
+    lights.set_shadow_atlas_size(target.device(), 256);
+    let settings = ShadowSettings {
+        resolution: 256,
+        ..Default::default()
+    };
+    lights.add_light(Light::directional([0.0, -1.0, 0.0], [1.0; 4]).with_shadows(settings));
+    lights.add_light(
+        Light::spot(
+            [0.0; 3],
+            [0.0, -1.0, 0.0],
+            cgmath::Deg(20.0),
+            cgmath::Deg(30.0),
+            [1.0; 4],
+        )
+        .with_shadows(settings),
+    );
+    lights.update(target.device(), target.queue(), &camera);
+    lights.bind_group();
+}
+
+#[test]
+fn test_downlevel_lights() {
+    // WebGL has no storage buffers, so lights and shadow views are capped uniform arrays.
+    let (device, queue) = futures::executor::block_on(async {
+        let instance = wgpu::Instance::default();
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await
+            .unwrap();
+        adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features: wgpu::Features::default(),
+                    limits: wgpu::Limits::downlevel_webgl2_defaults()
+                        .using_resolution(adapter.limits()),
+                    label: None,
+                },
+                None,
+            )
+            .await
+            .unwrap()
+    });
+    assert!(!storage_lights_supported(&device));
+
+    // The default model shader compiles against the uniform bindings.
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(
+            format!(
+                "{}\n{}",
+                light_shader_bindings(&device),
+                include_str!("../../core/assets/shaders/default.wgsl")
+            )
+            .into(),
+        ),
+    });
+
+    // Lights and shadow views past the caps are left out rather than growing the buffers.
+    let camera = CameraWrapper::new(&device, 4, 4);
+    let mut lights = LightWrapper::new(&device);
+    for i in 0..DOWNLEVEL_MAX_LIGHTS + 4 {
+        lights.add_light(Light::point([i as f32, 0.0, 0.0], [1.0; 4]));
+    }
+    let settings = ShadowSettings {
+        resolution: 64,
+        cascades: MAX_CASCADES,
+        ..Default::default()
+    };
+    for _ in 0..DOWNLEVEL_MAX_SHADOW_VIEWS / MAX_CASCADES as usize + 1 {
+        lights.add_light(Light::directional([0.0, -1.0, 0.0], [1.0; 4]).with_shadows(settings));
+    }
+    lights.update(&device, &queue, &camera);
+    lights.bind_group();
+}
+
+#[test]
+fn test_frustum_culling() {
+    use cgmath::{Quaternion, Vector3};
+
+    let target = futures::executor::block_on(RenderTarget::offscreen(4, 4, false, false, None));
+    // Looks down +X from the origin.
+    let camera = CameraWrapper::new(target.device(), 4, 4);
+    let frustum = camera.frustum();
+    let sphere = |center: [f32; 3]| BoundingSphere {
+        center,
+        radius: 0.5,
+    };
+    assert!(frustum.intersects_sphere(&sphere([5.0, 0.0, 0.0])));
+    assert!(!frustum.intersects_sphere(&sphere([-5.0, 0.0, 0.0])));
+    // Partly in view.
+    assert!(frustum.intersects_sphere(&sphere([0.0, 0.0, 0.3])));
+
+    let mut model = Model::unit_cube(target.device(), target.queue(), target.texture_layout());
+    let identity = Quaternion::new(1.0, 0.0, 0.0, 0.0);
+    for position in [
+        [5.0, 0.0, 0.0],
+        [-5.0, 0.0, 0.0],
+        [5.0, 0.0, 1.0],
+        [0.0, 50.0, 0.0],
+    ] {
+        model
+            .instance_cache
+            .insert(ModelInstance::new(Vector3::from(position), identity));
+    }
+    model.update_instances(target.device(), target.queue(), &camera);
+    assert_eq!(model.instance_cache.culled_count(), 2);
+    let drawn = model
+        .instance_cache
+        .lod_ranges()
+        .iter()
+        .map(|range| range.len())
+        .sum::<usize>();
+    assert_eq!(drawn, 2);
+}
+
+#[test]
+fn test_instance_ids() {
+    use cgmath::{Quaternion, Vector3};
+
+    let target = futures::executor::block_on(RenderTarget::offscreen(4, 4, false, false, None));
+    let identity = Quaternion::new(1.0, 0.0, 0.0, 0.0);
+    let at = |x: f32| ModelInstance::new(Vector3::new(x, 0.0, 0.0), identity);
+
+    let mut cache = InstanceCache::new();
+    let ids = (0..4)
+        .map(|x| cache.insert(at(x as f32)))
+        .collect::<Vec<_>>();
+    cache.upload(target.device(), target.queue());
+    assert_eq!(cache.uploaded_count(), 4);
+    cache.upload(target.device(), target.queue());
+    assert_eq!(cache.uploaded_count(), 0);
+
+    // Only the updated instance is written.
+    cache.update(ids[2], at(10.0)).unwrap();
+    cache.upload(target.device(), target.queue());
+    assert_eq!(cache.uploaded_count(), 1);
+    assert_eq!(cache.get(ids[2]), Some(&at(10.0)));
+
+    // Removing moves the last instance into the gap, keeping its handle.
+    assert_eq!(cache.remove(ids[1]), Ok(at(1.0)));
+    cache.upload(target.device(), target.queue());
+    assert_eq!(cache.uploaded_count(), 1);
+    assert_eq!(cache.get(ids[1]), None);
+    assert_eq!(cache.get(ids[3]), Some(&at(3.0)));
+    assert_eq!(cache.remove(ids[1]), Err(InstanceError::NotFound(ids[1])));
+    assert_eq!(
+        cache.update(ids[1], at(0.0)),
+        Err(InstanceError::NotFound(ids[1]))
+    );
+
+    // Handles aren't reused, and the buffer grows as needed.
+    let new_ids = (0..3)
+        .map(|x| cache.insert(at(x as f32)))
+        .collect::<Vec<_>>();
+    assert!(new_ids.iter().all(|id| !ids.contains(id)));
+    cache.upload(target.device(), target.queue());
+    assert_eq!(cache.uploaded_count(), 6);
+    assert_eq!(cache.iter().count(), 6);
+}
+
+#[test]
+fn test_instance_transform() {
+    use cgmath::{Deg, InnerSpace, Matrix3, Quaternion, Rotation3, Vector3, Vector4};
+
+    let identity = ModelInstance::at_origin().as_raw();
+    let unit: [[f32; 4]; 4] = cgmath::Matrix4::from_scale(1.0).into();
+    assert_eq!(identity.model, unit);
+    assert_eq!(identity.tint, [1.0; 4]);
+
+    let instance = ModelInstance::new(
+        Vector3::new(1.0, 2.0, 3.0),
+        Quaternion::from_angle_z(Deg(30.0)),
+    )
+    .with_scale([2.0, 0.5, 1.0])
+    .with_tint([1.0, 0.0, 0.0, 1.0])
+    .with_data([4.0, 0.0, 0.0, 0.0]);
+    assert_eq!(instance.max_scale(), 2.0);
+    let raw = instance.as_raw();
+    assert_eq!(raw.tint, [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(raw.data, [4.0, 0.0, 0.0, 0.0]);
+
+    // A normal stays perpendicular to a tangent under non-uniform scale.
+    let model = Matrix3::from_cols(
+        Vector4::from(raw.model[0]).truncate(),
+        Vector4::from(raw.model[1]).truncate(),
+        Vector4::from(raw.model[2]).truncate(),
+    );
+    let normal_matrix = Matrix3::from(raw.normal);
+    let tangent = model * Vector3::new(1.0, 1.0, 0.0);
+    let normal = normal_matrix * Vector3::new(1.0, -1.0, 0.0);
+    assert!(tangent.dot(normal).abs() < 1e-5);
+}
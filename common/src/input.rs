@@ -12,9 +12,8 @@ enum MouseAction {
     Released(MouseButton),
 }
 
-/// This impl makes a few assumptions:
-/// 1. The window size will never change.
-/// 2. There will only be one window.
+/// This impl assumes there will only be one window.
+/// Cursor positions are in physical pixels, relative to the window's current size.
 #[derive(Debug, Clone)]
 pub struct Input {
     mouse_actions: Vec<MouseAction>,
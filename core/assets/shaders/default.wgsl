@@ -8,17 +8,7 @@ struct CameraUniform {
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
-struct Light {
-    position: vec3<f32>,
-    color: vec4<f32>,
-};
-const NUM_LIGHTS = 4;
-struct LightBuffer {
-    ambient_light: vec4<f32>,
-    lights: array<Light, NUM_LIGHTS>,
-};
-@group(2) @binding(0)
-var<uniform> light_buffer: LightBuffer;
+// The light bind group (group 2) is declared by sundile_graphics::light_shader_bindings, which this shader is appended to.
 
 struct InstanceInput {
     @location(5) model_matrix_0: vec4<f32>,
@@ -28,6 +18,9 @@ struct InstanceInput {
     @location(9) normal_matrix_0: vec3<f32>,
     @location(10) normal_matrix_1: vec3<f32>,
     @location(11) normal_matrix_2: vec3<f32>,
+    @location(12) tint: vec4<f32>,
+    // Free for custom shaders.
+    @location(13) data: vec4<f32>,
 };
 
 struct VertexInput {
@@ -41,12 +34,13 @@ struct VertexInput {
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
-    @location(1) tangent_position: vec3<f32>,
-    @location(2) tangent_view_position: vec3<f32>,
-    @location(3) tangent_light_position_0: vec3<f32>,
-    @location(4) tangent_light_position_1: vec3<f32>,
-    @location(5) tangent_light_position_2: vec3<f32>,
-    @location(6) tangent_light_position_3: vec3<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) world_normal: vec3<f32>,
+    @location(3) world_tangent: vec3<f32>,
+    @location(4) world_bitangent: vec3<f32>,
+    @location(5) view_depth: f32,
+    @location(6) tint: vec4<f32>,
+    @location(7) instance_data: vec4<f32>,
 };
 
 @vertex
@@ -66,77 +60,195 @@ fn vs_main(
         instance.normal_matrix_2,
     );
 
-    let world_normal = normalize(normal_matrix * model.normal);
-    let world_tangent = normalize(normal_matrix * model.tangent);
-    let world_bitangent = normalize(normal_matrix * model.bitangent);
-    let tangent_matrix = transpose(mat3x3<f32>(
-        world_tangent,
-        world_bitangent,
-        world_normal,
-    ));
-
     let world_position = model_matrix * vec4<f32>(model.position, 1.0);
 
     var out: VertexOutput;
     out.clip_position = camera.view_proj * world_position;
     out.tex_coords = model.tex_coords;
-    out.tangent_position = tangent_matrix * world_position.xyz;
-    out.tangent_view_position = tangent_matrix * camera.view_pos.xyz;
-
-    out.tangent_light_position_0 = tangent_matrix * light_buffer.lights[0].position;
-    out.tangent_light_position_1 = tangent_matrix * light_buffer.lights[1].position;
-    out.tangent_light_position_2 = tangent_matrix * light_buffer.lights[2].position;
-    out.tangent_light_position_3 = tangent_matrix * light_buffer.lights[3].position;
+    out.world_position = world_position.xyz;
+    out.world_normal = normalize(normal_matrix * model.normal);
+    // Tangents lie along the surface, so unlike normals they follow the model matrix.
+    let linear_matrix = mat3x3<f32>(
+        instance.model_matrix_0.xyz,
+        instance.model_matrix_1.xyz,
+        instance.model_matrix_2.xyz,
+    );
+    out.world_tangent = normalize(linear_matrix * model.tangent);
+    out.world_bitangent = normalize(linear_matrix * model.bitangent);
+    // W of a perspective projection is the distance along the view direction.
+    out.view_depth = out.clip_position.w;
+    out.tint = instance.tint;
+    out.instance_data = instance.data;
 
     return out;
 }
 
 /////////////////////////////////////////////////
-// Fragment shader - basic Blinn-Phong shading w/ point lights
+// Fragment shader - metallic-roughness PBR w/ directional, point and spot lights, and PCF shadows
 
 @group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
+var t_base_color: texture_2d<f32>;
 @group(0) @binding(1)
-var s_diffuse: sampler;
+var s_base_color: sampler;
 @group(0) @binding(2)
 var t_normal: texture_2d<f32>;
 @group(0) @binding(3)
 var s_normal: sampler;
+@group(0) @binding(5)
+var t_metallic_roughness: texture_2d<f32>;
+@group(0) @binding(6)
+var s_metallic_roughness: sampler;
+@group(0) @binding(7)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(8)
+var s_occlusion: sampler;
+@group(0) @binding(9)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(10)
+var s_emissive: sampler;
+
+struct Material {
+    base_color: vec4<f32>,
+    emissive: vec4<f32>,
+    metallic: f32,
+    roughness: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+};
+@group(0) @binding(4)
+var<uniform> material: Material;
+
+const PI = 3.14159265359;
+
+// Trowbridge-Reitz (GGX) normal distribution.
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// Smith's method with Schlick-GGX, remapped for direct lighting.
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    let g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return g_v * g_l;
+}
+
+// Falls off with the light's attenuation factors, and fades smoothly to zero at its range.
+fn distance_attenuation(light: Light, distance: f32) -> f32 {
+    let a = light.attenuation;
+    let falloff = 1.0 / max(a.x + a.y * distance + a.z * distance * distance, 0.0001);
+    if (light.range <= 0.0) {
+        return falloff;
+    }
+    let ratio = distance / light.range;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return falloff * window * window;
+}
+
+// Fraction of the light reaching a surface, filtered over 3x3 texels of the light's shadow map.
+fn shadow_factor(light: Light, world_position: vec3<f32>, view_depth: f32) -> f32 {
+    // Cascades are ordered from nearest to farthest. Spot lights have a single view.
+    var index = u32(light.shadow_view);
+    let last = index + light.shadow_view_count - 1u;
+    loop {
+        if (index >= last || view_depth <= shadow_views.views[index].split_depth) {
+            break;
+        }
+        index = index + 1u;
+    }
+    let view = shadow_views.views[index];
+    if (view_depth > view.split_depth) {
+        return 1.0;
+    }
+
+    let clip = view.view_proj * vec4<f32>(world_position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    if (clip.w <= 0.0 || ndc.z > 1.0 || abs(ndc.x) > 1.0 || abs(ndc.y) > 1.0) {
+        return 1.0;
+    }
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_shadow));
+    let uv = view.atlas_rect.xy + (ndc.xy * vec2<f32>(0.5, -0.5) + 0.5) * view.atlas_rect.zw;
+    // Samples are kept inside the view's tile, so filtering never reads another shadow map.
+    let min_uv = view.atlas_rect.xy + texel * 0.5;
+    let max_uv = view.atlas_rect.xy + view.atlas_rect.zw - texel * 0.5;
+
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y = y + 1) {
+        for (var x = -1; x <= 1; x = x + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit = lit + textureSampleCompareLevel(t_shadow, s_shadow, clamp(uv + offset, min_uv, max_uv), ndc.z - light.shadow_bias);
+        }
+    }
+    return lit / 9.0;
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
 
-//FIXME
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
-    let object_normal: vec4<f32> = textureSample(t_normal, s_normal, in.tex_coords);
+    let base_color = textureSample(t_base_color, s_base_color, in.tex_coords) * material.base_color * in.tint;
+    let metallic_roughness = textureSample(t_metallic_roughness, s_metallic_roughness, in.tex_coords);
+    let metallic = clamp(material.metallic * metallic_roughness.b, 0.0, 1.0);
+    // Perfectly smooth surfaces reflect point lights into a single pixel, so roughness is kept above zero.
+    let roughness = clamp(material.roughness * metallic_roughness.g, 0.04, 1.0);
+    let occlusion = mix(1.0, textureSample(t_occlusion, s_occlusion, in.tex_coords).r, material.occlusion_strength);
+    let emissive = textureSample(t_emissive, s_emissive, in.tex_coords).rgb * material.emissive.rgb;
+
+    let normal_sample = textureSample(t_normal, s_normal, in.tex_coords).xyz * 2.0 - 1.0;
+    let tangent_matrix = mat3x3<f32>(
+        normalize(in.world_tangent),
+        normalize(in.world_bitangent),
+        normalize(in.world_normal),
+    );
+    let normal = normalize(tangent_matrix * (normal_sample * vec3<f32>(material.normal_scale, material.normal_scale, 1.0)));
+    let view_dir = normalize(camera.view_pos.xyz - in.world_position);
+    let n_dot_v = max(dot(normal, view_dir), 0.0001);
+
+    // Dielectrics reflect about 4% of light head-on. Metals reflect their base color.
+    let f0 = mix(vec3<f32>(0.04, 0.04, 0.04), base_color.rgb, metallic);
 
     var total_light = vec3<f32>(0.0, 0.0, 0.0);
-    for (var i: i32 = 0; i < NUM_LIGHTS; i = i + 1) {
-        var tangent_light_position: vec3<f32>;
-        switch (i) {
-            default: {break;}
-            case 0: {tangent_light_position = in.tangent_light_position_0;}
-            case 1: {tangent_light_position = in.tangent_light_position_1;}
-            case 2: {tangent_light_position = in.tangent_light_position_2;}
-            case 3: {tangent_light_position = in.tangent_light_position_3;}
+    for (var i = 0u; i < light_buffer.count; i = i + 1u) {
+        let light = light_buffer.lights[i];
+
+        var light_dir: vec3<f32>;
+        var attenuation = 1.0;
+        if (light.kind == LIGHT_DIRECTIONAL) {
+            light_dir = -normalize(light.direction);
+        } else {
+            let to_light = light.position - in.world_position;
+            let distance = length(to_light);
+            light_dir = to_light / max(distance, 0.0001);
+            attenuation = distance_attenuation(light, distance);
+            if (light.kind == LIGHT_SPOT) {
+                let cos_angle = dot(-light_dir, normalize(light.direction));
+                attenuation = attenuation * clamp((cos_angle - light.cos_outer) / max(light.cos_inner - light.cos_outer, 0.0001), 0.0, 1.0);
+            }
+        }
+        if (light.shadow_view >= 0 && attenuation > 0.0) {
+            attenuation = attenuation * shadow_factor(light, in.world_position, in.view_depth);
         }
 
-        let light = light_buffer.lights[i];
-        let light_color = light.color.rgb * light.color.a;
+        // A light's color is the light a surface facing it receives, so a white light on a white diffuse surface is white.
+        let radiance = light.color.rgb * light.color.a * PI * attenuation;
 
-        let tangent_normal = object_normal.xyz * 2.0 - 1.0; //?
-        let light_dir = normalize(tangent_light_position - in.tangent_position);
-        let view_dir = normalize(in.tangent_view_position - in.tangent_position);
         let half_dir = normalize(view_dir + light_dir);
+        let n_dot_l = max(dot(normal, light_dir), 0.0);
+        let n_dot_h = max(dot(normal, half_dir), 0.0);
 
-        let diffuse_strength = max(dot(tangent_normal, light_dir), 0.0);
-        let diffuse_color = light_color * diffuse_strength;
-
-        let specular_strength = pow(max(dot(tangent_normal, half_dir), 0.0), 32.0);
-        let specular_color = light_color * specular_strength;
+        let fresnel = fresnel_schlick(max(dot(half_dir, view_dir), 0.0), f0);
+        let specular = distribution_ggx(n_dot_h, roughness) * geometry_smith(n_dot_v, n_dot_l, roughness) * fresnel
+            / (4.0 * n_dot_v * n_dot_l + 0.0001);
+        let diffuse = (1.0 - fresnel) * (1.0 - metallic) * base_color.rgb / PI;
 
-        total_light = total_light + diffuse_color + specular_color;
+        total_light = total_light + (diffuse + specular) * radiance * n_dot_l;
     }
 
-    total_light = total_light + (light_buffer.ambient_light.rgb * light_buffer.ambient_light.a);
-    return vec4<f32>(object_color.rgb * total_light, object_color.a);
+    let ambient = light_buffer.ambient_light.rgb * light_buffer.ambient_light.a * base_color.rgb * occlusion;
+    return vec4<f32>(total_light + ambient + emissive, base_color.a);
 }
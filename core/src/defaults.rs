@@ -1,5 +1,7 @@
 use sundile_assets::AssetTypeMap;
-use sundile_graphics::{Font, RenderTarget, TextureWrapper};
+use sundile_graphics::{
+    error_shader, light_shader_bindings, DrawTarget, Font, Model, TextureWrapper,
+};
 
 use crate::SceneBuilder;
 
@@ -7,22 +9,38 @@ use crate::SceneBuilder;
 pub fn default_scene(_: SceneBuilder) {}
 
 /// Loads in all default assets.
-pub fn load_default_assets(render_target: &RenderTarget, assets: &mut AssetTypeMap) {
+pub fn load_default_assets<T: DrawTarget>(render_target: &T, assets: &mut AssetTypeMap) {
     use log::info;
     use wgpu::*;
 
     // Shaders
     if assets.try_get_asset::<ShaderModule>("default").is_err() {
-        let asset = render_target
-            .device
-            .create_shader_module(include_wgsl!("../assets/shaders/default.wgsl"));
+        // The light bindings depend on whether the device has storage buffers.
+        let device = render_target.device();
+        let source = format!(
+            "{}\n{}",
+            light_shader_bindings(device),
+            include_str!("../assets/shaders/default.wgsl")
+        );
+        let asset = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("../assets/shaders/default.wgsl"),
+            source: ShaderSource::Wgsl(source.into()),
+        });
         assets.try_insert_asset("default", asset).unwrap();
     } else {
         info!("Default shader overriden!");
     }
+    if assets.try_get_asset::<ShaderModule>("shadow").is_err() {
+        let asset = render_target
+            .device()
+            .create_shader_module(include_wgsl!("../assets/shaders/shadow.wgsl"));
+        assets.try_insert_asset("shadow", asset).unwrap();
+    } else {
+        info!("Shadow shader overriden!");
+    }
     if assets.try_get_asset::<ShaderModule>("2d").is_err() {
         let asset = render_target
-            .device
+            .device()
             .create_shader_module(include_wgsl!("../assets/shaders/2d.wgsl"));
         assets.try_insert_asset("2d", asset).unwrap();
     } else {
@@ -30,7 +48,7 @@ pub fn load_default_assets(render_target: &RenderTarget, assets: &mut AssetTypeM
     }
     if assets.try_get_asset::<ShaderModule>("passthrough").is_err() {
         let asset = render_target
-            .device
+            .device()
             .create_shader_module(include_wgsl!("../assets/shaders/passthrough.wgsl"));
         assets.try_insert_asset("passthrough", asset).unwrap();
     } else {
@@ -91,8 +109,8 @@ pub fn load_default_assets(render_target: &RenderTarget, assets: &mut AssetTypeM
         .try_insert_asset(
             "test_atlas",
             TextureWrapper::from_bytes(
-                &render_target.device,
-                &render_target.queue,
+                render_target.device(),
+                render_target.queue(),
                 include_bytes!("../assets/textures/test_atlas.png"),
                 "test atlas",
                 false,
@@ -100,4 +118,17 @@ pub fn load_default_assets(render_target: &RenderTarget, assets: &mut AssetTypeM
             .unwrap(),
         )
         .unwrap();
+
+    // Fallbacks
+    let (device, queue) = (render_target.device(), render_target.queue());
+    assets.set_fallback(TextureWrapper::placeholder(device, queue));
+    assets.set_fallback(Model::unit_cube(
+        device,
+        queue,
+        render_target.texture_layout(),
+    ));
+    assets.set_fallback(error_shader(device));
+    assets.set_fallback(Font {
+        data: include_bytes!("../assets/fonts/UBUNTUMONO-R.TTF").to_vec(),
+    });
 }
@@ -10,8 +10,9 @@ use crate::defaults::load_default_assets;
 use crate::renderer::*;
 use crate::renderer2d::*;
 use crate::SceneBuilder;
-use crate::SceneFn;
 use crate::SceneMap;
+use log::error;
+use sundile_assets::types::scenes::SceneDescription;
 
 pub struct Game {
     pub renderer: Renderer,
@@ -23,8 +24,8 @@ pub struct Game {
 }
 
 impl Game {
-    pub fn new(
-        render_target: &RenderTarget,
+    pub fn new<T: DrawTarget>(
+        render_target: &T,
         mut assets: AssetTypeMap,
         scenes: SceneMap,
         viewport: Option<Viewport>,
@@ -32,8 +33,8 @@ impl Game {
     ) -> Self {
         load_default_assets(render_target, &mut assets);
 
-        let renderer = Renderer::new(&render_target, &mut assets, viewport);
-        let renderer2d = Renderer2d::new(&render_target, &mut assets);
+        let renderer = Renderer::new(render_target, &mut assets, viewport);
+        let renderer2d = Renderer2d::new(render_target, &mut assets);
 
         Game {
             renderer,
@@ -48,9 +49,17 @@ impl Game {
     pub fn update(&mut self, dt: time::Duration) {
         // TODO: This should be something like Scene.init()
         if !self.scene_initialized {
-            self.scenes
-                .get("default")
-                .unwrap_or(&(default_scene as SceneFn))(self.get_scene_builder());
+            let has_default = self.scenes.contains_key("default")
+                || self
+                    .assets
+                    .lock()
+                    .unwrap()
+                    .try_get_asset::<SceneDescription>("default")
+                    .is_ok();
+            match has_default {
+                true => self.set_scene("default"),
+                false => default_scene(self.get_scene_builder()),
+            }
             self.scene_initialized = true;
         }
         if self.paused {
@@ -59,7 +68,7 @@ impl Game {
         self.renderer.update(dt);
     }
 
-    pub fn render(&mut self, render_target: &mut RenderTarget) {
+    pub fn render<T: DrawTarget>(&mut self, render_target: &mut T) {
         if self.paused {
             return;
         }
@@ -74,12 +83,33 @@ impl Game {
         self.renderer.handle_input(input);
     }
 
-    //TODO: Scenes should be assets loaded with AssetManager struct.
+    /// Resizes the renderers to match the render target. Call this after [RenderTarget::resize].
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.renderer.resize(width, height);
+        self.renderer2d.resize(width, height);
+    }
+
+    /// Opens a scene by name. Scenes registered as functions take priority over [SceneDescription] assets.
     pub fn set_scene<'s>(&mut self, scene: &'s str) {
-        self.scenes[scene](self.get_scene_builder());
+        if let Some(scene_fn) = self.scenes.get(scene) {
+            scene_fn(self.get_scene_builder());
+            return;
+        }
+        let description = self
+            .assets
+            .lock()
+            .unwrap()
+            .try_get_asset::<SceneDescription>(scene);
+        match description {
+            Ok(description) => {
+                self.renderer.apply_scene(&description);
+                self.get_scene_builder().load_description(&description);
+            }
+            Err(e) => error!("Unable to open scene `{}`. {}", scene, e),
+        }
     }
 
     pub fn get_scene_builder(&self) -> SceneBuilder {
-        SceneBuilder::new(self.assets.clone())
+        SceneBuilder::new(self.assets.clone(), self.renderer.stand_ins())
     }
 }
@@ -0,0 +1,267 @@
+//! Golden-image regression tests. A [GoldenTest] runs a scene in an offscreen [RenderTarget] for a set number of frames,
+//! with a fixed camera and time step, then compares the last frame to a reference image.
+//! ```ignore
+//! #[test]
+//! fn cube() {
+//!     GoldenTest::new("cube")
+//!         .with_scene(|builder| builder.new_model_instance("cube", ModelInstance::new(Vector3::zero(), Quaternion::one())))
+//!         .run()
+//!         .unwrap();
+//! }
+//! ```
+//! References are stored as tests/golden/(name).png. A missing reference fails the test.
+//! Set [UPDATE_ENV_VAR] to record every reference instead of comparing, then review and commit them.
+//! The target uses a software adapter by default, so frames are the same on every machine, including ones without a GPU.
+use std::path::*;
+
+use log::warn;
+use sundile_assets::AssetTypeMap;
+use sundile_common::time::Duration;
+use sundile_graphics::image::{Rgba, RgbaImage};
+use sundile_graphics::{image, Camera, CaptureError, DrawTarget, RenderTarget};
+use thiserror::Error;
+
+use crate::defaults::default_scene;
+use crate::{Game, SceneFn, SceneMap};
+
+/// Set this environment variable to record references.
+pub const UPDATE_ENV_VAR: &str = "SUNDILE_UPDATE_GOLDEN";
+
+/// Called before each frame is rendered, with the frame's index. Used to draw 2D elements, which only last a frame.
+pub type FrameFn = fn(&mut Game, u32);
+/// Creates the assets a test uses. The default assets are added afterwards, as [Game::new] does.
+pub type AssetsFn = fn(&RenderTarget) -> AssetTypeMap;
+
+/// Error type for golden-image tests.
+#[derive(Error, Debug)]
+pub enum GoldenError {
+    #[error("Unable to read the frame. {0}")]
+    Capture(#[from] CaptureError),
+    #[error("Unable to read or write an image. {0}")]
+    Image(#[from] image::ImageError),
+    #[error("Unable to create the output directory. {0}")]
+    Io(#[from] std::io::Error),
+    #[error("There is no reference at {}. Set {UPDATE_ENV_VAR} to record it.", .0.display())]
+    MissingReference(PathBuf),
+    #[error("The reference is {expected:?}, but the frame is {actual:?}.")]
+    SizeMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    #[error("{mismatched} of {total} pixels differ from {} by more than {tolerance}. See {}.", .reference.display(), .diff.display())]
+    Mismatch {
+        mismatched: usize,
+        total: usize,
+        tolerance: u8,
+        reference: PathBuf,
+        diff: PathBuf,
+    },
+}
+
+/// Renders a scene offscreen and compares it to a reference image. See the [module docs](self).
+pub struct GoldenTest {
+    name: String,
+    width: u32,
+    height: u32,
+    frames: u32,
+    time_step: Duration,
+    tolerance: u8,
+    max_mismatched: usize,
+    reference_dir: PathBuf,
+    output_dir: PathBuf,
+    camera: Option<Camera>,
+    scene: Option<SceneFn>,
+    frame: Option<FrameFn>,
+    assets: Option<AssetsFn>,
+    force_fallback_adapter: bool,
+}
+impl GoldenTest {
+    /// Creates a test which renders one 128x128 frame of an empty scene.
+    pub fn new<S>(name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            width: 128,
+            height: 128,
+            frames: 1,
+            time_step: Duration::from_secs(1.0 / 60.0),
+            tolerance: 2,
+            max_mismatched: 0,
+            reference_dir: "tests/golden".into(),
+            output_dir: std::env::temp_dir().join("sundile_golden"),
+            camera: None,
+            scene: None,
+            frame: None,
+            assets: None,
+            force_fallback_adapter: true,
+        }
+    }
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+    /// Sets the number of frames to run. Only the last frame is compared.
+    pub fn with_frames(mut self, frames: u32) -> Self {
+        self.frames = frames;
+        self
+    }
+    /// Sets the time passed to [Game::update] each frame. Defaults to 1/60 of a second.
+    pub fn with_time_step(mut self, time_step: Duration) -> Self {
+        self.time_step = time_step;
+        self
+    }
+    /// Sets the largest difference allowed in any channel of a pixel, from 0 to 255. Defaults to 2.
+    pub fn with_tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+    /// Sets the number of pixels which may exceed the tolerance. Defaults to 0.
+    pub fn with_max_mismatched(mut self, pixels: usize) -> Self {
+        self.max_mismatched = pixels;
+        self
+    }
+    /// Sets the directory references are read from, relative to the working directory. Defaults to tests/golden.
+    pub fn with_reference_dir<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.reference_dir = path.into();
+        self
+    }
+    /// Sets the directory rendered frames and diff images are written to on a mismatch.
+    /// Defaults to sundile_golden in the system's temporary directory.
+    pub fn with_output_dir<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.output_dir = path.into();
+        self
+    }
+    /// Fixes the camera, overriding any camera set by the scene. Defaults to the [Game]'s initial camera.
+    pub fn with_camera(mut self, camera: Camera) -> Self {
+        self.camera = Some(camera);
+        self
+    }
+    /// Sets the scene, which opens on the first frame.
+    pub fn with_scene(mut self, scene: SceneFn) -> Self {
+        self.scene = Some(scene);
+        self
+    }
+    /// Runs a function before each frame is rendered.
+    pub fn with_frame(mut self, frame: FrameFn) -> Self {
+        self.frame = Some(frame);
+        self
+    }
+    /// Creates the assets the test uses. Defaults to the default assets alone.
+    pub fn with_assets(mut self, assets: AssetsFn) -> Self {
+        self.assets = Some(assets);
+        self
+    }
+    /// Sets whether to render with a software adapter. Defaults to true. Otherwise a hardware adapter is used where there is one,
+    /// which is faster, but may not match references recorded on other machines.
+    pub fn with_force_fallback_adapter(mut self, force_fallback_adapter: bool) -> Self {
+        self.force_fallback_adapter = force_fallback_adapter;
+        self
+    }
+
+    /// Runs the scene and compares the last frame to the reference. Returns the frame.
+    pub fn run(self) -> Result<RgbaImage, GoldenError> {
+        let mut target = futures::executor::block_on(RenderTarget::offscreen(
+            self.width,
+            self.height,
+            self.force_fallback_adapter,
+            false,
+            Some(&self.name),
+        ));
+        let assets = match self.assets {
+            Some(assets) => assets(&target),
+            None => AssetTypeMap::new(),
+        };
+        let mut scenes = SceneMap::new();
+        scenes.insert("default", self.scene.unwrap_or(default_scene));
+        let mut game = Game::new(&target, assets, scenes, None, false);
+
+        for frame in 0..self.frames {
+            game.update(self.time_step);
+            if let Some(camera) = &self.camera {
+                let camera_wrapper = &mut game.renderer.camera_wrapper;
+                camera_wrapper.camera = camera.clone();
+                camera_wrapper.update(Duration::new(0.0));
+            }
+            if let Some(draw) = self.frame {
+                draw(&mut game, frame);
+            }
+            target.begin_frame();
+            game.render(&mut target);
+            target.end_frame();
+        }
+
+        let frame = target.read_frame()?;
+        self.compare(&frame)?;
+        Ok(frame)
+    }
+
+    fn compare(&self, frame: &RgbaImage) -> Result<(), GoldenError> {
+        let reference_path = self.reference_dir.join(format!("{}.png", self.name));
+        if std::env::var_os(UPDATE_ENV_VAR).is_some() {
+            warn!("Recording golden image {}", reference_path.display());
+            std::fs::create_dir_all(&self.reference_dir)?;
+            frame.save_with_format(&reference_path, image::ImageFormat::Png)?;
+            return Ok(());
+        }
+        if !reference_path.exists() {
+            return Err(GoldenError::MissingReference(reference_path));
+        }
+
+        let reference = image::open(&reference_path)?.to_rgba8();
+        if reference.dimensions() != frame.dimensions() {
+            return Err(GoldenError::SizeMismatch {
+                expected: reference.dimensions(),
+                actual: frame.dimensions(),
+            });
+        }
+
+        // Mismatched pixels are red in the diff image. Matching pixels are faded, to show where the mismatches are.
+        let mut mismatched = 0;
+        let diff = RgbaImage::from_fn(frame.width(), frame.height(), |x, y| {
+            let (actual, expected) = (frame.get_pixel(x, y), reference.get_pixel(x, y));
+            let distance = actual
+                .0
+                .iter()
+                .zip(expected.0)
+                .map(|(a, e)| a.abs_diff(e))
+                .max()
+                .unwrap_or(0);
+            match distance > self.tolerance {
+                true => {
+                    mismatched += 1;
+                    Rgba([255, 0, 0, 255])
+                }
+                false => {
+                    let [r, g, b, _] = actual.0;
+                    let faded = 64 + ((r as u16 + g as u16 + b as u16) / 12) as u8;
+                    Rgba([faded, faded, faded, 255])
+                }
+            }
+        });
+        if mismatched <= self.max_mismatched {
+            return Ok(());
+        }
+
+        std::fs::create_dir_all(&self.output_dir)?;
+        let actual_path = self.output_dir.join(format!("{}.png", self.name));
+        let diff_path = self.output_dir.join(format!("{}.diff.png", self.name));
+        frame.save_with_format(&actual_path, image::ImageFormat::Png)?;
+        diff.save_with_format(&diff_path, image::ImageFormat::Png)?;
+        Err(GoldenError::Mismatch {
+            mismatched,
+            total: (frame.width() * frame.height()) as usize,
+            tolerance: self.tolerance,
+            reference: reference_path,
+            diff: diff_path,
+        })
+    }
+}
@@ -1,5 +1,7 @@
 pub mod defaults;
 pub mod game;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod golden;
 pub mod renderer;
 pub mod renderer2d;
 pub mod scene;
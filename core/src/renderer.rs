@@ -1,46 +1,66 @@
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 use sundile_assets::*;
 use sundile_common::*;
 use sundile_graphics::*;
 
+/// Counts from the last [Renderer::render].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    /// Model instances drawn.
+    pub instances_drawn: usize,
+    /// Model instances skipped because they were outside the camera's view.
+    pub instances_culled: usize,
+}
+
+/// Models drawn in place of missing models, keyed by the missing model's name. See [crate::SceneBuilder::new_model_instance].
+/// Kept apart from the [AssetTypeMap], so stand-ins are never mistaken for the real assets.
+pub type StandInMap = Arc<Mutex<HashMap<String, Model>>>;
+
 pub struct Renderer {
     pub viewport: Option<Viewport>,
+    /// The render target's size, which the viewport is scaled with.
+    screen_size: (u32, u32),
 
     pub camera_wrapper: CameraWrapper,
     pub light_wrapper: LightWrapper,
+    /// Handles of the lights added by the last scene, by name.
+    pub scene_lights: HashMap<String, LightId>,
+    /// Orbits the origin until a scene replaces the lights.
+    test_light: Option<LightId>,
 
     model_pipeline: wgpu::RenderPipeline,
+    shadow_pipeline: wgpu::RenderPipeline,
+    stand_ins: StandInMap,
+    stats: RenderStats,
 }
 
 impl Renderer {
-    pub fn new(
-        render_target: &RenderTarget,
+    pub fn new<T: DrawTarget>(
+        render_target: &T,
         assets: &mut AssetTypeMap,
         viewport: Option<Viewport>,
     ) -> Self {
         //
         // Setup
         //
-        let (device, config) = (&render_target.device, &render_target.config);
+        let device = render_target.device();
+        let screen_size = render_target.size();
 
         let (width, height) = {
             if let Some(viewport) = viewport {
                 (viewport.width as u32, viewport.height as u32)
             } else {
-                (config.width as u32, config.height as u32)
+                screen_size
             }
         };
 
         let camera_wrapper = CameraWrapper::new(&device, width, height);
         let mut light_wrapper = LightWrapper::new(&device);
         light_wrapper.set_ambient(Color::from_rgba(1.0, 1.0, 1.0, 0.1).as_array());
-        light_wrapper
-            .add_light(
-                "test",
-                LightUniform::new([0.0, 1.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
-            )
-            .unwrap();
+        let test_light =
+            light_wrapper.add_light(Light::point([0.0, 1.0, 0.0], [1.0, 1.0, 1.0, 1.0]));
 
         //
         // Pipelines
@@ -48,7 +68,7 @@ impl Renderer {
 
         let camera_bind_group_layout = &camera_wrapper.bind_group_layout;
         let light_bind_group_layout = &light_wrapper.bind_group_layout;
-        let texture_bind_group_layout = &render_target.texture_layout;
+        let texture_bind_group_layout = render_target.texture_layout();
 
         let model_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -62,7 +82,7 @@ impl Renderer {
             });
 
         let default_shader = assets
-            .try_get_asset::<wgpu::ShaderModule>("default")
+            .try_get_asset_or_fallback::<wgpu::ShaderModule>("default")
             .unwrap();
 
         let model_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
@@ -77,7 +97,7 @@ impl Renderer {
                 module: default_shader.as_ref(),
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
+                    format: render_target.color_format(),
                     blend: Some(wgpu::BlendState::REPLACE),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
@@ -92,7 +112,7 @@ impl Renderer {
                 conservative: false,
             },
             depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::DEPTH_FORMAT,
+                format: render_target.depth_format(),
                 depth_write_enabled: true,
                 depth_compare: wgpu::CompareFunction::Less,
                 stencil: wgpu::StencilState::default(),
@@ -106,13 +126,65 @@ impl Renderer {
             multiview: None,
         });
 
+        let shadow_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Pipeline Layout"),
+                bind_group_layouts: &[light_wrapper.shadow_pass_layout()],
+                push_constant_ranges: &[],
+            });
+
+        let shadow_shader = assets
+            .try_get_asset_or_fallback::<wgpu::ShaderModule>("shadow")
+            .unwrap();
+
+        let shadow_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&shadow_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shadow_shader.as_ref(),
+                entry_point: "vs_main",
+                buffers: &[ModelVertex::desc(), InstanceRaw::desc()],
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // Both faces cast shadows, so open meshes do too.
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: SHADOW_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                // Slope-scaled bias keeps surfaces at grazing angles from shadowing themselves.
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
         Renderer {
             viewport,
+            screen_size,
 
             camera_wrapper,
             light_wrapper,
+            scene_lights: HashMap::new(),
+            test_light: Some(test_light),
 
             model_pipeline,
+            shadow_pipeline,
+            stand_ins: StandInMap::default(),
+            stats: RenderStats::default(),
         }
     }
 
@@ -120,33 +192,154 @@ impl Renderer {
         self.camera_wrapper.update(dt);
 
         use cgmath::*;
-        let mut light = self.light_wrapper.get_light("test").unwrap();
-        light.position = [
-            light.position[0] + Angle::cos(Rad::<f32>(std::f32::consts::PI * dt.as_secs_f32())),
-            light.position[1],
-            light.position[2] + Angle::sin(Rad::<f32>(std::f32::consts::PI * dt.as_secs_f32())),
-        ];
-        self.light_wrapper.update_light("test", light).unwrap();
+        // Scenes may replace the test light.
+        let test_light = self
+            .test_light
+            .and_then(|id| Some((id, *self.light_wrapper.get_light(id)?)));
+        if let Some((id, mut light)) = test_light {
+            light.position = [
+                light.position[0] + Angle::cos(Rad::<f32>(std::f32::consts::PI * dt.as_secs_f32())),
+                light.position[1],
+                light.position[2] + Angle::sin(Rad::<f32>(std::f32::consts::PI * dt.as_secs_f32())),
+            ];
+            self.light_wrapper.update_light(id, light).unwrap();
+        }
+    }
+
+    /// Applies the lights, camera and ambient color of a scene asset. Anything the scene leaves out is unchanged.
+    pub fn apply_scene(&mut self, description: &types::scenes::SceneDescription) {
+        if !description.lights.is_empty() {
+            self.light_wrapper.clear_lights();
+            self.test_light = None;
+            self.scene_lights = description
+                .lights
+                .iter()
+                .map(|light| {
+                    (
+                        light.name.clone(),
+                        self.light_wrapper.add_light(light.into()),
+                    )
+                })
+                .collect();
+        }
+        if let Some(camera) = &description.camera {
+            self.camera_wrapper.camera = Camera::new(
+                camera.position,
+                cgmath::Deg(camera.yaw),
+                cgmath::Deg(camera.pitch),
+            );
+            if let Some(fovy) = camera.fovy {
+                self.camera_wrapper.projection.set_fovy(cgmath::Deg(fovy));
+            }
+        }
+        if let Some(ambient) = description.ambient {
+            self.light_wrapper.set_ambient(ambient);
+        }
+    }
+
+    /// Returns the stand-ins drawn in place of missing models.
+    pub fn stand_ins(&self) -> StandInMap {
+        self.stand_ins.clone()
+    }
+
+    /// Returns the counts from the last frame.
+    pub fn stats(&self) -> RenderStats {
+        self.stats
     }
 
     pub fn handle_input(&mut self, input: &Input) {
         self.camera_wrapper.handle_input(input);
     }
 
-    pub fn render(&mut self, render_target: &mut RenderTarget, assets: Arc<Mutex<AssetTypeMap>>) {
+    /// Scales the viewport with the render target, and updates the camera's aspect ratio to match.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (old_width, old_height) = self.screen_size;
+        self.screen_size = (width, height);
+        let (width, height) = match self.viewport.as_mut() {
+            Some(viewport) => {
+                let (sx, sy) = (
+                    width as f32 / old_width as f32,
+                    height as f32 / old_height as f32,
+                );
+                viewport.x *= sx;
+                viewport.y *= sy;
+                viewport.width *= sx;
+                viewport.height *= sy;
+                (viewport.width as u32, viewport.height as u32)
+            }
+            None => (width, height),
+        };
+        self.camera_wrapper.resize(width.max(1), height.max(1));
+    }
+
+    pub fn render<T: DrawTarget>(
+        &mut self,
+        render_target: &mut T,
+        assets: Arc<Mutex<AssetTypeMap>>,
+    ) {
         //
         // Setup
         //
-        self.camera_wrapper.render(&render_target.queue);
-        let light_bind_group = self.light_wrapper.get_bind_group(&render_target.device);
+        self.camera_wrapper.render(render_target.queue());
+        self.light_wrapper.update(
+            render_target.device(),
+            render_target.queue(),
+            &self.camera_wrapper,
+        );
+        let light_bind_group = self.light_wrapper.bind_group();
         let camera_bind_group = &self.camera_wrapper.bind_group;
 
         let mut assets = assets.lock().unwrap();
-        let mut model_map = assets.try_take_asset_map::<Model>().ok();
-        if let Some(mm) = model_map.as_mut() {
-            for (_, model) in mm {
-                model.instance_cache.update(&render_target.device);
+        let mut model_map = assets.try_get_asset_map_mut::<Model>().unwrap_or_default();
+        let mut stand_ins = self.stand_ins.lock().unwrap();
+        // Models which have since been loaded take over their stand-in's instances.
+        stand_ins.retain(|name, stand_in| match model_map.get_mut(name) {
+            Some(model) if model.instance_cache.instances().is_empty() => {
+                std::mem::swap(&mut model.instance_cache, &mut stand_in.instance_cache);
+                false
+            }
+            Some(model) => {
+                for (_, instance) in stand_in.instance_cache.iter() {
+                    model.instance_cache.insert(*instance);
+                }
+                false
             }
+            None => true,
+        });
+        let mut models = model_map
+            .into_values()
+            .chain(stand_ins.values_mut())
+            .collect::<Vec<_>>();
+
+        self.stats = RenderStats::default();
+        for model in models.iter_mut() {
+            model.update_instances(
+                render_target.device(),
+                render_target.queue(),
+                &self.camera_wrapper,
+            );
+            let culled = model.instance_cache.culled_count();
+            self.stats.instances_culled += culled;
+            self.stats.instances_drawn += model.instance_cache.instances().len() - culled;
+        }
+
+        //
+        // Shadows
+        //
+
+        if let Some(frame) = render_target.frame() {
+            self.light_wrapper.render_shadows(
+                frame.encoder,
+                &self.shadow_pipeline,
+                |render_pass| {
+                    for model in models.iter() {
+                        model.render_shadow(render_pass);
+                    }
+                },
+            );
         }
 
         //
@@ -169,16 +362,10 @@ impl Renderer {
                 );
             }
 
-            if let Some(mm) = model_map.as_ref() {
-                render_pass.set_pipeline(&self.model_pipeline);
-                for (_, model) in mm {
-                    model.render(&mut render_pass, &camera_bind_group, &light_bind_group);
-                }
+            render_pass.set_pipeline(&self.model_pipeline);
+            for model in models.iter() {
+                model.render(&mut render_pass, &camera_bind_group, light_bind_group);
             }
         }
-
-        if let Some(mm) = model_map {
-            assets.insert_map(mm);
-        }
     }
 }
@@ -8,11 +8,12 @@
 // Draw textured quad
 // Draw text
 use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Mutex};
-use sundile_assets::AssetTypeMap;
+use sundile_assets::{AssetEvent, AssetEventKind, AssetTypeMap};
 use sundile_graphics::{
-    Color, Font, FontSpecifier, GlyphRenderer, RenderTarget, Sprite, TextBlock, TextureAtlas,
-    Vert2d, Vertex,
+    Color, DrawTarget, Font, FontSpecifier, GlyphRenderer, Sprite, TextBlock, TextureAtlas,
+    TextureWrapper, Vert2d, Vertex,
 };
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::*;
@@ -32,6 +33,8 @@ const DEFAULT_LAYOUT: Layout<BuiltInLineBreaker> = Layout::SingleLine {
     v_align: VerticalAlign::Bottom,
 };
 const DEFAULT_FONT: FontId = FontId(0);
+/// Name of the texture asset used for the texture atlas.
+const ATLAS_TEXTURE: &str = "test_atlas";
 
 /// This struct is a wrapper for wgpu_glyph's Section class.
 /// It contains all the parameters needed to create the section,
@@ -69,6 +72,7 @@ impl SectionBuilder {
 /// right next to the camera.
 pub struct Renderer2d {
     texture_atlas: TextureAtlas,
+    texture_bind_group_layout: BindGroupLayout,
     queue: Vec<Quad>,
     pipeline: wgpu::RenderPipeline,
     color: Color,
@@ -80,12 +84,15 @@ pub struct Renderer2d {
     font_size: f32,
     current_font: Option<FontId>,
     current_layout: Option<Layout<BuiltInLineBreaker>>,
+
+    font_events: Receiver<AssetEvent>,
+    texture_events: Receiver<AssetEvent>,
 }
 
 #[allow(dead_code)]
 impl Renderer2d {
-    pub fn new(render_target: &RenderTarget, assets: &mut AssetTypeMap) -> Self {
-        let (device, config) = (&render_target.device, &render_target.config);
+    pub fn new<T: DrawTarget>(render_target: &T, assets: &mut AssetTypeMap) -> Self {
+        let device = render_target.device();
 
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -129,7 +136,7 @@ impl Renderer2d {
                 module: shader.as_ref(),
                 entry_point: "fs_main",
                 targets: &[Some(ColorTargetState {
-                    format: config.format,
+                    format: render_target.color_format(),
                     blend: Some(BlendState::ALPHA_BLENDING),
                     write_mask: ColorWrites::ALL,
                 })],
@@ -152,8 +159,15 @@ impl Renderer2d {
             multiview: None,
         });
 
-        let text_wrapper =
-            GlyphRenderer::new(&render_target, assets.try_take_asset_map::<Font>().ok());
+        let font_events = assets.subscribe_to::<Font>();
+        let texture_events = assets.subscribe_to::<TextureWrapper>();
+
+        let text_wrapper = GlyphRenderer::new(
+            render_target,
+            assets.try_get_asset_map::<Font>().ok(),
+            assets.try_get_fallback::<Font>(),
+            assets.fallback_mode(),
+        );
 
         // let texture_atlas = TextureAtlasBuilder::new()
         //     .with_sprite_sheet("atlas_0", assets.get_asset("textures", "atlas_0"), SpriteSheet::new(16,16,0,0,0,0))
@@ -163,36 +177,69 @@ impl Renderer2d {
         let texture_atlas = TextureAtlas::new(
             render_target,
             &texture_bind_group_layout,
-            assets.try_take_asset("test_atlas").unwrap(),
+            assets.try_get_asset_or_fallback(ATLAS_TEXTURE).unwrap(),
             HashMap::from_iter([
                 ("default".into(), Sprite::new(vec![[0, 0]], 16, 16, 1, 0.0)),
                 ("circle".into(), Sprite::new(vec![[16, 0]], 16, 16, 1, 0.0)),
             ]),
         );
 
+        let (width, height) = render_target.size();
         Self {
             texture_atlas,
+            texture_bind_group_layout,
             queue: vec![],
             pipeline,
             color: Color::from_rgb(1.0, 1.0, 1.0),
-            screen_size: [render_target.config.width, render_target.config.height],
+            screen_size: [width, height],
 
             text_wrapper,
             text_queue: vec![],
-            text_bounds: (
-                render_target.config.width as f32,
-                render_target.config.height as f32,
-            ),
+            text_bounds: (width as f32, height as f32),
             font_size: 16.0,
             current_font: None,
             current_layout: None,
+
+            font_events,
+            texture_events,
+        }
+    }
+
+    /// Updates the font table and texture atlas for assets inserted, replaced or removed since the last frame.
+    fn handle_asset_events<T: DrawTarget>(&mut self, render_target: &T, assets: &AssetTypeMap) {
+        while let Ok(event) = self.font_events.try_recv() {
+            match event.kind {
+                AssetEventKind::Inserted | AssetEventKind::Replaced => {
+                    if let Ok(font) = assets.try_get_asset::<Font>(&event.name) {
+                        self.text_wrapper.add_font(&event.name, &font);
+                    }
+                }
+                AssetEventKind::Removed => self.text_wrapper.remove_font(&event.name),
+            }
+        }
+        while let Ok(event) = self.texture_events.try_recv() {
+            if event.name != ATLAS_TEXTURE || event.kind == AssetEventKind::Removed {
+                continue;
+            }
+            if let Ok(texture) = assets.try_get_asset::<TextureWrapper>(ATLAS_TEXTURE) {
+                self.texture_atlas.set_texture(
+                    render_target,
+                    &self.texture_bind_group_layout,
+                    texture,
+                );
+            }
         }
     }
 
-    pub fn render(&mut self, render_target: &mut RenderTarget, assets: Arc<Mutex<AssetTypeMap>>) {
+    pub fn render<T: DrawTarget>(
+        &mut self,
+        render_target: &mut T,
+        assets: Arc<Mutex<AssetTypeMap>>,
+    ) {
         // Update any text assets.
         let lock = assets.lock();
         let assets = lock.unwrap();
+        self.handle_asset_events(render_target, &assets);
         if let Ok(textblocks) = assets.try_get_asset_map::<TextBlock>() {
             textblocks.iter().for_each(|(_, value)| {
                 value.instance_cache.iter().for_each(|instance| {
@@ -265,7 +312,7 @@ impl Renderer2d {
             indices.push(i0 + 2);
         }
 
-        let device = &render_target.device;
+        let device = render_target.device();
         let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("2D Vertex Buffer"),
             contents: bytemuck::cast_slice(&vertices),
@@ -354,6 +401,18 @@ impl Renderer2d {
         );
     }
 
+    /// Updates the mapping from pixel coordinates to the screen. Text bounds which covered the whole screen are resized with it.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let [old_width, old_height] = self.screen_size;
+        if self.text_bounds == (old_width as f32, old_height as f32) {
+            self.text_bounds = (width as f32, height as f32);
+        }
+        self.screen_size = [width, height];
+    }
+
     /// Sets bounding box for text.
     pub fn set_text_bounds(&mut self, width: f32, height: f32) {
         self.text_bounds = (width, height);
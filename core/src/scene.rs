@@ -3,8 +3,13 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use sundile_assets::AssetTypeMap;
-use sundile_graphics::{Model, ModelInstance, TextBlock, TextBlockInstance};
+use cgmath::{Deg, Euler, Quaternion, Vector3};
+use sundile_assets::{types::scenes::SceneDescription, AssetTypeMap};
+use sundile_graphics::{
+    FontSpecifier, InstanceId, Model, ModelInstance, TextBlock, TextBlockInstance,
+};
+
+use crate::StandInMap;
 
 /// Function type for scenes.
 /// Initializes the scene. Runs on scene open.
@@ -15,26 +20,61 @@ pub type SceneMap = HashMap<&'static str, SceneFn>;
 
 pub struct SceneBuilder {
     pub assets: Arc<Mutex<AssetTypeMap>>,
+    pub stand_ins: StandInMap,
 }
 
 impl SceneBuilder {
-    pub fn new(assets: Arc<Mutex<AssetTypeMap>>) -> Self {
-        Self { assets }
+    pub fn new(assets: Arc<Mutex<AssetTypeMap>>, stand_ins: StandInMap) -> Self {
+        Self { assets, stand_ins }
     }
 
     // TODO: These intsancaing functions should be a single function.
-    pub fn new_model_instance(&self, name: &'static str, instance: ModelInstance) {
+    /// Adds an instance of a model and returns its handle in the model's [sundile_graphics::InstanceCache].
+    /// If the model is missing, the fallback model is instanced in its place, as a stand-in in [SceneBuilder::stand_ins].
+    /// Once the model is loaded, it takes over the stand-in's instances, keeping their handles if it had none of its own.
+    pub fn new_model_instance(&self, name: &str, instance: ModelInstance) -> InstanceId {
         let mut assets = self.assets.lock().unwrap();
-        // TODO: This is atrocious.
-        let mut model = assets.try_take_asset::<Model>(name).unwrap();
-        model.instance_cache.insert(instance);
-        assets.try_insert_asset(name, model).unwrap();
+        if assets.try_get_asset::<Model>(name).is_ok() {
+            let model = assets.try_get_asset_mut::<Model>(name).unwrap();
+            return model.instance_cache.insert(instance);
+        }
+        // Give the stand-in its own instance cache, so that it renders where this model would.
+        let fallback = assets.try_get_asset_or_fallback::<Model>(name).unwrap();
+        let mut stand_ins = self.stand_ins.lock().unwrap();
+        stand_ins
+            .entry(name.to_owned())
+            .or_insert_with(|| fallback.share_geometry())
+            .instance_cache
+            .insert(instance)
     }
 
-    pub fn new_text_instance(&self, name: &'static str, instance: TextBlockInstance) {
+    pub fn new_text_instance(&self, name: &str, instance: TextBlockInstance) {
         let mut assets = self.assets.lock().unwrap();
-        let mut text = assets.try_take_asset::<TextBlock>(name).unwrap();
+        let text = assets.try_get_asset_mut::<TextBlock>(name).unwrap();
         text.instance_cache.push(instance);
-        assets.try_insert_asset(name, text).unwrap();
+    }
+
+    /// Adds the model and text instances listed in a scene asset.
+    /// Lights, camera and ambient color are applied by [crate::Renderer::apply_scene].
+    pub fn load_description(&self, description: &SceneDescription) {
+        for model in &description.models {
+            let [x, y, z] = model.rotation;
+            let rotation = Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z)));
+            self.new_model_instance(
+                &model.model,
+                ModelInstance::new(Vector3::from(model.position), rotation)
+                    .with_scale(model.scale)
+                    .with_tint(model.tint)
+                    .with_data(model.data),
+            );
+        }
+        for text in &description.texts {
+            let mut instance = TextBlockInstance::new(text.x, text.y, text.relative_position);
+            instance.font = text.font.as_ref().map(|font| FontSpecifier {
+                name: font.name.clone(),
+                size: font.size,
+            });
+            self.new_text_instance(&text.text, instance);
+        }
     }
 }
@@ -0,0 +1,134 @@
+use cgmath::{Deg, Euler, Quaternion, Vector3};
+use sundile_core::golden::*;
+use sundile_graphics::*;
+
+#[test]
+fn golden_models() {
+    GoldenTest::new("models")
+        .with_frames(3)
+        .with_camera(Camera::new((0.0, 1.5, -4.0), Deg(90.0), Deg(-20.0)))
+        .with_scene(|builder| {
+            let rotation = Quaternion::from(Euler::new(Deg(0.0), Deg(30.0), Deg(0.0)));
+            builder.new_model_instance(
+                "cube",
+                ModelInstance::new(Vector3::new(0.0, 0.0, 0.0), rotation),
+            );
+            builder.new_model_instance(
+                "cube",
+                ModelInstance::new(
+                    Vector3::new(1.5, -0.5, 1.0),
+                    Quaternion::from(Euler::new(Deg(45.0), Deg(0.0), Deg(0.0))),
+                ),
+            );
+        })
+        .with_frame(|game, _| {
+            // There is no `cube` model, so a stand-in is drawn without touching the assets.
+            let assets = game.assets.lock().unwrap();
+            assert!(assets.try_get_asset::<Model>("cube").is_err());
+            let stand_ins = game.renderer.stand_ins();
+            assert_eq!(
+                stand_ins.lock().unwrap()["cube"]
+                    .instance_cache
+                    .instances()
+                    .len(),
+                2
+            );
+        })
+        .run()
+        .unwrap();
+}
+
+#[test]
+fn golden_2d() {
+    GoldenTest::new("2d")
+        .with_frame(|game, _| {
+            let renderer2d = &mut game.renderer2d;
+            renderer2d.set_color(Color::from_rgba(1.0, 0.5, 0.0, 1.0));
+            renderer2d.draw_quad(16.0, 16.0, 48.0, 32.0);
+            renderer2d.set_color(Color::from_rgba(0.0, 0.4, 1.0, 1.0));
+            renderer2d.draw_quad_rel(0.5, 0.5, 0.25, 0.4);
+        })
+        .run()
+        .unwrap();
+}
+
+#[test]
+fn golden_text() {
+    GoldenTest::new("text")
+        .with_size(160, 64)
+        .with_frame(|game, _| {
+            let renderer2d = &mut game.renderer2d;
+            renderer2d.set_color(Color::from_rgba(1.0, 1.0, 1.0, 1.0));
+            renderer2d.draw_text("Sundile".into(), 8.0, 8.0);
+        })
+        .run()
+        .unwrap();
+}
+
+#[test]
+fn golden_shadows() {
+    GoldenTest::new("shadows")
+        .with_size(192, 128)
+        .with_frames(2)
+        .with_assets(|target| {
+            // Shadows are easier to see on a plain white cube than on the placeholder texture.
+            let mut assets = sundile_assets::AssetTypeMap::new();
+            let mut cube =
+                Model::unit_cube(target.device(), target.queue(), target.texture_layout());
+            cube.materials = vec![std::rc::Rc::new(Material::untextured(
+                MaterialFactors::default(),
+                target.device(),
+                target.queue(),
+                target.texture_layout(),
+            ))];
+            assets.try_insert_asset("white_cube", cube).unwrap();
+            assets
+        })
+        .with_camera(Camera::new((0.0, 4.0, -6.0), Deg(90.0), Deg(-35.0)))
+        .with_scene(|builder| {
+            let rotation = Quaternion::from(Euler::new(Deg(0.0), Deg(0.0), Deg(0.0)));
+            for x in -3..=3 {
+                for z in -3..=3 {
+                    builder.new_model_instance(
+                        "white_cube",
+                        ModelInstance::new(Vector3::new(x as f32, -1.0, z as f32), rotation),
+                    );
+                }
+            }
+            builder.new_model_instance(
+                "white_cube",
+                ModelInstance::new(
+                    Vector3::new(-1.0, 0.0, 0.0),
+                    Quaternion::from(Euler::new(Deg(0.0), Deg(30.0), Deg(0.0))),
+                ),
+            );
+            builder.new_model_instance(
+                "white_cube",
+                ModelInstance::new(Vector3::new(1.5, 0.0, 1.0), rotation),
+            );
+        })
+        .with_frame(|game, frame| {
+            if frame > 0 {
+                return;
+            }
+            let lights = &mut game.renderer.light_wrapper;
+            lights.clear_lights();
+            lights.add_light(
+                Light::directional([-1.0, -2.0, 1.0], [1.0, 1.0, 1.0, 0.6])
+                    .with_shadows(ShadowSettings::default()),
+            );
+            lights.add_light(
+                Light::spot(
+                    [3.0, 3.0, 0.0],
+                    [-1.5, -3.0, 1.0],
+                    Deg(25.0),
+                    Deg(35.0),
+                    [1.0, 0.8, 0.6, 1.0],
+                )
+                .with_range(8.0)
+                .with_shadows(ShadowSettings::default()),
+            );
+        })
+        .run()
+        .unwrap();
+}
@@ -1,7 +1,7 @@
 // pub use crate::debug_gui::*;
 pub use crate::Engine;
 pub use log;
-use log::debug;
+use log::{debug, error};
 pub use sundile_assets::*;
 pub use sundile_common::*;
 use sundile_core::SceneFn;
@@ -245,6 +245,7 @@ pub struct AssetTypeMapBuilder<'a> {
     map: AssetTypeMap,
     deserializer: Option<Deserializer<'a>>,
     bin: Option<&'a [u8]>,
+    packs: Vec<(&'a str, i32, Deserializer<'a>, &'a [u8])>,
 }
 impl<'a> AssetTypeMapBuilder<'a> {
     /// Creates a new AssetTypeMapBuilder with default options.
@@ -253,6 +254,7 @@ impl<'a> AssetTypeMapBuilder<'a> {
             map: AssetTypeMap::new(),
             deserializer: None,
             bin: None,
+            packs: vec![],
         }
     }
     /// Adds an asset. Will create a category for the associated type if needed.
@@ -270,18 +272,51 @@ impl<'a> AssetTypeMapBuilder<'a> {
         self.bin = Some(bin);
         self
     }
+    /// Mounts an additional asset pack, such as a patch, DLC or mod, with the given priority.
+    /// Where asset names conflict, the pack with the highest priority wins. Assets added with [AssetTypeMapBuilder::with_deserializer] have priority 0.
+    /// Those are mounted as `base`; a pack with that name, or with the name of an earlier pack, is skipped with an error.
+    /// See [AssetTypeMap::mount].
+    pub fn with_pack(
+        mut self,
+        name: &'a str,
+        priority: i32,
+        deserializer: Deserializer<'a>,
+        bin: &'a [u8],
+    ) -> Self {
+        self.packs.push((name, priority, deserializer, bin));
+        self
+    }
+    /// Sets what happens when an asset is missing or fails to build, for the map and every deserializer and pack.
+    /// Defaults to placeholders in debug builds and strict in release builds. See [FallbackMode].
+    pub fn with_fallback_mode(mut self, mode: FallbackMode) -> Self {
+        self.map.set_fallback_mode(mode);
+        self
+    }
     /// Builds the AssetTypeMap
     pub(crate) fn build(mut self, render_target: &RenderTarget) -> AssetTypeMap {
         debug!("Building AssetMap");
-        match self.deserializer {
-            Some(de) => {
-                self.map
-                    .try_combine(de.deserialize(self.bin.unwrap(), render_target))
-                    .unwrap();
-                self.map
+        let mode = self.map.fallback_mode();
+        if let Some(de) = self.deserializer {
+            let assets = de
+                .with_fallback_mode(mode)
+                .deserialize(self.bin.unwrap(), render_target);
+            self.map.mount("base", 0, assets).unwrap();
+        }
+        for (name, priority, de, bin) in self.packs {
+            // Checked before deserializing, so a skipped pack's assets are never built.
+            if self.map.is_mounted(name) {
+                error!(
+                    "Skipping asset pack. {}",
+                    AssetError::PackAlreadyMounted(name.into())
+                );
+                continue;
+            }
+            let assets = de.with_fallback_mode(mode).deserialize(bin, render_target);
+            if let Err(e) = self.map.mount(name, priority, assets) {
+                error!("Skipping asset pack. {}", e);
             }
-            None => self.map,
         }
+        self.map
     }
 }
 
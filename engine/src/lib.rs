@@ -22,7 +22,11 @@ pub mod prelude {
 use log::*;
 pub use prelude::*;
 use wasm_bindgen::prelude::wasm_bindgen;
-use winit::{event::VirtualKeyCode, window::*};
+use winit::{
+    dpi::PhysicalSize,
+    event::{Event, VirtualKeyCode, WindowEvent},
+    window::*,
+};
 
 //NOTE: Because this is wasm_bindgen, it *cannot* have a lifetime or type parameter!
 #[wasm_bindgen]
@@ -59,6 +63,24 @@ impl Engine {
         event_loop.run(move |event, _, control_flow| {
             //match debug_gui.handle_event(event) {
             // Some(event) => {
+            match &event {
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(size),
+                    ..
+                } => resize(&mut render_target, &mut game, *size),
+                Event::WindowEvent {
+                    event: WindowEvent::ScaleFactorChanged { new_inner_size, .. },
+                    ..
+                } => resize(&mut render_target, &mut game, **new_inner_size),
+                #[cfg(target_arch = "wasm32")]
+                Event::MainEventsCleared => {
+                    if let Some(size) = fit_canvas(&_window) {
+                        resize(&mut render_target, &mut game, size);
+                    }
+                }
+                _ => {}
+            }
+
             let can_update_game = input.update(&event);
             if !can_update_game {
                 return;
@@ -99,3 +121,27 @@ impl Engine {
         });
     }
 }
+
+/// Resizes the render target and the game to a new window size, in physical pixels.
+fn resize(render_target: &mut RenderTarget, game: &mut Game, size: PhysicalSize<u32>) {
+    if size.width == 0 || size.height == 0 {
+        return;
+    }
+    render_target.resize(size.width, size.height);
+    game.resize(size.width, size.height);
+}
+
+/// Sizes the canvas to fill its parent element, at the device's pixel ratio. Returns the new size if it changed.
+/// Browsers don't resize canvases, so winit only reports a resize when this calls [Window::set_inner_size].
+#[cfg(target_arch = "wasm32")]
+fn fit_canvas(window: &Window) -> Option<PhysicalSize<u32>> {
+    use winit::platform::web::WindowExtWebSys;
+    let parent = window.canvas().parent_element()?;
+    let size = winit::dpi::LogicalSize::new(parent.client_width(), parent.client_height())
+        .to_physical::<u32>(window.scale_factor());
+    if size.width == 0 || size.height == 0 || size == window.inner_size() {
+        return None;
+    }
+    window.set_inner_size(size);
+    Some(size)
+}
@@ -0,0 +1,139 @@
+//! Serde helpers for byte buffers such as embedded textures and fonts.
+//! Binary formats like bincode store the bytes as usual. Human-readable formats store a summary of the bytes,
+//! with their size, hash and image dimensions, followed by the bytes in base64.
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Sprite {
+//!     #[serde(with = "sundile_graphics::blob")]
+//!     png: Vec<u8>,
+//! }
+//! ```
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::*;
+
+/// Human-readable form of a byte buffer. Only `base64` is read back; the rest is informational.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlobSummary {
+    pub size: usize,
+    /// 64-bit FNV-1a hash of the bytes, in hex.
+    pub hash: String,
+    /// Format and dimensions, if the bytes are an image, e.g. "Png 64x64".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    pub base64: String,
+}
+impl BlobSummary {
+    pub fn new(bytes: &[u8]) -> Self {
+        Self {
+            size: bytes.len(),
+            hash: format!("{:016x}", fnv1a(bytes)),
+            image: describe_image(bytes),
+            base64: STANDARD.encode(bytes),
+        }
+    }
+    /// Decodes the bytes.
+    pub fn decode(&self) -> Result<Vec<u8>, base64::DecodeError> {
+        STANDARD.decode(&self.base64)
+    }
+}
+
+/// A byte buffer which serializes through [crate::blob].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Blob(#[serde(with = "crate::blob")] pub Vec<u8>);
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Reads only the image header, so this is cheap even for large textures.
+fn describe_image(bytes: &[u8]) -> Option<String> {
+    let reader = image::io::Reader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?;
+    let format = reader.format()?;
+    let (width, height) = reader.into_dimensions().ok()?;
+    Some(format!("{:?} {}x{}", format, width, height))
+}
+
+pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match serializer.is_human_readable() {
+        true => BlobSummary::new(bytes).serialize(serializer),
+        false => serializer.serialize_bytes(bytes),
+    }
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match deserializer.is_human_readable() {
+        true => BlobSummary::deserialize(deserializer)?
+            .decode()
+            .map_err(de::Error::custom),
+        false => serde_bytes_buf(deserializer),
+    }
+}
+
+/// Deserializes a byte buffer from a binary format.
+fn serde_bytes_buf<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct BytesVisitor;
+    impl<'de> de::Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a byte buffer")
+        }
+        fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(bytes.to_vec())
+        }
+        fn visit_byte_buf<E>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E> {
+            Ok(bytes)
+        }
+        fn visit_seq<A>(self, mut seq: A) -> Result<Vec<u8>, A::Error>
+        where
+            A: de::SeqAccess<'de>,
+        {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+    }
+    deserializer.deserialize_byte_buf(BytesVisitor)
+}
+
+/// The same, for optional byte buffers.
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        struct BlobRef<'a>(&'a Vec<u8>);
+        impl<'a> Serialize for BlobRef<'a> {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                super::serialize(self.0, serializer)
+            }
+        }
+        bytes.as_ref().map(BlobRef).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<Blob>::deserialize(deserializer)?.map(|blob| blob.0))
+    }
+}
@@ -15,6 +15,7 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
 #[derive(Debug)]
 pub struct Projection {
     aspect: f32,
+    height: u32,
     fovy: Rad<f32>,
     znear: f32,
     zfar: f32,
@@ -24,6 +25,7 @@ impl Projection {
     pub fn new<F: Into<Rad<f32>>>(width: u32, height: u32, fovy: F, znear: f32, zfar: f32) -> Self {
         Self {
             aspect: width as f32 / height as f32,
+            height,
             fovy: fovy.into(),
             znear,
             zfar,
@@ -32,10 +34,29 @@ impl Projection {
 
     pub fn resize(&mut self, width: u32, height: u32) {
         self.aspect = width as f32 / height as f32;
+        self.height = height;
+    }
+
+    pub fn set_fovy<F: Into<Rad<f32>>>(&mut self, fovy: F) {
+        self.fovy = fovy.into();
+    }
+
+    /// Returns the size of one pixel, in world units, at the given distance from the camera.
+    pub fn pixel_size_at(&self, distance: f32) -> f32 {
+        2.0 * distance * (self.fovy / 2.0).tan() / self.height.max(1) as f32
     }
 
     pub fn calc_matrix(&self) -> Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.znear, self.zfar)
+        self.calc_matrix_with_range(self.znear, self.zfar)
+    }
+
+    /// Like [Projection::calc_matrix], but with different near and far planes. Used to fit shadow cascades to part of the view.
+    pub fn calc_matrix_with_range(&self, znear: f32, zfar: f32) -> Matrix4<f32> {
+        OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, znear, zfar)
+    }
+
+    pub fn znear(&self) -> f32 {
+        self.znear
     }
 }
 
@@ -60,7 +81,7 @@ impl CameraUniform {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Camera {
     pub pos: Point3<f32>,
     pitch: Rad<f32>,
@@ -164,6 +185,8 @@ pub struct CameraWrapper {
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub controller: CameraController,
     pub projection: Projection,
+    /// How large, in pixels, simplification errors may appear before a more detailed level of detail is used.
+    pub lod_pixel_error: f32,
 }
 
 impl CameraWrapper {
@@ -218,15 +241,33 @@ impl CameraWrapper {
             bind_group,
             controller,
             projection,
+            lod_pixel_error: 1.0,
         }
     }
 
+    /// Returns the largest simplification error, in world units, that is acceptable at the given distance from the camera.
+    pub fn lod_tolerance(&self, distance: f32) -> f32 {
+        self.projection.pixel_size_at(distance) * self.lod_pixel_error
+    }
+
+    /// Returns the camera's view frustum, as of the last update.
+    pub fn frustum(&self) -> crate::geometry::Frustum {
+        crate::geometry::Frustum::from_matrix(self.uniform.view_proj.into())
+    }
+
     pub fn update(&mut self, dt: Duration) {
         self.controller.update(&mut self.camera, dt);
         self.uniform
             .update_view_proj(&self.camera, &self.projection);
     }
 
+    /// Updates the aspect ratio for a new viewport size, in physical pixels. Takes effect on the next [CameraWrapper::render].
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.projection.resize(width, height);
+        self.uniform
+            .update_view_proj(&self.camera, &self.projection);
+    }
+
     pub fn handle_input(&mut self, input: &Input) {
         self.controller.handle_input(&input);
     }
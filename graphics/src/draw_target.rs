@@ -0,0 +1,87 @@
+use crate::*;
+
+/// Anything renderers can draw to: a window's surface or a texture through [RenderTarget], or a [HeadlessRenderTarget].
+/// Renderers are generic over this, so the same pipeline can draw to a window, a texture or a test buffer.
+/// ```ignore
+/// fn draw<T: DrawTarget>(target: &mut T) {
+///     target.begin_frame();
+///     drop(target.get_render_pass(true, true));
+///     target.end_frame();
+/// }
+/// ```
+pub trait DrawTarget {
+    fn device(&self) -> &wgpu::Device;
+    fn queue(&self) -> &wgpu::Queue;
+    /// Layout of the bind group shared by textures and materials.
+    fn texture_layout(&self) -> &wgpu::BindGroupLayout;
+    /// Format of the color attachment. Pipelines drawing to this target must use it.
+    fn color_format(&self) -> wgpu::TextureFormat;
+    /// Format of the depth attachment.
+    fn depth_format(&self) -> wgpu::TextureFormat {
+        texture::DEPTH_FORMAT
+    }
+    /// Size of the color and depth attachments, in physical pixels.
+    fn size(&self) -> (u32, u32);
+
+    /// Creates the frame's encoder and views. Does nothing if a frame is already in progress.
+    fn begin_frame(&mut self);
+    /// Submits the frame, and presents it if drawing to a window.
+    fn end_frame(&mut self);
+    /// Returns the frame in progress, or None outside of [DrawTarget::begin_frame] and [DrawTarget::end_frame].
+    fn frame(&mut self) -> Option<Frame<'_>>;
+
+    /// Begins a render pass on the frame in progress. See [Frame::render_pass].
+    fn get_render_pass(&mut self, clear: bool, use_depth_stencil: bool) -> wgpu::RenderPass<'_> {
+        self.frame()
+            .expect("No frame in progress! Call begin_frame first.")
+            .render_pass(clear, use_depth_stencil)
+    }
+}
+
+/// The encoder and attachments of a frame in progress.
+pub struct Frame<'a> {
+    pub device: &'a wgpu::Device,
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    pub color_view: &'a wgpu::TextureView,
+    pub depth_view: &'a wgpu::TextureView,
+    pub width: u32,
+    pub height: u32,
+}
+impl<'a> Frame<'a> {
+    /// Begins a render pass. If `clear` is set, the color attachment is cleared to transparent magenta; otherwise it is loaded.
+    /// The depth attachment is always cleared to 1.0.
+    pub fn render_pass(self, clear: bool, use_depth_stencil: bool) -> wgpu::RenderPass<'a> {
+        self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self.color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: if clear {
+                        wgpu::LoadOp::Clear(wgpu::Color {
+                            r: 1.0,
+                            g: 0.0,
+                            b: 1.0,
+                            a: 0.0,
+                        })
+                    } else {
+                        wgpu::LoadOp::Load
+                    },
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: if use_depth_stencil {
+                Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: self.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                })
+            } else {
+                None
+            },
+        })
+    }
+}
@@ -0,0 +1,34 @@
+/// Decides what happens when an asset is missing or fails to build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FallbackMode {
+    /// Substitutes a built-in placeholder and logs a warning once. The default in debug builds.
+    Placeholder,
+    /// Fails hard, as before. The default in release builds; use it for release checks.
+    Strict,
+}
+impl Default for FallbackMode {
+    fn default() -> Self {
+        if cfg!(debug_assertions) {
+            FallbackMode::Placeholder
+        } else {
+            FallbackMode::Strict
+        }
+    }
+}
+impl FallbackMode {
+    /// Returns true if placeholders should be substituted.
+    pub fn is_placeholder(&self) -> bool {
+        *self == FallbackMode::Placeholder
+    }
+}
+
+/// WGSL source of the error shader. Compatible with the model pipeline; draws everything in flat magenta.
+pub const ERROR_SHADER_SOURCE: &str = include_str!("shaders/error.wgsl");
+
+/// Creates the error shader, used in place of shaders which are missing or fail to compile.
+pub fn error_shader(device: &wgpu::Device) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Error Shader"),
+        source: wgpu::ShaderSource::Wgsl(ERROR_SHADER_SOURCE.into()),
+    })
+}
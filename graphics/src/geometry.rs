@@ -0,0 +1,368 @@
+use cgmath::*;
+use serde::*;
+use std::collections::HashMap;
+
+/// Number of vertices assumed to fit in the GPU's post-transform vertex cache.
+const CACHE_SIZE: usize = 32;
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+impl Aabb {
+    /// Creates the smallest box containing every point, or an empty box at the origin if there are none.
+    pub fn from_points<I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = [f32; 3]>,
+    {
+        let mut points = points.into_iter();
+        let first = match points.next() {
+            Some(first) => first,
+            None => {
+                return Self {
+                    min: [0.0; 3],
+                    max: [0.0; 3],
+                }
+            }
+        };
+        points.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |aabb, point| {
+                aabb.union(&Self {
+                    min: point,
+                    max: point,
+                })
+            },
+        )
+    }
+    /// Returns the smallest box containing both boxes.
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: [0, 1, 2].map(|i| self.min[i].min(other.min[i])),
+            max: [0, 1, 2].map(|i| self.max[i].max(other.max[i])),
+        }
+    }
+    pub fn center(&self) -> [f32; 3] {
+        [0, 1, 2].map(|i| (self.min[i] + self.max[i]) * 0.5)
+    }
+    /// Returns true if the point is inside the box or on its surface.
+    pub fn contains(&self, point: [f32; 3]) -> bool {
+        (0..3).all(|i| self.min[i] <= point[i] && point[i] <= self.max[i])
+    }
+}
+
+/// A bounding sphere.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingSphere {
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
+/// Bounds of a mesh in model space, used for culling and picking.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MeshBounds {
+    pub aabb: Aabb,
+    /// Centered on the box, so it is not always the smallest possible sphere.
+    pub sphere: BoundingSphere,
+}
+impl MeshBounds {
+    /// Computes the bounds of a set of points.
+    pub fn from_points(points: &[[f32; 3]]) -> Self {
+        let aabb = Aabb::from_points(points.iter().copied());
+        let center = Point3::from(aabb.center());
+        let radius = points
+            .iter()
+            .map(|point| Point3::from(*point).distance(center))
+            .fold(0.0, f32::max);
+        Self {
+            aabb,
+            sphere: BoundingSphere {
+                center: center.into(),
+                radius,
+            },
+        }
+    }
+    /// Returns bounds containing both bounds.
+    pub fn union(&self, other: &MeshBounds) -> Self {
+        let aabb = self.aabb.union(&other.aabb);
+        let center = Point3::from(aabb.center());
+        let radius = [self.sphere, other.sphere]
+            .iter()
+            .map(|sphere| Point3::from(sphere.center).distance(center) + sphere.radius)
+            .fold(0.0, f32::max);
+        Self {
+            aabb,
+            sphere: BoundingSphere {
+                center: center.into(),
+                radius,
+            },
+        }
+    }
+}
+
+/// A view frustum, as six planes facing inwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far, each as (normal, distance).
+    pub planes: [[f32; 4]; 6],
+}
+impl Frustum {
+    /// Extracts the frustum of a view-projection matrix, with wgpu's depth range of 0 to 1.
+    pub fn from_matrix(view_proj: Matrix4<f32>) -> Self {
+        let row = |i: usize| {
+            Vector4::new(
+                view_proj.x[i],
+                view_proj.y[i],
+                view_proj.z[i],
+                view_proj.w[i],
+            )
+        };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Self {
+            planes: [w + x, w - x, w + y, w - y, z, w - z]
+                .map(|plane| (plane / plane.truncate().magnitude()).into()),
+        }
+    }
+    /// Returns true if any part of the sphere may be inside the frustum.
+    /// Spheres near the frustum's corners may be reported as inside when they're not.
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        let [cx, cy, cz] = sphere.center;
+        self.planes
+            .iter()
+            .all(|[a, b, c, d]| a * cx + b * cy + c * cz + d >= -sphere.radius)
+    }
+}
+
+/// Merges vertices which are identical byte-for-byte, returning the unique vertices and the remapped indices.
+/// Vertices keep the order in which they first appear.
+pub fn weld_vertices<T>(vertices: &[T], indices: &[u32]) -> (Vec<T>, Vec<u32>)
+where
+    T: bytemuck::Pod,
+{
+    let mut unique = Vec::<T>::new();
+    let mut lookup = HashMap::<&[u8], u32>::new();
+    let remap = vertices
+        .iter()
+        .map(|vertex| {
+            *lookup.entry(bytemuck::bytes_of(vertex)).or_insert_with(|| {
+                unique.push(*vertex);
+                unique.len() as u32 - 1
+            })
+        })
+        .collect::<Vec<_>>();
+    let indices = indices.iter().map(|i| remap[*i as usize]).collect();
+    (unique, indices)
+}
+
+/// Scores a vertex for [optimize_vertex_cache]. Vertices near the front of the cache and vertices with few remaining
+/// triangles score higher, so that triangles reuse cached vertices and vertices are finished off quickly.
+fn vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        // The last triangle's vertices are penalized slightly, so strips don't just go back and forth.
+        Some(position) if position < 3 => 0.75,
+        Some(position) => (1.0 - (position - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(1.5),
+    };
+    cache_score + 2.0 * (remaining as f32).powf(-0.5)
+}
+
+/// Reorders triangles so that consecutive triangles share vertices, improving the hit rate of the GPU's vertex cache.
+/// Uses Tom Forsyth's linear-speed vertex cache optimization. The output is deterministic.
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    let corners = |t: usize| [0, 1, 2].map(|k| indices[t * 3 + k] as usize);
+
+    let mut vertex_triangles = vec![Vec::<usize>::new(); vertex_count];
+    for t in 0..triangle_count {
+        for v in corners(t) {
+            vertex_triangles[v].push(t);
+        }
+    }
+    let mut cache_positions = vec![None; vertex_count];
+    let mut vertex_scores = vertex_triangles
+        .iter()
+        .map(|triangles| vertex_score(None, triangles.len()))
+        .collect::<Vec<_>>();
+    let mut triangle_scores = (0..triangle_count)
+        .map(|t| corners(t).iter().map(|v| vertex_scores[*v]).sum::<f32>())
+        .collect::<Vec<_>>();
+    let mut emitted = vec![false; triangle_count];
+
+    let mut output = Vec::with_capacity(indices.len());
+    let mut cache = Vec::<usize>::with_capacity(CACHE_SIZE + 3);
+    let mut next_unemitted = 0;
+    let mut best =
+        (0..triangle_count).reduce(|best, t| match triangle_scores[t] > triangle_scores[best] {
+            true => t,
+            false => best,
+        });
+
+    while let Some(triangle) = best {
+        emitted[triangle] = true;
+        let triangle_corners = corners(triangle);
+        output.extend(triangle_corners.map(|v| v as u32));
+        for v in triangle_corners {
+            vertex_triangles[v].retain(|t| *t != triangle);
+        }
+
+        // Move the triangle's vertices to the front of the cache, pushing the oldest vertices out.
+        let mut new_cache = triangle_corners.to_vec();
+        new_cache.extend(cache.iter().filter(|v| !triangle_corners.contains(v)));
+        for (position, v) in new_cache.iter().enumerate() {
+            cache_positions[*v] = (position < CACHE_SIZE).then_some(position);
+            let score = vertex_score(cache_positions[*v], vertex_triangles[*v].len());
+            let delta = score - vertex_scores[*v];
+            vertex_scores[*v] = score;
+            for t in &vertex_triangles[*v] {
+                triangle_scores[*t] += delta;
+            }
+        }
+        new_cache.truncate(CACHE_SIZE);
+        cache = new_cache;
+
+        best = None;
+        let mut best_score = f32::MIN;
+        for v in &cache {
+            for t in &vertex_triangles[*v] {
+                if triangle_scores[*t] > best_score {
+                    best = Some(*t);
+                    best_score = triangle_scores[*t];
+                }
+            }
+        }
+        if best.is_none() {
+            while next_unemitted < triangle_count && emitted[next_unemitted] {
+                next_unemitted += 1;
+            }
+            best = (next_unemitted < triangle_count).then_some(next_unemitted);
+        }
+    }
+    // Keep any indices which do not form a whole triangle.
+    output.extend_from_slice(&indices[triangle_count * 3..]);
+    output
+}
+
+/// Reorders vertices by their first use in the index buffer, so that vertex fetches are mostly sequential.
+/// Unused vertices are dropped.
+pub fn optimize_vertex_fetch<T>(vertices: &[T], indices: &[u32]) -> (Vec<T>, Vec<u32>)
+where
+    T: Copy,
+{
+    let mut remap = vec![None; vertices.len()];
+    let mut ordered = Vec::with_capacity(vertices.len());
+    let indices = indices
+        .iter()
+        .map(|i| {
+            *remap[*i as usize].get_or_insert_with(|| {
+                ordered.push(vertices[*i as usize]);
+                ordered.len() as u32 - 1
+            })
+        })
+        .collect();
+    (ordered, indices)
+}
+
+/// Returns the average number of vertices transformed per triangle with a FIFO cache of the given size.
+/// Lower is better; 0.5 is the practical minimum for large regular meshes, 3.0 means no reuse at all.
+pub fn average_cache_miss_ratio(indices: &[u32], cache_size: usize) -> f32 {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return 0.0;
+    }
+    let mut cache = std::collections::VecDeque::with_capacity(cache_size);
+    let mut misses = 0;
+    for i in indices {
+        if !cache.contains(i) {
+            misses += 1;
+            if cache.len() == cache_size {
+                cache.pop_front();
+            }
+            cache.push_back(*i);
+        }
+    }
+    misses as f32 / triangle_count as f32
+}
+
+/// Simplifies a triangle mesh by snapping its vertices to a grid of the given cell size.
+/// Each cell is represented by the vertex nearest the cell's average position, and triangles which collapse are dropped.
+/// Returns indices into the original vertices, along with the largest distance any vertex moved.
+pub fn simplify_by_clustering(
+    positions: &[[f32; 3]],
+    indices: &[u32],
+    cell_size: f32,
+) -> (Vec<u32>, f32) {
+    let origin = Aabb::from_points(indices.iter().map(|i| positions[*i as usize])).min;
+    let cell_of = |v: u32| {
+        let position = positions[v as usize];
+        [0, 1, 2].map(|k| ((position[k] - origin[k]) / cell_size).floor() as i32)
+    };
+
+    // Only vertices used by the mesh take part, in order of first use so that the output is deterministic.
+    let mut cells = HashMap::<[i32; 3], Vec<u32>>::new();
+    let mut cell_order = Vec::new();
+    let mut seen = vec![false; positions.len()];
+    for v in indices {
+        if !std::mem::replace(&mut seen[*v as usize], true) {
+            let members = cells.entry(cell_of(*v)).or_default();
+            if members.is_empty() {
+                cell_order.push(cell_of(*v));
+            }
+            members.push(*v);
+        }
+    }
+
+    let mut representative = HashMap::<u32, u32>::new();
+    let mut error = 0.0f32;
+    for cell in cell_order {
+        let members = &cells[&cell];
+        let mean = members
+            .iter()
+            .map(|v| Vector3::from(positions[*v as usize]))
+            .sum::<Vector3<f32>>()
+            / members.len() as f32;
+        let chosen = members
+            .iter()
+            .copied()
+            .reduce(|best, v| {
+                let distance = |v: u32| (Vector3::from(positions[v as usize]) - mean).magnitude2();
+                match distance(v) < distance(best) {
+                    true => v,
+                    false => best,
+                }
+            })
+            .unwrap();
+        for v in members {
+            let moved = Point3::from(positions[*v as usize])
+                .distance(Point3::from(positions[chosen as usize]));
+            error = error.max(moved);
+            representative.insert(*v, chosen);
+        }
+    }
+
+    let mut kept = std::collections::HashSet::new();
+    let mut output = Vec::new();
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| representative[&triangle[k]]);
+        if a == b || b == c || a == c {
+            continue;
+        }
+        // Rotate so the smallest index comes first, which keeps the winding, to find duplicate triangles.
+        let key = match a.min(b).min(c) {
+            m if m == a => [a, b, c],
+            m if m == b => [b, c, a],
+            _ => [c, a, b],
+        };
+        if kept.insert(key) {
+            output.extend([a, b, c]);
+        }
+    }
+    (output, error)
+}
@@ -1,14 +1,21 @@
+pub mod blob;
 pub mod camera;
+pub mod draw_target;
+pub mod fallback;
+pub mod geometry;
 pub mod light;
 pub mod model;
 pub mod render_target;
+pub mod shadow;
 pub mod text;
 pub mod texture;
 pub mod texture_atlas;
 
 pub mod prelude {
+    pub use crate::blob::{Blob, BlobSummary};
     pub use crate::{
-        camera::*, light::*, model::*, render_target::*, text::*, texture::*, texture_atlas::*, *,
+        camera::*, draw_target::*, fallback::*, geometry::*, light::*, model::*, render_target::*,
+        shadow::*, text::*, texture::*, texture_atlas::*, *,
     };
     pub use image;
     pub use wgpu_glyph;
@@ -1,53 +1,221 @@
+use cgmath::Rad;
 use std::collections::HashMap;
-use wgpu::util::DeviceExt;
+use thiserror::Error;
 use wgpu::*;
 
-const NUM_LIGHTS: usize = 4;
+use crate::camera::CameraWrapper;
+use crate::shadow::*;
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct LightUniform {
+/// Number of lights the buffer is first created with. It doubles whenever more are added.
+const INITIAL_CAPACITY: usize = 4;
+/// Most lights drawn on adapters without storage buffers, such as WebGL. Matches `LightBuffer` in lights_downlevel.wgsl.
+pub const DOWNLEVEL_MAX_LIGHTS: usize = 32;
+
+/// Returns true if the device can bind the lights and shadow views as storage buffers.
+/// Otherwise, as on WebGL, they are kept in uniform arrays holding at most [DOWNLEVEL_MAX_LIGHTS] lights and [DOWNLEVEL_MAX_SHADOW_VIEWS] views.
+pub fn storage_lights_supported(device: &Device) -> bool {
+    device.limits().max_storage_buffers_per_shader_stage >= 2
+}
+
+/// WGSL declarations of the light bind group (group 2) for the device. See [storage_lights_supported].
+/// The default model shader is appended to these, and custom model shaders which read lights should be too.
+pub fn light_shader_bindings(device: &Device) -> &'static str {
+    match storage_lights_supported(device) {
+        true => include_str!("shaders/lights.wgsl"),
+        false => include_str!("shaders/lights_downlevel.wgsl"),
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum LightError {
+    #[error("Cannot find light {0:?}. It may have been removed.")]
+    NotFound(LightId),
+}
+
+/// Stable handle to a light in a [LightWrapper]. Handles are never reused, so a removed light's handle stays invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LightId(u64);
+
+/// How a [Light] casts light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    /// Lights everything from one direction, like the sun. Ignores position, range and attenuation.
+    Directional,
+    /// Lights every direction from a position.
+    Point,
+    /// Lights a cone from a position. Full strength within `inner_angle` of the direction, fading out at `outer_angle`.
+    Spot {
+        inner_angle: Rad<f32>,
+        outer_angle: Rad<f32>,
+    },
+}
+
+/// A light. Colors are RGBA, where A indicates strength.
+/// ```ignore
+/// let lamp = Light::point([0.0, 2.0, 0.0], [1.0, 0.9, 0.8, 1.0])
+///     .with_range(10.0)
+///     .with_attenuation(1.0, 0.0, 0.1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
     pub position: [f32; 3],
-    _padding: u32,
+    /// Direction the light points in. Used by directional and spot lights.
+    pub direction: [f32; 3],
     pub color: [f32; 4],
+    /// Distance at which the light fades out completely. Unlimited if None.
+    pub range: Option<f32>,
+    /// Constant, linear and quadratic attenuation factors. Defaults to [1.0, 0.0, 0.0], which doesn't fall off with distance.
+    pub attenuation: [f32; 3],
+    /// Casts shadows if set. Ignored by point lights.
+    pub shadows: Option<ShadowSettings>,
 }
 
-impl Default for LightUniform {
-    fn default() -> Self {
+impl Light {
+    /// Creates a directional light pointing in `direction`.
+    pub fn directional(direction: [f32; 3], color: [f32; 4]) -> Self {
         Self {
+            kind: LightKind::Directional,
             position: [0.0; 3],
-            _padding: 0,
-            color: [0.0; 4],
+            direction,
+            color,
+            range: None,
+            attenuation: [1.0, 0.0, 0.0],
+            shadows: None,
         }
     }
-}
-impl LightUniform {
-    pub fn new(position: [f32; 3], color: [f32; 4]) -> Self {
+
+    /// Creates a point light at `position`.
+    pub fn point(position: [f32; 3], color: [f32; 4]) -> Self {
         Self {
+            kind: LightKind::Point,
             position,
-            _padding: 0,
+            direction: [0.0, -1.0, 0.0],
             color,
+            range: None,
+            attenuation: [1.0, 0.0, 0.0],
+            shadows: None,
+        }
+    }
+
+    /// Creates a spot light at `position` pointing in `direction`. Angles are measured from the direction.
+    pub fn spot<A: Into<Rad<f32>>>(
+        position: [f32; 3],
+        direction: [f32; 3],
+        inner_angle: A,
+        outer_angle: A,
+        color: [f32; 4],
+    ) -> Self {
+        Self {
+            kind: LightKind::Spot {
+                inner_angle: inner_angle.into(),
+                outer_angle: outer_angle.into(),
+            },
+            position,
+            direction,
+            color,
+            range: None,
+            attenuation: [1.0, 0.0, 0.0],
+            shadows: None,
+        }
+    }
+
+    /// Sets the distance at which the light fades out completely.
+    pub fn with_range(mut self, range: f32) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Sets the constant, linear and quadratic attenuation factors.
+    pub fn with_attenuation(mut self, constant: f32, linear: f32, quadratic: f32) -> Self {
+        self.attenuation = [constant, linear, quadratic];
+        self
+    }
+
+    /// Makes the light cast shadows.
+    pub fn with_shadows(mut self, settings: ShadowSettings) -> Self {
+        self.shadows = Some(settings);
+        self
+    }
+}
+
+/// GPU layout of a [Light]. Matches `Light` in lights.wgsl.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightUniform {
+    position: [f32; 3],
+    kind: u32,
+    direction: [f32; 3],
+    /// Zero if unlimited.
+    range: f32,
+    color: [f32; 4],
+    attenuation: [f32; 3],
+    cos_inner: f32,
+    cos_outer: f32,
+    shadow_bias: f32,
+    /// Index of the light's first shadow view, or -1 if it casts no shadows.
+    shadow_view: i32,
+    /// Number of shadow views. Directional lights have one per cascade.
+    shadow_view_count: u32,
+}
+
+impl From<&Light> for LightUniform {
+    fn from(light: &Light) -> Self {
+        let (kind, cos_inner, cos_outer) = match light.kind {
+            LightKind::Directional => (0, 1.0, 1.0),
+            LightKind::Point => (1, 1.0, 1.0),
+            LightKind::Spot {
+                inner_angle,
+                outer_angle,
+            } => (2, inner_angle.0.cos(), outer_angle.0.cos()),
+        };
+        Self {
+            position: light.position,
+            kind,
+            direction: light.direction,
+            range: light.range.unwrap_or(0.0).max(0.0),
+            color: light.color,
+            attenuation: light.attenuation,
+            cos_inner,
+            cos_outer,
+            shadow_bias: 0.0,
+            shadow_view: -1,
+            shadow_view_count: 0,
         }
     }
 }
 
+/// Start of the light buffer, followed by the lights. Matches `LightBuffer` in lights.wgsl.
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct LightBufferUniform {
+struct LightHeader {
     ambient: [f32; 4],
-    lights: [LightUniform; NUM_LIGHTS],
+    count: u32,
+    _padding: [u32; 3],
 }
 
 #[derive(Debug)]
 /// Wrapper for all light operations.
+/// Lights are stored in a storage buffer, which grows as lights are added.
+/// Where storage buffers aren't available, as on WebGL, only the first [DOWNLEVEL_MAX_LIGHTS] lights are drawn. See [storage_lights_supported].
+/// Call [LightWrapper::update] once per frame, before [LightWrapper::render_shadows] and [LightWrapper::bind_group].
 pub struct LightWrapper {
-    uniform: LightBufferUniform,
-    used_lights: usize,
+    ambient: [f32; 4],
+    lights: Vec<(LightId, Light)>,
+    indices: HashMap<LightId, usize>, // Points to the light's position in `lights`.
+    next_id: u64,
+
+    shadows: ShadowAtlas,
+    /// Index of each light casting shadows, and the atlas tiles of its views.
+    shadow_layout: Vec<(usize, Vec<ShadowTile>)>,
 
     dirty: bool,
+    /// False if the lights are kept in a uniform array of [DOWNLEVEL_MAX_LIGHTS].
+    storage: bool,
+    capacity: usize,
     buffer: Option<Buffer>,
+    bind_group: Option<BindGroup>,
 
-    map: HashMap<&'static str, usize>, //Hashmap that points to stored light uniforms.
     pub bind_group_layout: BindGroupLayout,
 }
 
@@ -55,29 +223,68 @@ impl LightWrapper {
     /// Creates a new light wrapper.
     /// Ambient light defaults to [1.0;4].
     pub fn new(device: &Device) -> Self {
+        let storage = storage_lights_supported(device);
+        let buffer_type = match storage {
+            true => BufferBindingType::Storage { read_only: true },
+            false => BufferBindingType::Uniform,
+        };
         let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            entries: &[BindGroupLayoutEntry {
-                binding: 0,
-                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: buffer_type,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Depth,
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Comparison),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: buffer_type,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
             label: Some("Light Buffer Layout"),
         });
 
         Self {
-            uniform: LightBufferUniform {
-                ambient: [1.0; 4],
-                lights: [LightUniform::default(); NUM_LIGHTS],
-            },
-            used_lights: 0,
+            ambient: [1.0; 4],
+            lights: Vec::new(),
+            indices: HashMap::new(),
+            next_id: 0,
+            shadows: ShadowAtlas::new(device, DEFAULT_SHADOW_ATLAS_SIZE),
+            shadow_layout: Vec::new(),
             dirty: true,
+            storage,
+            capacity: match storage {
+                true => INITIAL_CAPACITY,
+                false => DOWNLEVEL_MAX_LIGHTS,
+            },
             buffer: None,
-            map: HashMap::new(),
+            bind_group: None,
             bind_group_layout,
         }
     }
@@ -85,74 +292,290 @@ impl LightWrapper {
     /// Sets the ambient color in RGBA, where A indicates strength
     pub fn set_ambient(&mut self, color: [f32; 4]) {
         self.dirty = true;
-        self.uniform.ambient = color;
+        self.ambient = color;
     }
 
-    /// Adds a point light uniform.
-    pub fn add_light(&mut self, name: &'static str, light: LightUniform) -> Result<(), &str> {
-        if self.used_lights >= NUM_LIGHTS {
-            return Err("Exceeded maximum number of lights!");
-        }
+    /// Replaces the shadow atlas with one `size` texels wide and tall. Every shadow map must fit in the atlas.
+    pub fn set_shadow_atlas_size(&mut self, device: &Device, size: u32) {
+        self.shadows = ShadowAtlas::new(device, size);
+        self.bind_group = None;
         self.dirty = true;
-        self.uniform.lights[self.used_lights] = light;
-        self.map.insert(name, self.used_lights);
-        self.used_lights += 1;
-        Ok(())
     }
 
-    /// Returns a non-mut LightUniform.
-    pub fn get_light(&mut self, name: &'static str) -> Result<LightUniform, &str> {
-        if !self.map.contains_key(name) {
-            return Err("Cannot find light.");
-        }
-        Ok(self.uniform.lights[self.map[name]])
+    /// Adds a light and returns its handle.
+    pub fn add_light(&mut self, light: Light) -> LightId {
+        let id = LightId(self.next_id);
+        self.next_id += 1;
+        self.dirty = true;
+        self.indices.insert(id, self.lights.len());
+        self.lights.push((id, light));
+        id
     }
 
-    /// Replace the currently stored light uniform with a new one.
-    pub fn update_light(&mut self, name: &'static str, light: LightUniform) -> Result<(), &str> {
-        if !self.map.contains_key(name) {
-            return Err("Cannot find light.");
-        }
+    /// Returns the light, or None if it has been removed.
+    pub fn get_light(&self, id: LightId) -> Option<&Light> {
+        self.indices.get(&id).map(|&index| &self.lights[index].1)
+    }
+
+    /// Replaces the light.
+    pub fn update_light(&mut self, id: LightId, light: Light) -> Result<(), LightError> {
+        let index = *self.indices.get(&id).ok_or(LightError::NotFound(id))?;
         self.dirty = true;
-        self.uniform.lights[self.map[name]] = light;
+        self.lights[index].1 = light;
         Ok(())
     }
 
-    /// Removes the currently stored light uniform.
-    pub fn remove_light(&mut self, name: &'static str) -> Result<(), &str> {
-        if !self.map.contains_key(name) {
-            return Err("Cannot find light.");
+    /// Removes the light and returns it. Other lights keep their handles.
+    pub fn remove_light(&mut self, id: LightId) -> Result<Light, LightError> {
+        let index = self.indices.remove(&id).ok_or(LightError::NotFound(id))?;
+        self.dirty = true;
+        let (_, light) = self.lights.swap_remove(index);
+        if let Some((moved, _)) = self.lights.get(index) {
+            self.indices.insert(*moved, index);
         }
+        Ok(light)
+    }
+
+    /// Removes every light.
+    pub fn clear_lights(&mut self) {
         self.dirty = true;
-        self.uniform.lights[self.map[name]] = LightUniform::default();
-        // sort array
-        self.used_lights -= 1;
-        Ok(())
+        self.lights.clear();
+        self.indices.clear();
+    }
+
+    /// Number of lights.
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
     }
 
-    /// Gets the current light bind group and clears the light uniforms for this pass.
-    /// Updates any dirty buffers.
-    pub fn get_bind_group(&mut self, device: &wgpu::Device) -> BindGroup {
+    /// Number of lights drawn. Without storage buffers, lights past the first [DOWNLEVEL_MAX_LIGHTS] are left out.
+    fn drawn_len(&self) -> usize {
+        match self.storage {
+            true => self.lights.len(),
+            false => self.lights.len().min(DOWNLEVEL_MAX_LIGHTS),
+        }
+    }
+
+    /// Iterates over every light and its handle.
+    pub fn iter(&self) -> impl Iterator<Item = (LightId, &Light)> {
+        self.lights.iter().map(|(id, light)| (*id, light))
+    }
+
+    /// Uploads any changes, and fits the shadow maps of directional lights to the camera's view.
+    /// The buffers and bind group are only recreated when they need to grow.
+    /// Without storage buffers, lights past the first [DOWNLEVEL_MAX_LIGHTS] are left out.
+    pub fn update(&mut self, device: &Device, queue: &Queue, camera: &CameraWrapper) {
         if self.dirty {
-            self.buffer = Some(device.create_buffer_init(&util::BufferInitDescriptor {
+            self.layout_shadows();
+        }
+        let mut recreated = self
+            .shadows
+            .write_views(device, queue, self.shadow_views(camera));
+
+        if self.storage && self.lights.len() > self.capacity {
+            self.capacity = self.lights.len().next_power_of_two();
+            self.buffer = None;
+        }
+        if self.buffer.is_none() {
+            self.buffer = Some(device.create_buffer(&BufferDescriptor {
                 label: Some("Lights buffer"),
-                contents: bytemuck::cast_slice(&[self.uniform]),
-                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                size: (std::mem::size_of::<LightHeader>()
+                    + self.capacity * std::mem::size_of::<LightUniform>())
+                    as BufferAddress,
+                usage: match self.storage {
+                    true => BufferUsages::STORAGE,
+                    false => BufferUsages::UNIFORM,
+                } | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
             }));
+            recreated = true;
+            self.dirty = true;
+        }
+        if recreated || self.bind_group.is_none() {
+            self.bind_group = Some(
+                device.create_bind_group(&BindGroupDescriptor {
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: self
+                                .buffer
+                                .as_ref()
+                                .expect("Lights buffer not set!")
+                                .as_entire_binding(),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::TextureView(&self.shadows.view),
+                        },
+                        BindGroupEntry {
+                            binding: 2,
+                            resource: BindingResource::Sampler(&self.shadows.sampler),
+                        },
+                        BindGroupEntry {
+                            binding: 3,
+                            resource: self.shadows.view_buffer().as_entire_binding(),
+                        },
+                    ],
+                    label: Some("Lights bind group"),
+                }),
+            );
+        }
+
+        if self.dirty {
+            if self.drawn_len() < self.lights.len() {
+                log::warn!(
+                    "Only {} of {} lights are drawn without storage buffers.",
+                    self.drawn_len(),
+                    self.lights.len()
+                );
+            }
+            let mut lights = self
+                .lights
+                .iter()
+                .take(self.drawn_len())
+                .map(|(_, light)| LightUniform::from(light))
+                .collect::<Vec<_>>();
+            let mut first_view = 0;
+            for (index, tiles) in &self.shadow_layout {
+                let light = &mut lights[*index];
+                light.shadow_bias = self.lights[*index].1.shadows.unwrap_or_default().bias;
+                light.shadow_view = first_view as i32;
+                light.shadow_view_count = tiles.len() as u32;
+                first_view += tiles.len();
+            }
+            let header = LightHeader {
+                ambient: self.ambient,
+                count: lights.len() as u32,
+                _padding: [0; 3],
+            };
+            let buffer = self.buffer.as_ref().expect("Lights buffer not set!");
+            queue.write_buffer(buffer, 0, bytemuck::bytes_of(&header));
+            if !lights.is_empty() {
+                queue.write_buffer(
+                    buffer,
+                    std::mem::size_of::<LightHeader>() as BufferAddress,
+                    bytemuck::cast_slice(&lights),
+                );
+            }
             self.dirty = false;
         }
+    }
+
+    /// Gets the light bind group, as of the last [LightWrapper::update].
+    pub fn bind_group(&self) -> &BindGroup {
+        self.bind_group
+            .as_ref()
+            .expect("Lights bind group not set! Call LightWrapper::update first.")
+    }
+
+    /// Draws the shadow maps of every light casting shadows. See [ShadowAtlas::render].
+    pub fn render_shadows<'a, F>(
+        &'a self,
+        encoder: &'a mut CommandEncoder,
+        pipeline: &'a RenderPipeline,
+        draw: F,
+    ) where
+        F: FnMut(&mut RenderPass<'a>),
+    {
+        self.shadows.render(encoder, pipeline, draw);
+    }
+
+    /// Layout of the shadow pipeline's bind group.
+    pub fn shadow_pass_layout(&self) -> &BindGroupLayout {
+        &self.shadows.pass_layout
+    }
+
+    /// Assigns atlas tiles to every light casting shadows. Lights whose shadow maps don't fit cast no shadows,
+    /// nor do lights past the [ShadowAtlas::max_views] limit.
+    fn layout_shadows(&mut self) {
+        let atlas_size = self.shadows.size();
+        let requests = self
+            .lights
+            .iter()
+            .take(self.drawn_len())
+            .enumerate()
+            .filter_map(|(index, (_, light))| {
+                let settings = light.shadows?;
+                let count = match light.kind {
+                    LightKind::Directional => settings.cascades.clamp(1, MAX_CASCADES),
+                    LightKind::Spot { .. } => 1,
+                    LightKind::Point => return None,
+                };
+                Some((index, count as usize, settings.resolution.min(atlas_size)))
+            })
+            .collect::<Vec<_>>();
+        let sizes = requests
+            .iter()
+            .flat_map(|(_, count, resolution)| std::iter::repeat_n(*resolution, *count))
+            .collect::<Vec<_>>();
+
+        let mut tiles = pack_shadow_tiles(&sizes, atlas_size).into_iter();
+        let mut views_left = self.shadows.max_views().unwrap_or(usize::MAX);
+        self.shadow_layout = requests
+            .into_iter()
+            .filter_map(|(index, count, _)| {
+                let light_tiles = tiles.by_ref().take(count).collect::<Vec<_>>();
+                let light_tiles = light_tiles.into_iter().collect::<Option<Vec<_>>>();
+                if light_tiles.is_none() {
+                    log::warn!(
+                        "Shadow atlas is full! {:?} casts no shadows.",
+                        self.lights[index].0
+                    );
+                }
+                let light_tiles = light_tiles?;
+                if light_tiles.len() > views_left {
+                    log::warn!(
+                        "Too many shadow maps without storage buffers! {:?} casts no shadows.",
+                        self.lights[index].0
+                    );
+                    return None;
+                }
+                views_left -= light_tiles.len();
+                Some((index, light_tiles))
+            })
+            .collect();
+    }
 
-        device.create_bind_group(&BindGroupDescriptor {
-            layout: &self.bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: self
-                    .buffer
-                    .as_ref()
-                    .expect("Lights buffer not set!")
-                    .as_entire_binding(),
-            }],
-            label: None,
-        })
+    /// Returns the view of every shadow map, in the order of [LightWrapper::layout_shadows].
+    fn shadow_views(&self, camera: &CameraWrapper) -> Vec<ShadowView> {
+        self.shadow_layout
+            .iter()
+            .flat_map(|(index, tiles)| {
+                let light = &self.lights[*index].1;
+                let settings = light.shadows.unwrap_or_default();
+                let views = match light.kind {
+                    LightKind::Directional => directional_shadow_views(
+                        light.direction,
+                        &settings,
+                        &camera.camera,
+                        &camera.projection,
+                    ),
+                    LightKind::Spot { outer_angle, .. } => vec![(
+                        spot_shadow_view(
+                            light.position,
+                            light.direction,
+                            outer_angle,
+                            light.range.unwrap_or(settings.max_distance),
+                        ),
+                        f32::MAX,
+                    )],
+                    LightKind::Point => vec![],
+                };
+                tiles
+                    .iter()
+                    .zip(views)
+                    .map(|(tile, (view_proj, split_depth))| ShadowView {
+                        tile: *tile,
+                        view_proj,
+                        split_depth,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
     }
 }
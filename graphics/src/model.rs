@@ -1,8 +1,14 @@
 use crate::prelude::*;
 use cgmath::InnerSpace;
 use cgmath::*;
+use log::warn;
 use serde::*;
-use std::{path::Path, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    path::Path,
+    rc::Rc,
+};
 use thiserror::Error;
 use tobj::LoadOptions;
 use wgpu::util::DeviceExt;
@@ -54,76 +60,344 @@ impl Vertex for ModelVertex {
     }
 }
 
-/// A Material is a collection of textures used on a model.
+/// The scalar factors of a [Material], following glTF's metallic-roughness model. Each is multiplied with the matching texture.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MaterialFactors {
+    /// Linear color and alpha.
+    pub base_color: [f32; 4],
+    /// 0.0 is a dielectric, such as plastic or wood, and 1.0 is a metal.
+    pub metallic: f32,
+    /// 0.0 is a mirror, and 1.0 is completely rough.
+    pub roughness: f32,
+    /// Scales the X and Y of the normal map.
+    pub normal_scale: f32,
+    /// How much the occlusion texture darkens ambient light, from 0.0 to 1.0.
+    pub occlusion_strength: f32,
+    /// Linear emitted color. Black unless set.
+    pub emissive: [f32; 3],
+}
+impl Default for MaterialFactors {
+    /// A white, fairly rough dielectric which emits nothing.
+    fn default() -> Self {
+        Self {
+            base_color: [1.0; 4],
+            metallic: 0.0,
+            roughness: 0.5,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            emissive: [0.0; 3],
+        }
+    }
+}
+impl MaterialFactors {
+    /// Approximates a Blinn-Phong material, as described by older `.mtl` files.
+    /// The diffuse color becomes the base color, and the specular exponent is mapped to a roughness.
+    pub fn from_blinn_phong(diffuse: [f32; 3], shininess: f32) -> Self {
+        let [r, g, b] = diffuse;
+        Self {
+            base_color: [r, g, b, 1.0],
+            roughness: (2.0 / (shininess.max(0.0) + 2.0)).sqrt(),
+            ..Self::default()
+        }
+    }
+}
+impl From<&tobj::Material> for MaterialFactors {
+    /// Reads the PBR extension to `.mtl` (`Pr`, `Pm` and `Ke`) where present, and approximates the rest from `Kd`, `Ns` and `d`.
+    fn from(mat: &tobj::Material) -> Self {
+        // tobj reports colors missing from the .mtl as black.
+        // A black diffuse color alongside a diffuse map almost always means `Kd` was left out, not that the model is black.
+        let diffuse = match mat.diffuse == [0.0; 3] && !mat.diffuse_texture.is_empty() {
+            true => [1.0; 3],
+            false => mat.diffuse,
+        };
+        let param = |key: &str| {
+            mat.unknown_param
+                .get(key)
+                .map(|value| value.split_whitespace())
+        };
+        let scalar = |key: &str| param(key)?.next()?.parse::<f32>().ok();
+
+        let mut factors = Self::from_blinn_phong(diffuse, mat.shininess);
+        factors.base_color[3] = mat.dissolve;
+        if let Some(roughness) = scalar("Pr") {
+            factors.roughness = roughness;
+        }
+        if let Some(metallic) = scalar("Pm") {
+            factors.metallic = metallic;
+        }
+        if let Some(mut emissive) = param("Ke") {
+            for channel in factors.emissive.iter_mut() {
+                match emissive.next().and_then(|value| value.parse().ok()) {
+                    Some(value) => *channel = value,
+                    None => break,
+                }
+            }
+        }
+        factors
+    }
+}
+
+/// POD version of [MaterialFactors], bound at binding 4 of the texture bind group.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaterialUniform {
+    base_color: [f32; 4],
+    emissive: [f32; 4],
+    metallic: f32,
+    roughness: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+}
+impl From<MaterialFactors> for MaterialUniform {
+    fn from(factors: MaterialFactors) -> Self {
+        let [er, eg, eb] = factors.emissive;
+        Self {
+            base_color: factors.base_color,
+            emissive: [er, eg, eb, 1.0],
+            metallic: factors.metallic,
+            roughness: factors.roughness,
+            normal_scale: factors.normal_scale,
+            occlusion_strength: factors.occlusion_strength,
+        }
+    }
+}
+
+/// The texture slots of a [Material]. Each is multiplied with the matching [MaterialFactors] field.
+#[derive(Clone)]
+pub struct MaterialTextures {
+    /// sRGB color and alpha.
+    pub base_color: Rc<TextureWrapper>,
+    /// Linear. Roughness is read from the green channel and metallic from the blue channel, as in glTF.
+    pub metallic_roughness: Rc<TextureWrapper>,
+    /// Tangent-space normal map.
+    pub normal: Rc<TextureWrapper>,
+    /// Linear. Ambient occlusion is read from the red channel.
+    pub occlusion: Rc<TextureWrapper>,
+    /// sRGB emitted color.
+    pub emissive: Rc<TextureWrapper>,
+}
+impl MaterialTextures {
+    /// Creates the textures used in place of missing ones: [TextureWrapper::flat_normal] for the normal map,
+    /// and [TextureWrapper::white] for the rest, so that the factors show through unchanged.
+    pub fn defaults(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let white = Rc::new(TextureWrapper::white(device, queue));
+        Self {
+            base_color: white.clone(),
+            metallic_roughness: white.clone(),
+            normal: Rc::new(TextureWrapper::flat_normal(device, queue)),
+            occlusion: white.clone(),
+            emissive: white,
+        }
+    }
+    /// Iterates over every slot, in binding order.
+    pub fn iter(&self) -> impl Iterator<Item = &Rc<TextureWrapper>> {
+        [
+            &self.base_color,
+            &self.normal,
+            &self.metallic_roughness,
+            &self.occlusion,
+            &self.emissive,
+        ]
+        .into_iter()
+    }
+}
+
+/// A Material is a collection of textures and scalar factors used on a model, shaded with the metallic-roughness model.
 pub struct Material {
-    pub diffuse_texture: Rc<TextureWrapper>,
-    pub normal_texture: Rc<TextureWrapper>,
+    pub textures: MaterialTextures,
+    pub factors: MaterialFactors,
+    pub factor_buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
 }
 impl Material {
-    /// Creates a new [Material].
+    /// Creates a new [Material]. The textures are bound at even bindings of the texture bind group, each followed by its sampler,
+    /// with the factors at binding 4: base color at 0, normal at 2, metallic-roughness at 5, occlusion at 7 and emissive at 9.
     pub fn new(
         label: Option<&str>,
-        diffuse_texture: Rc<TextureWrapper>,
-        normal_texture: Rc<TextureWrapper>,
+        textures: MaterialTextures,
+        factors: MaterialFactors,
         device: &wgpu::Device,
         texture_layout: &wgpu::BindGroupLayout,
     ) -> Self {
+        let factor_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Factor Buffer", label)),
+            contents: bytemuck::cast_slice(&[MaterialUniform::from(factors)]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: 4,
+            resource: factor_buffer.as_entire_binding(),
+        }];
+        for (texture, binding) in textures.iter().zip([0, 2, 5, 7, 9]) {
+            entries.push(wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: binding + 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            });
+        }
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: texture_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&normal_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 3,
-                    resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
-                },
-            ],
+            entries: &entries,
             label,
         });
         Self {
-            diffuse_texture,
-            normal_texture,
+            textures,
+            factors,
+            factor_buffer,
             bind_group,
         }
     }
 }
+impl Material {
+    /// Creates a material without textures. Used for models without materials.
+    pub fn untextured(
+        factors: MaterialFactors,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        Self::new(
+            Some("Untextured Material"),
+            MaterialTextures::defaults(device, queue),
+            factors,
+            device,
+            texture_layout,
+        )
+    }
+    /// Creates the placeholder material, using [TextureWrapper::placeholder] as the base color.
+    pub fn placeholder(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        Self::new(
+            Some("Placeholder Material"),
+            MaterialTextures {
+                base_color: Rc::new(TextureWrapper::placeholder(device, queue)),
+                ..MaterialTextures::defaults(device, queue)
+            },
+            MaterialFactors::default(),
+            device,
+            texture_layout,
+        )
+    }
+}
 impl core::fmt::Debug for Material {
     fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         formatter.debug_list().entry(&self.bind_group).finish()
     }
 }
 
-/// TODO: FIXME: This only takes diffuse and normal textures.
-/// Fix this so that it can deal with all of them.
-/// A terrible little builder struct so we can have a portable intermediary format.
-/// Note that this is only useful if you cannot directly pass the [Texture]s into [Material]::new().
-#[derive(Debug, Serialize, Deserialize)]
+/// A portable intermediary format for [Material]s, holding each texture as encoded bytes.
+/// Importers fill in whichever slots their format provides; see [MaterialBuilder::from_obj].
+/// Note that this is only useful if you cannot directly pass the [TextureWrapper]s into [Material::new].
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct MaterialBuilder {
-    diffuse_texture: Vec<u8>,
-    normal_texture: Vec<u8>,
+    #[serde(with = "crate::blob::option")]
+    base_color_texture: Option<Vec<u8>>,
+    #[serde(with = "crate::blob::option")]
+    metallic_roughness_texture: Option<Vec<u8>>,
+    #[serde(with = "crate::blob::option")]
+    normal_texture: Option<Vec<u8>>,
+    #[serde(with = "crate::blob::option")]
+    occlusion_texture: Option<Vec<u8>>,
+    #[serde(with = "crate::blob::option")]
+    emissive_texture: Option<Vec<u8>>,
+    factors: MaterialFactors,
     label: Option<String>,
 }
 impl MaterialBuilder {
-    /// Creates a new MaterialBuilder. Note that this takes textures as bytes.
-    pub fn new(label: Option<String>, diffuse_texture: Vec<u8>, normal_texture: Vec<u8>) -> Self {
+    /// Creates a MaterialBuilder without textures and with the default [MaterialFactors].
+    pub fn new(label: Option<String>) -> Self {
         Self {
-            diffuse_texture,
-            normal_texture,
             label,
+            ..Self::default()
+        }
+    }
+    /// Creates a MaterialBuilder from a material in an `.mtl` file, reading its textures relative to `dir`.
+    /// `map_Kd` is the base color, `map_Bump` or `norm` the normal map, `map_Ka` the occlusion and `map_Ke` the emission.
+    /// `map_Pr` and `map_Pm` are packed into the metallic-roughness texture.
+    /// Textures the material does not name are left out. Textures which cannot be read are warned about and left empty,
+    /// so that building the material fails or falls back to a placeholder.
+    pub fn from_obj(mat: &tobj::Material, dir: &Path) -> Self {
+        let read = |name: &str| match name.is_empty() {
+            true => None,
+            false => Some(std::fs::read(dir.join(name)).unwrap_or_else(|e| {
+                warn!(
+                    "Unable to read texture `{}`. {}",
+                    dir.join(name).display(),
+                    e
+                );
+                vec![]
+            })),
+        };
+        let param = |key: &str| read(mat.unknown_param.get(key).map_or("", |name| name.trim()));
+        let normal_texture = match mat.normal_texture.is_empty() {
+            true => param("norm"),
+            false => read(&mat.normal_texture),
+        };
+        let metallic_roughness_texture =
+            pack_metallic_roughness(param("map_Pm").as_deref(), param("map_Pr").as_deref());
+        Self {
+            base_color_texture: read(&mat.diffuse_texture),
+            metallic_roughness_texture,
+            normal_texture,
+            occlusion_texture: read(&mat.ambient_texture),
+            emissive_texture: param("map_Ke"),
+            factors: MaterialFactors::from(mat),
+            label: Some(mat.name.clone()),
+        }
+    }
+    /// Sets the base color texture. Without one, the material is a solid [MaterialFactors::base_color].
+    pub fn with_base_color_texture(mut self, texture: Option<Vec<u8>>) -> Self {
+        self.base_color_texture = texture;
+        self
+    }
+    /// Sets the metallic-roughness texture, with roughness in the green channel and metallic in the blue channel.
+    pub fn with_metallic_roughness_texture(mut self, texture: Option<Vec<u8>>) -> Self {
+        self.metallic_roughness_texture = texture;
+        self
+    }
+    /// Sets the normal map. Without one, the surface is flat.
+    pub fn with_normal_texture(mut self, texture: Option<Vec<u8>>) -> Self {
+        self.normal_texture = texture;
+        self
+    }
+    /// Sets the ambient occlusion texture, read from the red channel.
+    pub fn with_occlusion_texture(mut self, texture: Option<Vec<u8>>) -> Self {
+        self.occlusion_texture = texture;
+        self
+    }
+    /// Sets the emissive texture. It has no effect unless [MaterialFactors::emissive] is set.
+    pub fn with_emissive_texture(mut self, texture: Option<Vec<u8>>) -> Self {
+        self.emissive_texture = texture;
+        self
+    }
+    /// Replaces the bytes of every texture slot that is set, e.g. to resize textures when baking.
+    pub fn map_textures<F>(mut self, mut f: F) -> Self
+    where
+        F: FnMut(Vec<u8>) -> Vec<u8>,
+    {
+        for texture in [
+            &mut self.base_color_texture,
+            &mut self.metallic_roughness_texture,
+            &mut self.normal_texture,
+            &mut self.occlusion_texture,
+            &mut self.emissive_texture,
+        ] {
+            *texture = texture.take().map(&mut f);
         }
+        self
+    }
+    /// Sets the material's factors. Defaults to [MaterialFactors::default].
+    pub fn with_factors(mut self, factors: MaterialFactors) -> Self {
+        self.factors = factors;
+        self
     }
-    /// Builds the material. This will fail if the textures cannot be created from the passed-in bits.
+    /// Builds the material. This will panic if the textures cannot be created from the passed-in bits.
     /// You might want to pass the newly-created Rc<Texture>'s to an AssetTypeMap.
     pub fn build(
         self,
@@ -131,45 +405,182 @@ impl MaterialBuilder {
         queue: &wgpu::Queue,
         texture_layout: &wgpu::BindGroupLayout,
     ) -> Material {
+        self.try_build(device, queue, texture_layout).unwrap()
+    }
+    /// Builds the material, returning an error if the textures cannot be created from the passed-in bits.
+    pub fn try_build(
+        self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_layout: &wgpu::BindGroupLayout,
+    ) -> Result<Material, TextureError> {
+        self.build_with_fallback(device, queue, texture_layout, FallbackMode::Strict)
+    }
+    /// Builds the material. Missing textures are replaced with those of [MaterialTextures::defaults].
+    /// In [FallbackMode::Placeholder], color textures which cannot be created are replaced with [TextureWrapper::placeholder],
+    /// the rest are replaced with the defaults, and a warning is logged.
+    /// In [FallbackMode::Strict] this behaves like [MaterialBuilder::try_build].
+    pub fn build_with_fallback(
+        self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_layout: &wgpu::BindGroupLayout,
+        mode: FallbackMode,
+    ) -> Result<Material, TextureError> {
         let name = self.label.unwrap_or("unnamed mesh".to_string());
-        let diffuse_texture = Rc::new(
-            TextureWrapper::from_bytes(
-                device,
-                queue,
-                &self.diffuse_texture[..],
-                &*format!("{} Diffuse", &name),
-                false,
-            )
-            .unwrap(),
-        );
-        let normal_texture = Rc::new(
-            TextureWrapper::from_bytes(
-                device,
-                queue,
-                &self.normal_texture[..],
-                &*format!("{} Normal", &name),
+        let defaults = MaterialTextures::defaults(device, queue);
+        // Only color textures are sRGB. The rest hold linear data, which is stored like a normal map.
+        let load =
+            |bytes: Option<Vec<u8>>, kind: &str, is_color: bool, default: &Rc<TextureWrapper>| {
+                let label = format!("{} {}", &name, kind);
+                let bytes = match bytes {
+                    Some(bytes) => bytes,
+                    None => return Ok(default.clone()),
+                };
+                match TextureWrapper::from_bytes(device, queue, &bytes, &label, !is_color) {
+                    Ok(texture) => Ok(Rc::new(texture)),
+                    Err(e) if mode.is_placeholder() => {
+                        warn!("{} could not be loaded; using a placeholder. {}", label, e);
+                        Ok(match is_color {
+                            true => Rc::new(TextureWrapper::placeholder(device, queue)),
+                            false => default.clone(),
+                        })
+                    }
+                    Err(e) => Err(e),
+                }
+            };
+        let textures = MaterialTextures {
+            base_color: load(
+                self.base_color_texture,
+                "Base Color",
                 true,
-            )
-            .unwrap(),
-        );
-        Material::new(
+                &defaults.base_color,
+            )?,
+            metallic_roughness: load(
+                self.metallic_roughness_texture,
+                "Metallic-Roughness",
+                false,
+                &defaults.metallic_roughness,
+            )?,
+            normal: load(self.normal_texture, "Normal", false, &defaults.normal)?,
+            occlusion: load(
+                self.occlusion_texture,
+                "Occlusion",
+                false,
+                &defaults.occlusion,
+            )?,
+            emissive: load(self.emissive_texture, "Emissive", true, &defaults.emissive)?,
+        };
+        Ok(Material::new(
             Some(&*name),
-            diffuse_texture,
-            normal_texture,
+            textures,
+            self.factors,
             device,
             texture_layout,
-        )
+        ))
     }
 }
 
+/// Packs separate grayscale metallic and roughness maps into one glTF-style texture, as a PNG.
+/// Missing maps are white, leaving the factor unchanged. Maps which cannot be decoded are warned about and treated as missing.
+fn pack_metallic_roughness(metallic: Option<&[u8]>, roughness: Option<&[u8]>) -> Option<Vec<u8>> {
+    let decode = |bytes: Option<&[u8]>| {
+        image::load_from_memory(bytes?)
+            .map_err(|e| warn!("Unable to decode metallic or roughness map. {}", e))
+            .ok()
+            .map(|image| image.to_luma8())
+    };
+    let (metallic, roughness) = (decode(metallic), decode(roughness));
+    let (width, height) = metallic.as_ref().or(roughness.as_ref())?.dimensions();
+    // Maps of different sizes are sampled to the size of the first.
+    let channel = |map: &Option<image::GrayImage>, x: u32, y: u32| match map {
+        Some(map) => {
+            let (w, h) = map.dimensions();
+            map.get_pixel(x * w / width, y * h / height).0[0]
+        }
+        None => 255,
+    };
+    let packed = image::RgbaImage::from_fn(width, height, |x, y| {
+        image::Rgba([
+            255,
+            channel(&roughness, x, y),
+            channel(&metallic, x, y),
+            255,
+        ])
+    });
+    let mut png = vec![];
+    image::DynamicImage::ImageRgba8(packed)
+        .write_to(
+            &mut std::io::Cursor::new(&mut png),
+            image::ImageOutputFormat::Png,
+        )
+        .ok()?;
+    Some(png)
+}
+
 /// A mesh describes part of the geometry of a model.
 #[derive(Debug)]
 pub struct Mesh {
     pub name: String,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
+    /// [wgpu::IndexFormat::Uint16] if every index fits, otherwise [wgpu::IndexFormat::Uint32].
+    pub index_format: wgpu::IndexFormat,
     pub num_elements: u32,
     pub material: usize,
+    /// Model-space bounds, for culling and picking.
+    pub bounds: MeshBounds,
+    /// Simplified levels of detail, from most to least detailed. Level 0 is the mesh itself.
+    pub lods: Vec<MeshLod>,
+}
+impl Mesh {
+    /// Returns the index buffer and number of indices for a level of detail, clamped to the levels available.
+    pub fn lod(&self, level: usize) -> (&wgpu::Buffer, u32) {
+        match level.min(self.lods.len()) {
+            0 => (&self.index_buffer, self.num_elements),
+            level => {
+                let lod = &self.lods[level - 1];
+                (&lod.index_buffer, lod.num_elements)
+            }
+        }
+    }
+}
+
+/// A simplified level of detail of a [Mesh]. Shares the mesh's vertex buffer.
+#[derive(Debug)]
+pub struct MeshLod {
+    pub index_buffer: wgpu::Buffer,
+    pub num_elements: u32,
+    /// The largest distance, in model space, that a vertex moved during simplification.
+    pub error: f32,
+}
+
+/// A simplified level of detail as stored in a [MeshBuilder]. Indexes into the builder's vertices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeshLodData {
+    pub indices: Vec<u32>,
+    pub error: f32,
+}
+
+/// Controls how levels of detail are generated by [MeshBuilder::generate_lods].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LodSettings {
+    /// The most simplified levels to generate, in addition to the full-detail mesh.
+    pub levels: usize,
+    /// Largest error allowed in the first simplified level, as a fraction of the mesh's bounding radius.
+    /// Each further level doubles it.
+    pub error_threshold: f32,
+    /// Levels which remove less than this fraction of the previous level's triangles are skipped.
+    pub min_reduction: f32,
+}
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            levels: 3,
+            error_threshold: 0.02,
+            min_reduction: 0.2,
+        }
+    }
 }
 
 /// A struct for creating meshes. Generally follows the builder pattern, but [generate] does not consume self.
@@ -181,6 +592,10 @@ pub struct MeshBuilder {
     name: Option<String>,
     material_id: Option<usize>,
     calculate_tangents: bool,
+    /// Computed by [MeshBuilder::optimize], or when the mesh is generated.
+    bounds: Option<MeshBounds>,
+    /// Generated by [MeshBuilder::generate_lods].
+    pub lods: Vec<MeshLodData>,
 }
 impl MeshBuilder {
     /// Creates a new MeshBuilder.
@@ -191,16 +606,113 @@ impl MeshBuilder {
             name: None,
             material_id: None,
             calculate_tangents: false,
+            bounds: None,
+            lods: vec![],
+        }
+    }
+    /// Creates a MeshBuilder from a mesh loaded by tobj with `single_index` and `triangulate`, calculating tangents.
+    /// Meshes without texture coordinates get zeroed ones, and meshes without normals get smooth normals.
+    pub fn from_obj(name: String, mesh: &tobj::Mesh) -> Self {
+        let num_vertices = mesh.positions.len() / 3;
+        let has_tex_coords = mesh.texcoords.len() >= num_vertices * 2;
+        let has_normals = mesh.normals.len() >= num_vertices * 3;
+
+        let vertices = (0..num_vertices)
+            .map(|i| ModelVertex {
+                position: [
+                    mesh.positions[i * 3],
+                    mesh.positions[i * 3 + 1],
+                    mesh.positions[i * 3 + 2],
+                ],
+                tex_coords: match has_tex_coords {
+                    true => [mesh.texcoords[i * 2], mesh.texcoords[i * 2 + 1]],
+                    false => [0.0; 2],
+                },
+                normal: match has_normals {
+                    true => [
+                        mesh.normals[i * 3],
+                        mesh.normals[i * 3 + 1],
+                        mesh.normals[i * 3 + 2],
+                    ],
+                    false => [0.0; 3],
+                },
+                tangent: [0.0; 3], //calculated by the builder
+                bitangent: [0.0; 3],
+            })
+            .collect();
+
+        let builder = Self::new(vertices, mesh.indices.clone())
+            .with_name(name)
+            .with_material_id(mesh.material_id.unwrap_or(0))
+            .with_tangents(true);
+        match has_normals {
+            true => builder,
+            false => builder.with_smooth_normals(),
         }
     }
     /// Allows you to replace the currently existing vertex array while conforming to builder pattern.
+    /// Levels of detail are discarded.
     pub fn with_vertices(mut self, vertices: Vec<ModelVertex>) -> Self {
         self.vertices = vertices;
+        self.bounds = None;
+        self.lods.clear();
         self
     }
     /// Allows you to replace the currently exiting index array while conforming to builder pattern.
+    /// Levels of detail are discarded.
     pub fn with_indices(mut self, indices: Vec<u32>) -> Self {
         self.indices = indices;
+        self.lods.clear();
+        self
+    }
+    /// Prepares the mesh for rendering: welds duplicate vertices, reorders triangles for the vertex cache and vertices
+    /// for fetching, and computes the mesh's bounds. Intended to run once, when assets are baked.
+    /// Levels of detail are discarded, so generate them afterwards.
+    pub fn optimize(mut self) -> Self {
+        self.lods.clear();
+        let (vertices, indices) = weld_vertices(&self.vertices, &self.indices);
+        let indices = optimize_vertex_cache(&indices, vertices.len());
+        let (vertices, indices) = optimize_vertex_fetch(&vertices, &indices);
+        self.vertices = vertices;
+        self.indices = indices;
+        self.bounds = Some(self.bounds());
+        self
+    }
+    /// Generates simplified levels of detail, replacing any generated before. See [LodSettings].
+    pub fn generate_lods(mut self, settings: &LodSettings) -> Self {
+        self.lods.clear();
+        let positions = self.vertices.iter().map(|v| v.position).collect::<Vec<_>>();
+        let radius = self.bounds().sphere.radius;
+        if radius <= 0.0 {
+            return self;
+        }
+        let mut triangles = self.indices.len();
+        for level in 0..settings.levels {
+            let max_error = settings.error_threshold * radius * 2f32.powi(level as i32);
+            // Vertices move at most a cell's diagonal.
+            let cell_size = max_error / 3f32.sqrt();
+            let (indices, error) = simplify_by_clustering(&positions, &self.indices, cell_size);
+            if indices.is_empty() {
+                break;
+            }
+            if indices.len() as f32 > triangles as f32 * (1.0 - settings.min_reduction) {
+                continue;
+            }
+            triangles = indices.len();
+            self.lods.push(MeshLodData {
+                indices: optimize_vertex_cache(&indices, self.vertices.len()),
+                error,
+            });
+        }
+        self
+    }
+    /// Returns the bounds of the mesh's vertices.
+    pub fn bounds(&self) -> MeshBounds {
+        MeshBounds::from_points(&self.vertices.iter().map(|v| v.position).collect::<Vec<_>>())
+    }
+    /// Sets bounds computed earlier, e.g. by an older bake, instead of computing them when the mesh is generated.
+    pub fn with_bounds(mut self, bounds: MeshBounds) -> Self {
+        self.bounds = Some(bounds);
         self
     }
     /// Adds a name to the mesh, used for debugging. Defaults to "unnamed mesh".
@@ -213,6 +725,29 @@ impl MeshBuilder {
         self.material_id = Some(material_id);
         self
     }
+    /// Replaces the normals with smooth normals, averaged from the faces around each vertex.
+    /// Vertices at the same position share a normal even if their texture coordinates differ.
+    pub fn with_smooth_normals(mut self) -> Self {
+        let key = |v: &ModelVertex| v.position.map(f32::to_bits);
+        let mut normals = HashMap::<[u32; 3], Vector3<f32>>::new();
+        for c in self.indices.chunks_exact(3) {
+            let [v0, v1, v2] = [0, 1, 2].map(|i| self.vertices[c[i] as usize]);
+            let pos0 = Vector3::from(v0.position);
+            // Not normalized, so larger faces contribute more.
+            let face = (Vector3::from(v1.position) - pos0).cross(Vector3::from(v2.position) - pos0);
+            for v in [v0, v1, v2] {
+                *normals.entry(key(&v)).or_insert_with(Vector3::zero) += face;
+            }
+        }
+        for v in &mut self.vertices {
+            let normal = normals.get(&key(v)).copied().unwrap_or_else(Vector3::zero);
+            v.normal = match normal.magnitude2() > 0.0 {
+                true => normal.normalize().into(),
+                false => [0.0, 1.0, 0.0],
+            };
+        }
+        self
+    }
     /// Calculates tangents and bitangents for the mesh.
     pub fn with_tangents(mut self, calculate_tangents: bool) -> Self {
         self.calculate_tangents = calculate_tangents;
@@ -230,45 +765,54 @@ impl MeshBuilder {
             contents: bytemuck::cast_slice(&self.vertices),
             usage: wgpu::BufferUsages::VERTEX,
         });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some(&format!("{:?} Index Buffer", name)),
-            contents: bytemuck::cast_slice(&self.indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
+        // 16-bit indices halve the size of the index buffer.
+        let index_format = match self.vertices.len() <= u16::MAX as usize + 1 {
+            true => wgpu::IndexFormat::Uint16,
+            false => wgpu::IndexFormat::Uint32,
+        };
+        let create_index_buffer = |indices: &[u32], label: String| {
+            let contents = match index_format {
+                wgpu::IndexFormat::Uint16 => {
+                    bytemuck::cast_slice(&indices.iter().map(|i| *i as u16).collect::<Vec<_>>())
+                        .to_vec()
+                }
+                wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(indices).to_vec(),
+            };
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&label),
+                contents: &contents,
+                usage: wgpu::BufferUsages::INDEX,
+            })
+        };
+        let index_buffer = create_index_buffer(&self.indices, format!("{:?} Index Buffer", name));
+        let lods = self
+            .lods
+            .iter()
+            .enumerate()
+            .map(|(i, lod)| MeshLod {
+                index_buffer: create_index_buffer(
+                    &lod.indices,
+                    format!("{:?} LOD {} Index Buffer", name, i + 1),
+                ),
+                num_elements: lod.indices.len() as u32,
+                error: lod.error,
+            })
+            .collect();
 
         Mesh {
             name,
             vertex_buffer,
             index_buffer,
+            index_format,
             num_elements: self.indices.len() as u32,
             material: self.material_id.unwrap_or(0),
+            bounds: self.bounds.unwrap_or_else(|| self.bounds()),
+            lods,
         }
     }
     /// Builds a [Mesh], consuming self.
     pub fn build(mut self, device: &wgpu::Device) -> Mesh {
-        if self.calculate_tangents {
-            self.calculate_tangents();
-        }
-        let name = self.name.unwrap_or("unnamed mesh".to_string());
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some(&format!("{:?} Vertex Buffer", name)),
-            contents: bytemuck::cast_slice(&self.vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some(&format!("{:?} Index Buffer", name)),
-            contents: bytemuck::cast_slice(&self.indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-
-        Mesh {
-            name,
-            vertex_buffer,
-            index_buffer,
-            num_elements: self.indices.len() as u32,
-            material: self.material_id.unwrap_or(0),
-        }
+        self.generate(device)
     }
 
     fn calculate_tangents(&mut self) {
@@ -302,6 +846,10 @@ impl MeshBuilder {
             // the solution!
 
             let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
+            // Triangles without distinct texture coordinates (e.g. meshes without UVs) have no defined tangent.
+            if !r.is_finite() {
+                continue;
+            }
             let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
             let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * r;
 
@@ -328,39 +876,83 @@ impl MeshBuilder {
         for (i, n) in triangles_included.into_iter().enumerate() {
             let denom = 1.0 / n as f32;
             let mut v = &mut vertices[i];
-            v.tangent = (cgmath::Vector3::from(v.tangent) * denom)
-                .normalize()
-                .into();
-            v.bitangent = (cgmath::Vector3::from(v.bitangent) * denom)
-                .normalize()
-                .into();
+            let tangent = (cgmath::Vector3::from(v.tangent) * denom).normalize();
+            let bitangent = (cgmath::Vector3::from(v.bitangent) * denom).normalize();
+            if tangent.x.is_finite() && bitangent.x.is_finite() {
+                v.tangent = tangent.into();
+                v.bitangent = bitangent.into();
+            } else {
+                // Pick any tangent space around the normal.
+                let normal = cgmath::Vector3::from(v.normal);
+                let axis = match normal.x.abs() < 0.9 {
+                    true => cgmath::Vector3::unit_x(),
+                    false => cgmath::Vector3::unit_y(),
+                };
+                let tangent = normal.cross(axis).normalize();
+                v.tangent = tangent.into();
+                v.bitangent = normal.cross(tangent).into();
+            }
         }
     }
 }
 
-/// The ModelInstance struct defines the position and rotation of a model instance.
-#[derive(Debug)]
+/// The ModelInstance struct defines the transform and appearance of a model instance.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ModelInstance {
     pub position: Vector3<f32>,
     pub rotation: Quaternion<f32>,
+    /// Scale along each of the model's axes, applied before rotation.
+    pub scale: Vector3<f32>,
+    /// RGBA color multiplied with the material's base color.
+    pub tint: [f32; 4],
+    /// Free for custom shaders. Passed to the fragment stage as `instance_data`, but unused by the default shader.
+    pub data: [f32; 4],
 }
 impl ModelInstance {
-    /// Creates a new instance at the given position and rotation.
+    /// Creates a new instance at the given position and rotation, with no scale or tint.
     pub fn new(position: Vector3<f32>, rotation: Quaternion<f32>) -> Self {
-        Self { position, rotation }
+        Self {
+            position,
+            rotation,
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            tint: [1.0; 4],
+            data: [0.0; 4],
+        }
     }
     /// Creates an instance at the origin with no rotation.
     pub fn at_origin() -> Self {
-        Self {
-            position: Vector3::zero(),
-            rotation: Quaternion::zero(),
-        }
+        Self::new(Vector3::zero(), Quaternion::one())
+    }
+    pub fn with_scale<V: Into<Vector3<f32>>>(mut self, scale: V) -> Self {
+        self.scale = scale.into();
+        self
+    }
+    pub fn with_tint(mut self, tint: [f32; 4]) -> Self {
+        self.tint = tint;
+        self
+    }
+    pub fn with_data(mut self, data: [f32; 4]) -> Self {
+        self.data = data;
+        self
+    }
+    /// Returns the largest scale along any axis.
+    pub fn max_scale(&self) -> f32 {
+        let [x, y, z]: [f32; 3] = self.scale.into();
+        x.abs().max(y.abs()).max(z.abs())
     }
     /// Converts this easy-to-manipulate struct to the POD version used in shaders, [InstanceRaw].
     pub fn as_raw(&self) -> InstanceRaw {
+        let linear = Matrix3::from(self.rotation) * Matrix3::from_diagonal(self.scale);
+        // Normals need the inverse transpose so they stay perpendicular under non-uniform scale.
+        // A zero scale has no inverse, so those normals just follow the rotation.
+        let normal = linear
+            .invert()
+            .map_or(Matrix3::from(self.rotation), |inverse| inverse.transpose());
         InstanceRaw {
-            model: (Matrix4::from_translation(self.position) * Matrix4::from(self.rotation)).into(),
-            normal: Matrix3::from(self.rotation).into(),
+            model: (Matrix4::from_translation(self.position) * Matrix4::from(linear)).into(),
+            normal: normal.into(),
+            tint: self.tint,
+            data: self.data,
         }
     }
     // pub fn from_transform(t: sundile_scripting::components::Transform) -> Self {
@@ -377,6 +969,8 @@ impl ModelInstance {
 pub struct InstanceRaw {
     pub model: [[f32; 4]; 4],
     pub normal: [[f32; 3]; 3],
+    pub tint: [f32; 4],
+    pub data: [f32; 4],
 }
 impl Vertex for InstanceRaw {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
@@ -421,68 +1015,220 @@ impl Vertex for InstanceRaw {
                     shader_location: 11,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 25]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 29]>() as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
 }
 
+/// Stable handle to an instance in an [InstanceCache]. Handles are never reused, so a removed instance's handle stays invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InstanceId(u64);
+
 /// An InstanceCache holds all the instancing information for a particular model.
+/// Uploads only write the instances which changed or moved within the buffer, so animating a few instances is cheap.
 /// TODO: Generalize. Create an InstanceCache that can handle any type.
 /// Create an Instantiable trait for anything that can be pushed into a cache.
 #[derive(Debug)]
 pub struct InstanceCache {
     instances: Vec<ModelInstance>,
-    buffer: Option<wgpu::Buffer>,
+    ids: Vec<InstanceId>,
+    indices: HashMap<InstanceId, usize>, // Points to the instance's position in `instances`.
+    next_id: u64,
+    /// Set when instances are inserted or removed.
     dirty: bool,
-    // ranges: Vec<Range>,
+    /// Instances updated since the last upload.
+    changed: HashSet<InstanceId>,
+
+    buffer: Option<wgpu::Buffer>,
+    /// Number of instances the buffer can hold.
+    capacity: usize,
+    /// Instance in each slot of the buffer, as of the last upload.
+    slots: Vec<InstanceId>,
+    /// Number of instances written in the last upload.
+    uploaded: usize,
+    /// Level of detail of each instance as of the last upload, or None if it was culled.
+    lod_levels: Vec<Option<usize>>,
+    /// Range of the buffer holding the instances at each level of detail.
+    lod_ranges: Vec<Range<u32>>,
+    /// Range of the buffer holding the culled instances, after every level of detail.
+    culled_range: Range<u32>,
 }
 impl InstanceCache {
     /// Create a new InstanceCache.
     pub fn new() -> Self {
         Self {
             instances: vec![],
-            buffer: None,
+            ids: vec![],
+            indices: HashMap::new(),
+            next_id: 0,
             dirty: true,
-            // ranges: vec![],
+            changed: HashSet::new(),
+            buffer: None,
+            capacity: 0,
+            slots: vec![],
+            uploaded: 0,
+            lod_levels: vec![],
+            lod_ranges: vec![],
+            culled_range: 0..0,
         }
     }
-    /// Insert a new [ModelInstance] to the cache.
-    pub fn insert(&mut self, instance: ModelInstance) {
+    /// Returns the instances in the cache.
+    pub fn instances(&self) -> &[ModelInstance] {
+        &self.instances
+    }
+    /// Iterates over every instance and its handle.
+    pub fn iter(&self) -> impl Iterator<Item = (InstanceId, &ModelInstance)> {
+        self.ids.iter().copied().zip(self.instances.iter())
+    }
+    /// Insert a new [ModelInstance] to the cache and returns its handle.
+    pub fn insert(&mut self, instance: ModelInstance) -> InstanceId {
+        let id = InstanceId(self.next_id);
+        self.next_id += 1;
         self.dirty = true;
+        self.indices.insert(id, self.instances.len());
         self.instances.push(instance);
+        self.ids.push(id);
+        id
+    }
+    /// Returns the instance, or None if it has been removed.
+    pub fn get(&self, id: InstanceId) -> Option<&ModelInstance> {
+        self.indices.get(&id).map(|&index| &self.instances[index])
+    }
+    /// Replaces the instance. Only this instance is uploaded on the next update.
+    pub fn update(&mut self, id: InstanceId, instance: ModelInstance) -> Result<(), InstanceError> {
+        let index = *self.indices.get(&id).ok_or(InstanceError::NotFound(id))?;
+        self.instances[index] = instance;
+        self.changed.insert(id);
+        Ok(())
+    }
+    /// Removes the instance and returns it. Other instances keep their handles.
+    pub fn remove(&mut self, id: InstanceId) -> Result<ModelInstance, InstanceError> {
+        let index = self
+            .indices
+            .remove(&id)
+            .ok_or(InstanceError::NotFound(id))?;
+        self.dirty = true;
+        self.changed.remove(&id);
+        self.ids.swap_remove(index);
+        if let Some(moved) = self.ids.get(index) {
+            self.indices.insert(*moved, index);
+        }
+        Ok(self.instances.swap_remove(index))
     }
     /// Remove all [ModelInstance]s from the cache.
     pub fn clear(&mut self) {
         self.dirty = true;
         self.instances.clear();
+        self.ids.clear();
+        self.indices.clear();
+        self.changed.clear();
     }
-    /// Updates the cache's internal buffer if necessary.
-    pub fn update(&mut self, device: &wgpu::Device) {
-        if self.dirty {
-            self.buffer = Some(
-                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: None,
-                    contents: bytemuck::cast_slice(
-                        &self
-                            .instances
-                            .iter()
-                            .map(ModelInstance::as_raw)
-                            .collect::<Vec<_>>(),
-                    ),
-                    usage: wgpu::BufferUsages::VERTEX,
-                }),
+    /// Uploads any changes to the cache's internal buffer. All instances are drawn at full detail.
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.upload_lods(device, queue, |_| Some(0));
+    }
+    /// Uploads any changes to the cache's internal buffer, grouping instances by the level of detail `select_lod` returns for each.
+    /// Instances it returns None for are culled. They're compacted after the visible instances, where [Model::render] skips them.
+    /// The buffer grows as needed. Otherwise, only slots whose instance changed or moved are written.
+    pub fn upload_lods<F>(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, select_lod: F)
+    where
+        F: Fn(&ModelInstance) -> Option<usize>,
+    {
+        let levels = self.instances.iter().map(select_lod).collect::<Vec<_>>();
+        self.uploaded = 0;
+        if !self.dirty && self.changed.is_empty() && levels == self.lod_levels {
+            return;
+        }
+
+        // Sort instances by level so that each level is a contiguous range of the buffer, with culled instances last.
+        let sort_key = |i: &usize| levels[*i].unwrap_or(usize::MAX);
+        let mut order = (0..self.instances.len()).collect::<Vec<_>>();
+        order.sort_by_key(sort_key);
+        let level_count = levels.iter().flatten().max().map_or(0, |max| max + 1);
+        self.lod_ranges = (0..level_count)
+            .map(|level| {
+                let start = order.partition_point(|i| sort_key(i) < level) as u32;
+                let end = order.partition_point(|i| sort_key(i) <= level) as u32;
+                start..end
+            })
+            .collect();
+        let visible = self.lod_ranges.last().map_or(0, |range| range.end);
+        self.culled_range = visible..order.len() as u32;
+
+        let slots = order.iter().map(|i| self.ids[*i]).collect::<Vec<_>>();
+        if self.buffer.is_none() || slots.len() > self.capacity {
+            self.capacity = slots.len().next_power_of_two();
+            self.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Instance Buffer"),
+                size: (self.capacity * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+            self.slots.clear();
+        }
+
+        // Write each run of slots whose instance changed or moved.
+        let buffer = self.buffer.as_ref().unwrap();
+        let stale = |slot: usize| {
+            self.slots.get(slot) != Some(&slots[slot]) || self.changed.contains(&slots[slot])
+        };
+        let mut slot = 0;
+        while slot < slots.len() {
+            if !stale(slot) {
+                slot += 1;
+                continue;
+            }
+            let start = slot;
+            while slot < slots.len() && stale(slot) {
+                slot += 1;
+            }
+            let raw = order[start..slot]
+                .iter()
+                .map(|i| self.instances[*i].as_raw())
+                .collect::<Vec<_>>();
+            queue.write_buffer(
+                buffer,
+                (start * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+                bytemuck::cast_slice(&raw),
             );
+            self.uploaded += raw.len();
         }
+
+        self.slots = slots;
+        self.changed.clear();
+        self.lod_levels = levels;
         self.dirty = false;
     }
-    /// Sets the ranges of instances to be displayed. This is used so as not to render any instances that are off-screen.
-    pub fn set_ranges(&mut self) {
-        // This should allow you to set which instances to render.
-        // Possibly add helpers for all, none
-        todo!()
+    /// Returns the range of instances drawn at each level of detail, as of the last upload.
+    pub fn lod_ranges(&self) -> &[Range<u32>] {
+        &self.lod_ranges
+    }
+    /// Returns how many instances were culled in the last upload.
+    pub fn culled_count(&self) -> usize {
+        self.culled_range.len()
+    }
+    /// Returns how many instances were written to the GPU in the last upload.
+    pub fn uploaded_count(&self) -> usize {
+        self.uploaded
     }
 }
 
+#[derive(Error, Debug, PartialEq)]
+pub enum InstanceError {
+    #[error("Cannot find instance {0:?}. It may have been removed.")]
+    NotFound(InstanceId),
+}
+
 #[derive(Error, Debug)]
 pub enum ModelError {
     #[error("Failed to load model: `{0}`")]
@@ -529,57 +1275,27 @@ impl Model {
 
         let mut materials = Vec::new();
         for mat in obj_materials {
-            let diffuse_path = mat.diffuse_texture;
-            let diffuse_texture = Rc::new(TextureWrapper::load(
-                device,
-                queue,
-                containing_folder.join(diffuse_path),
-                false,
-            )?);
-
-            let normal_path = mat.normal_texture;
-            let normal_texture = Rc::new(TextureWrapper::load(
+            materials.push(Rc::new(
+                MaterialBuilder::from_obj(&mat, containing_folder).try_build(
+                    device,
+                    queue,
+                    texture_layout,
+                )?,
+            ));
+        }
+        if materials.is_empty() {
+            materials.push(Rc::new(Material::untextured(
+                MaterialFactors::default(),
                 device,
                 queue,
-                containing_folder.join(normal_path),
-                true,
-            )?);
-
-            materials.push(Rc::new(Material::new(
-                Some(mat.name.as_str()),
-                diffuse_texture,
-                normal_texture,
-                &device,
                 texture_layout,
             )));
         }
 
         let mut meshes = Vec::new();
         for m in obj_models {
-            let mut vertices = Vec::new();
-            for i in 0..m.mesh.positions.len() / 3 {
-                vertices.push(ModelVertex {
-                    position: [
-                        m.mesh.positions[i * 3],
-                        m.mesh.positions[i * 3 + 1],
-                        m.mesh.positions[i * 3 + 2],
-                    ],
-                    tex_coords: [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]],
-                    normal: [
-                        m.mesh.normals[i * 3],
-                        m.mesh.normals[i * 3 + 1],
-                        m.mesh.normals[i * 3 + 2],
-                    ],
-                    tangent: [0.0; 3], //calculated below
-                    bitangent: [0.0; 3],
-                });
-            }
-
             meshes.push(Rc::new(
-                MeshBuilder::new(vertices, m.mesh.indices.clone())
-                    .with_name(path.as_ref().display().to_string())
-                    .with_material_id(m.mesh.material_id.unwrap_or(0))
-                    .with_tangents(true)
+                MeshBuilder::from_obj(path.as_ref().display().to_string(), &m.mesh)
                     .generate(device),
             ));
         }
@@ -591,6 +1307,133 @@ impl Model {
         })
     }
 
+    /// Creates a unit cube centered on the origin with the placeholder material. Used in place of missing models.
+    pub fn unit_cube(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        // One face per axis direction: (normal, u axis, v axis).
+        let faces: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+            ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+            ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+            ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+            ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+            ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ];
+        let mut vertices = Vec::with_capacity(24);
+        let mut indices = Vec::with_capacity(36);
+        for (normal, u, v) in faces {
+            let (normal, u, v) = (Vector3::from(normal), Vector3::from(u), Vector3::from(v));
+            let base = vertices.len() as u32;
+            for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                vertices.push(ModelVertex {
+                    position: ((normal + u * su + v * sv) * 0.5).into(),
+                    tex_coords: [(su + 1.0) * 0.5, (1.0 - sv) * 0.5],
+                    normal: normal.into(),
+                    tangent: [0.0; 3], //calculated by the builder
+                    bitangent: [0.0; 3],
+                });
+            }
+            indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+
+        Self {
+            meshes: vec![Rc::new(
+                MeshBuilder::new(vertices, indices)
+                    .with_name("Unit Cube".into())
+                    .with_tangents(true)
+                    .build(device),
+            )],
+            materials: vec![Rc::new(Material::placeholder(
+                device,
+                queue,
+                texture_layout,
+            ))],
+            instance_cache: InstanceCache::new(),
+        }
+    }
+
+    /// Returns the error of each level of detail: the largest error among the model's meshes at that level.
+    /// Level 0 is full detail and has no error. Meshes with fewer levels use their least detailed one.
+    pub fn lod_errors(&self) -> Vec<f32> {
+        let level_count = self
+            .meshes
+            .iter()
+            .map(|mesh| mesh.lods.len())
+            .max()
+            .unwrap_or(0);
+        (0..=level_count)
+            .map(|level| {
+                self.meshes
+                    .iter()
+                    .filter_map(|mesh| match level.min(mesh.lods.len()) {
+                        0 => None,
+                        level => Some(mesh.lods[level - 1].error),
+                    })
+                    .fold(0.0, f32::max)
+            })
+            .collect()
+    }
+
+    /// Updates the instance buffer, culling instances outside the camera's view by the model's bounding sphere.
+    /// Visible instances use the least detailed level whose error would appear smaller than [CameraWrapper::lod_pixel_error] pixels.
+    pub fn update_instances(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera: &CameraWrapper,
+    ) {
+        let errors = self.lod_errors();
+        let bounds = self.bounds();
+        let frustum = camera.frustum();
+        self.instance_cache.upload_lods(device, queue, |instance| {
+            let center = bounds.map_or(Vector3::zero(), |bounds| bounds.sphere.center.into());
+            let position = instance.position
+                + instance
+                    .rotation
+                    .rotate_vector(center.mul_element_wise(instance.scale));
+            let scale = instance.max_scale();
+            if let Some(bounds) = bounds {
+                let sphere = BoundingSphere {
+                    center: position.into(),
+                    radius: bounds.sphere.radius * scale,
+                };
+                if !frustum.intersects_sphere(&sphere) {
+                    return None;
+                }
+            }
+            // Errors are in model space, so scaling the instance scales them too.
+            let tolerance = camera
+                .lod_tolerance(camera.camera.pos.distance(Point3::from_vec(position)))
+                / scale;
+            Some(
+                errors
+                    .iter()
+                    .rposition(|error| *error <= tolerance)
+                    .unwrap_or(0),
+            )
+        });
+    }
+
+    /// Returns the combined bounds of the model's meshes, or None if it has no meshes.
+    pub fn bounds(&self) -> Option<MeshBounds> {
+        self.meshes
+            .iter()
+            .map(|mesh| mesh.bounds)
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// Creates a new model sharing this model's meshes and materials, with its own empty set of instances.
+    pub fn share_geometry(&self) -> Self {
+        Self {
+            meshes: self.meshes.clone(),
+            materials: self.materials.clone(),
+            instance_cache: InstanceCache::new(),
+        }
+    }
+
     /// This function renders all of the model's instances to the screen.
     pub fn render<'r>(
         &'r self,
@@ -601,15 +1444,38 @@ impl Model {
         render_pass.set_vertex_buffer(1, self.instance_cache.buffer.as_ref().unwrap().slice(..));
         for mesh in &self.meshes {
             render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
             render_pass.set_bind_group(0, &self.materials[mesh.material].bind_group, &[]);
             render_pass.set_bind_group(1, camera_bind_group, &[]);
             render_pass.set_bind_group(2, light_bind_group, &[]);
-            render_pass.draw_indexed(
-                0..mesh.num_elements,
-                0,
-                0..self.instance_cache.instances.len() as u32,
-            ); //TODO: Add actual ranges.
+            self.draw_lods(render_pass, mesh);
+        }
+    }
+
+    /// Renders all of the model's instances into a shadow map. Uses the same instance buffer and levels of detail as [Model::render].
+    /// Culled instances may still cast shadows into view, so they're drawn too, at the least detailed level.
+    pub fn render_shadow<'r>(&'r self, render_pass: &mut wgpu::RenderPass<'r>) {
+        render_pass.set_vertex_buffer(1, self.instance_cache.buffer.as_ref().unwrap().slice(..));
+        for mesh in &self.meshes {
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            self.draw_lods(render_pass, mesh);
+            let culled = self.instance_cache.culled_range.clone();
+            if !culled.is_empty() {
+                let (index_buffer, num_elements) = mesh.lod(usize::MAX);
+                render_pass.set_index_buffer(index_buffer.slice(..), mesh.index_format);
+                render_pass.draw_indexed(0..num_elements, 0, culled);
+            }
+        }
+    }
+
+    /// One draw per level of detail, each covering the instances grouped at that level.
+    fn draw_lods<'r>(&'r self, render_pass: &mut wgpu::RenderPass<'r>, mesh: &'r Mesh) {
+        for (level, instances) in self.instance_cache.lod_ranges.iter().enumerate() {
+            if instances.is_empty() {
+                continue;
+            }
+            let (index_buffer, num_elements) = mesh.lod(level);
+            render_pass.set_index_buffer(index_buffer.slice(..), mesh.index_format);
+            render_pass.draw_indexed(0..num_elements, 0, instances.clone());
         }
     }
 }
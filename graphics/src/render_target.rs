@@ -1,53 +1,94 @@
+use std::num::NonZeroU32;
+use thiserror::Error;
 use wgpu::{CompositeAlphaMode, InstanceDescriptor};
 
 use crate::*;
 
+/// Color format of offscreen render targets.
+pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Error type for reading frames back from a [RenderTarget].
+#[derive(Error, Debug)]
+pub enum CaptureError {
+    #[error(
+        "No frame to read. Window targets must call capture_next_frame before rendering the frame."
+    )]
+    NoFrame,
+    #[error("Frames can't be read back on this platform.")]
+    Unsupported,
+    #[error("Unable to read frames with texture format {0:?}.")]
+    Format(wgpu::TextureFormat),
+    #[error("Unable to map frame buffer: {0}")]
+    Map(#[from] wgpu::BufferAsyncError),
+    #[error("Unable to write image: {0}")]
+    Image(#[from] image::ImageError),
+}
+
+/// Layout of the bind group of a [Material]. See [Material::new] for the bindings.
 fn get_texture_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    let texture = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        },
+        count: None,
+    };
+    let sampler = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    };
     device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         entries: &[
+            // Base color
+            texture(0),
+            sampler(1),
+            // Normal map
+            texture(2),
+            sampler(3),
+            // Material factors
             wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
+                binding: 4,
                 visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 2,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    multisampled: false,
-                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    view_dimension: wgpu::TextureViewDimension::D2,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
                 },
                 count: None,
             },
-            wgpu::BindGroupLayoutEntry {
-                binding: 3,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
-            },
+            // Metallic-roughness
+            texture(5),
+            sampler(6),
+            // Occlusion
+            texture(7),
+            sampler(8),
+            // Emissive
+            texture(9),
+            sampler(10),
         ],
         label: Some("texture_bind_group_layout"),
     })
 }
 
+/// A device without a window, used to build assets. Frames are drawn into a texture which is never shown,
+/// so renderers can be exercised in tests. Use [RenderTarget::offscreen] to read frames back.
 pub struct HeadlessRenderTarget {
     pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub instance: wgpu::Instance,
     pub texture_layout: wgpu::BindGroupLayout,
+
+    config: wgpu::SurfaceConfiguration,
+    frame_texture: Option<wgpu::Texture>,
+    encoder: Option<wgpu::CommandEncoder>,
+    color_view: Option<wgpu::TextureView>,
+    depth_view: Option<wgpu::TextureView>,
 }
 impl HeadlessRenderTarget {
     pub async fn new(enable_tracing: bool, label: Option<&str>) -> Self {
@@ -73,10 +114,11 @@ impl HeadlessRenderTarget {
             trace_path = Some(path);
         }
 
+        // Software adapters don't support SPIR-V passthrough, so it's only requested where available.
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::SPIRV_SHADER_PASSTHROUGH,
+                    features: adapter.features() & wgpu::Features::SPIRV_SHADER_PASSTHROUGH,
                     limits: wgpu::Limits::default(),
                     label,
                 },
@@ -93,17 +135,290 @@ impl HeadlessRenderTarget {
             queue,
             instance,
             texture_layout,
+
+            config: wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                format: OFFSCREEN_FORMAT,
+                width: 1,
+                height: 1,
+                present_mode: wgpu::PresentMode::Fifo,
+                alpha_mode: CompositeAlphaMode::Auto,
+                view_formats: vec![],
+            },
+            frame_texture: None,
+            encoder: None,
+            color_view: None,
+            depth_view: None,
         }
     }
+
+    /// Sets the size of the frames drawn to this target, in pixels. Defaults to 1x1.
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.config.width = width.max(1);
+        self.config.height = height.max(1);
+        self.frame_texture = None;
+        self
+    }
 }
 
+/// Requests a device, writing an API trace to ./dbg/trace if enabled.
+async fn request_device(
+    adapter: &wgpu::Adapter,
+    enable_tracing: bool,
+    label: Option<&str>,
+) -> (wgpu::Device, wgpu::Queue) {
+    let mut trace_path = None;
+    let dir = format!(
+        "./dbg/trace/{}__{}",
+        chrono::Local::now().format("%F-%s"),
+        label.unwrap_or_else(|| "UNLABLED")
+    );
+    let path = std::path::Path::new(&*dir);
+    if enable_tracing {
+        use log::debug;
+        std::fs::create_dir_all(&path).expect("Unable to create tracing path!");
+        trace_path = Some(path);
+        debug!("Render target tracing enabled.");
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    let limits = wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits());
+    #[cfg(not(target_arch = "wasm32"))]
+    let limits = wgpu::Limits::default();
+
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                features: wgpu::Features::default(),
+                limits,
+                label,
+            },
+            trace_path,
+        )
+        .await
+        .unwrap()
+}
+
+/// A frame copied into a buffer, waiting to be read back.
+struct FrameCapture {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    format: wgpu::TextureFormat,
+}
+impl FrameCapture {
+    /// Encodes a copy of the texture, which must be an 8-bit RGBA or BGRA texture.
+    fn new(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> Self {
+        let (width, height) = (config.width, config.height);
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (width * 4).div_ceil(align) * align;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Capture Buffer"),
+            size: padded_bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        Self {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            format: config.format,
+        }
+    }
+
+    /// Waits for the copy, and converts it to an RGBA image without row padding.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read(self, device: &wgpu::Device) -> Result<image::RgbaImage, CaptureError> {
+        let slice = self.buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv().expect("Frame capture was dropped")?;
+
+        let row_size = self.width as usize * 4;
+        let mut pixels = Vec::with_capacity(row_size * self.height as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks(self.padded_bytes_per_row as usize)
+        {
+            pixels.extend_from_slice(&row[..row_size]);
+        }
+        self.buffer.unmap();
+
+        if matches!(
+            self.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            pixels
+                .chunks_exact_mut(4)
+                .for_each(|pixel| pixel.swap(0, 2));
+        }
+        Ok(image::RgbaImage::from_raw(self.width, self.height, pixels)
+            .expect("Frame capture has the wrong size"))
+    }
+}
+
+/// Returns true if frames of this format can be captured.
+fn is_capturable(format: wgpu::TextureFormat) -> bool {
+    matches!(
+        format,
+        wgpu::TextureFormat::Rgba8Unorm
+            | wgpu::TextureFormat::Rgba8UnormSrgb
+            | wgpu::TextureFormat::Bgra8Unorm
+            | wgpu::TextureFormat::Bgra8UnormSrgb
+    )
+}
+
+/// Copies a frame drawn to an intermediate texture onto a surface, which can't be copied to or from on every backend.
+struct Blit {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+}
+impl Blit {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Blit Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/blit.wgsl").into()),
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Blit Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Blit Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blit Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        Self { pipeline, layout }
+    }
+
+    /// Encodes a copy of the source onto the target, which must be the same size.
+    fn encode(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        target: &wgpu::TextureView,
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Blit Bind Group"),
+            layout: &self.layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(source),
+            }],
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Blit Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_offscreen_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> wgpu::Texture {
+    create_frame_texture(device, config, "Offscreen Texture", config.usage)
+}
+
+/// Creates a texture to draw frames to, with the target's size and format.
+fn create_frame_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    label: &str,
+    usage: wgpu::TextureUsages,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage,
+        view_formats: &[],
+    })
+}
+
+/// Draws to a window's surface, or to a texture if created with [RenderTarget::offscreen].
 pub struct RenderTarget {
     pub adapter: wgpu::Adapter,
     pub config: wgpu::SurfaceConfiguration,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub instance: wgpu::Instance,
-    pub surface: wgpu::Surface,
+    /// None for offscreen targets.
+    pub surface: Option<wgpu::Surface>,
+    /// The texture drawn to by offscreen targets.
+    pub offscreen_texture: Option<wgpu::Texture>,
     pub texture_format: wgpu::TextureFormat,
     pub texture_layout: wgpu::BindGroupLayout,
 
@@ -111,6 +426,12 @@ pub struct RenderTarget {
     pub encoder: Option<wgpu::CommandEncoder>,
     pub color_view: Option<wgpu::TextureView>,
     pub depth_view: Option<wgpu::TextureView>,
+
+    capture_requested: bool,
+    capture: Option<FrameCapture>,
+    /// Drawn to instead of the surface when a frame is captured, then copied onto it.
+    capture_texture: Option<wgpu::Texture>,
+    blit: Option<Blit>,
 }
 impl RenderTarget {
     pub async fn new(
@@ -130,36 +451,7 @@ impl RenderTarget {
             .await
             .expect("Failed to create adapter!");
 
-        let mut trace_path = None;
-        let dir = format!(
-            "./dbg/trace/{}__{}",
-            chrono::Local::now().format("%F-%s"),
-            label.unwrap_or_else(|| "UNLABLED")
-        );
-        let path = std::path::Path::new(&*dir);
-        if enable_tracing {
-            use log::debug;
-            std::fs::create_dir_all(&path).expect("Unable to create tracing path!");
-            trace_path = Some(path);
-            debug!("Render target tracing enabled.");
-        }
-
-        #[cfg(target_arch = "wasm32")]
-        let limits = wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits());
-        #[cfg(not(target_arch = "wasm32"))]
-        let limits = wgpu::Limits::default();
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::default(),
-                    limits,
-                    label,
-                },
-                trace_path,
-            )
-            .await
-            .unwrap();
+        let (device, queue) = request_device(&adapter, enable_tracing, label).await;
 
         let texture_layout = get_texture_bind_group_layout(&device);
 
@@ -172,6 +464,7 @@ impl RenderTarget {
             .next()
             .unwrap_or(surface_caps.formats[0]);
 
+        // Surfaces can only be rendered to on some backends, so captured frames are drawn to a separate texture.
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: texture_format,
@@ -190,7 +483,8 @@ impl RenderTarget {
             device,
             queue,
             instance,
-            surface,
+            surface: Some(surface),
+            offscreen_texture: None,
             texture_format,
             texture_layout,
 
@@ -198,28 +492,195 @@ impl RenderTarget {
             encoder: None,
             color_view: None,
             depth_view: None,
+
+            capture_requested: false,
+            capture: None,
+            capture_texture: None,
+            blit: None,
         }
     }
 
-    pub fn begin_frame(&mut self) {
-        if self.surface_texture.is_none() {
-            self.surface_texture = Some(
-                self.surface
-                    .get_current_texture()
-                    .expect("Unable to get surface texture!"),
-            );
+    /// Creates a render target which draws into a texture of the given size instead of a window,
+    /// e.g. for thumbnails, screenshots and tests. Renderers draw to it as they would to a window.
+    /// Falls back to a software adapter if there is no hardware adapter, or always uses one if `force_fallback_adapter` is set,
+    /// e.g. so tests render the same on every machine. See [RenderTarget::read_frame].
+    pub async fn offscreen(
+        width: u32,
+        height: u32,
+        force_fallback_adapter: bool,
+        enable_tracing: bool,
+        label: Option<&str>,
+    ) -> Self {
+        let instance = wgpu::Instance::new(InstanceDescriptor::default());
+        let mut options = wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter,
+        };
+        let adapter = match instance.request_adapter(&options).await {
+            Some(adapter) => adapter,
+            None if force_fallback_adapter => panic!("Failed to create fallback adapter!"),
+            None => {
+                options.force_fallback_adapter = true;
+                instance
+                    .request_adapter(&options)
+                    .await
+                    .expect("Failed to create adapter!")
+            }
+        };
+
+        let (device, queue) = request_device(&adapter, enable_tracing, label).await;
+
+        let texture_layout = get_texture_bind_group_layout(&device);
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: OFFSCREEN_FORMAT,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+        let offscreen_texture = create_offscreen_texture(&device, &config);
+
+        Self {
+            adapter,
+            config,
+            device,
+            queue,
+            instance,
+            surface: None,
+            offscreen_texture: Some(offscreen_texture),
+            texture_format: OFFSCREEN_FORMAT,
+            texture_layout,
+
+            surface_texture: None,
+            encoder: None,
+            color_view: None,
+            depth_view: None,
+
+            capture_requested: false,
+            capture: None,
+            capture_texture: None,
+            blit: None,
+        }
+    }
+
+    /// Resizes the target, in physical pixels, e.g. when its window is resized or moved to a display with a different scale factor.
+    /// Reconfigures the surface, or recreates the offscreen texture. The depth texture follows on the next frame.
+    /// Zero sizes, as when a window is minimized, are ignored.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 || (width, height) == self.size() {
+            return;
+        }
+        self.config.width = width;
+        self.config.height = height;
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+        if self.offscreen_texture.is_some() {
+            self.offscreen_texture = Some(create_offscreen_texture(&self.device, &self.config));
+        }
+        // A capture of the old size can't be read back as a frame of the new one.
+        self.capture = None;
+    }
+
+    /// Copies the next frame drawn to a window so it can be read with [RenderTarget::read_frame].
+    /// Not needed for offscreen targets, whose last frame can always be read. Not supported on the web.
+    pub fn capture_next_frame(&mut self) -> Result<(), CaptureError> {
+        if cfg!(target_arch = "wasm32") {
+            return Err(CaptureError::Unsupported);
+        }
+        if !is_capturable(self.config.format) {
+            return Err(CaptureError::Format(self.config.format));
+        }
+        self.capture_requested = true;
+        Ok(())
+    }
+
+    /// Reads the last frame back to CPU memory, after [RenderTarget::end_frame]. Blocks until the GPU is done with it.
+    /// Window targets must call [RenderTarget::capture_next_frame] before drawing the frame.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_frame(&mut self) -> Result<image::RgbaImage, CaptureError> {
+        let capture = match (self.capture.take(), self.offscreen_texture.as_ref()) {
+            (Some(capture), _) => capture,
+            (None, Some(texture)) => {
+                let mut encoder =
+                    self.device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("Frame Capture Encoder"),
+                        });
+                let capture = FrameCapture::new(&self.device, &mut encoder, texture, &self.config);
+                self.queue.submit(std::iter::once(encoder.finish()));
+                capture
+            }
+            (None, None) => return Err(CaptureError::NoFrame),
+        };
+        capture.read(&self.device)
+    }
+
+    /// Reads the last frame as [RenderTarget::read_frame] does, and saves it as a PNG.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_frame<P>(&mut self, path: P) -> Result<(), CaptureError>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        self.read_frame()?
+            .save_with_format(path, image::ImageFormat::Png)?;
+        Ok(())
+    }
+}
+impl DrawTarget for RenderTarget {
+    fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+    fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+    fn texture_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.texture_layout
+    }
+    fn color_format(&self) -> wgpu::TextureFormat {
+        self.config.format
+    }
+    fn size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
+
+    fn begin_frame(&mut self) {
+        if self.encoder.is_none() {
+            let texture = match &self.surface {
+                Some(surface) => {
+                    let surface_texture = self.surface_texture.insert(
+                        surface
+                            .get_current_texture()
+                            .expect("Unable to get surface texture!"),
+                    );
+                    match std::mem::take(&mut self.capture_requested) {
+                        true => self.capture_texture.insert(create_frame_texture(
+                            &self.device,
+                            &self.config,
+                            "Capture Texture",
+                            wgpu::TextureUsages::RENDER_ATTACHMENT
+                                | wgpu::TextureUsages::COPY_SRC
+                                | wgpu::TextureUsages::TEXTURE_BINDING,
+                        )),
+                        false => &surface_texture.texture,
+                    }
+                }
+                None => self.offscreen_texture.as_ref().unwrap(),
+            };
+            self.color_view = Some(texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Render Target Texture View"),
+                format: Some(self.texture_format),
+                ..Default::default()
+            }));
             self.encoder = Some(self.device.create_command_encoder(
                 &wgpu::CommandEncoderDescriptor {
                     label: Some("Render Target Encoder"),
                 },
             ));
-            self.color_view = Some(self.surface_texture.as_ref().unwrap().texture.create_view(
-                &wgpu::TextureViewDescriptor {
-                    label: Some("Render Target Texture View"),
-                    format: Some(self.texture_format),
-                    ..Default::default()
-                },
-            ));
             self.depth_view = Some(
                 texture::TextureWrapper::create_depth_texture(
                     &self.device,
@@ -231,49 +692,107 @@ impl RenderTarget {
         }
     }
 
-    pub fn get_render_pass(&mut self, clear: bool, use_depth_stencil: bool) -> wgpu::RenderPass {
-        self.encoder
-            .as_mut()
-            .unwrap()
-            .begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: self.color_view.as_ref().unwrap(),
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: if clear {
-                            wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 1.0,
-                                g: 0.0,
-                                b: 1.0,
-                                a: 0.0,
-                            })
-                        } else {
-                            wgpu::LoadOp::Load
-                        },
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: if use_depth_stencil {
-                    Some(wgpu::RenderPassDepthStencilAttachment {
-                        view: &self.depth_view.as_ref().unwrap(),
-                        depth_ops: Some(wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(1.0),
-                            store: true,
-                        }),
-                        stencil_ops: None,
-                    })
-                } else {
-                    None
+    fn end_frame(&mut self) {
+        if let Some(mut encoder) = self.encoder.take() {
+            if let (Some(surface_texture), Some(texture)) =
+                (self.surface_texture.as_ref(), self.capture_texture.take())
+            {
+                self.capture = Some(FrameCapture::new(
+                    &self.device,
+                    &mut encoder,
+                    &texture,
+                    &self.config,
+                ));
+                let surface_view =
+                    surface_texture
+                        .texture
+                        .create_view(&wgpu::TextureViewDescriptor {
+                            label: Some("Surface Texture View"),
+                            format: Some(self.texture_format),
+                            ..Default::default()
+                        });
+                let blit = self
+                    .blit
+                    .get_or_insert_with(|| Blit::new(&self.device, self.texture_format));
+                blit.encode(
+                    &self.device,
+                    &mut encoder,
+                    self.color_view.as_ref().unwrap(),
+                    &surface_view,
+                );
+            }
+            self.queue.submit(std::iter::once(encoder.finish()));
+            if let Some(surface_texture) = self.surface_texture.take() {
+                surface_texture.present();
+            }
+        }
+    }
+
+    fn frame(&mut self) -> Option<Frame<'_>> {
+        Some(Frame {
+            device: &self.device,
+            encoder: self.encoder.as_mut()?,
+            color_view: self.color_view.as_ref()?,
+            depth_view: self.depth_view.as_ref()?,
+            width: self.config.width,
+            height: self.config.height,
+        })
+    }
+}
+
+impl DrawTarget for HeadlessRenderTarget {
+    fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+    fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+    fn texture_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.texture_layout
+    }
+    fn color_format(&self) -> wgpu::TextureFormat {
+        self.config.format
+    }
+    fn size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
+
+    fn begin_frame(&mut self) {
+        if self.encoder.is_none() {
+            let texture = self
+                .frame_texture
+                .get_or_insert_with(|| create_offscreen_texture(&self.device, &self.config));
+            self.color_view = Some(texture.create_view(&wgpu::TextureViewDescriptor::default()));
+            self.depth_view = Some(
+                texture::TextureWrapper::create_depth_texture(
+                    &self.device,
+                    &self.config,
+                    "Depth Texture",
+                )
+                .view,
+            );
+            self.encoder = Some(self.device.create_command_encoder(
+                &wgpu::CommandEncoderDescriptor {
+                    label: Some("Headless Render Target Encoder"),
                 },
-            })
+            ));
+        }
     }
 
-    pub fn end_frame(&mut self) {
-        if self.surface_texture.is_some() {
-            self.queue
-                .submit(std::iter::once(self.encoder.take().unwrap().finish()));
-            self.surface_texture.take().unwrap().present();
+    fn end_frame(&mut self) {
+        if let Some(encoder) = self.encoder.take() {
+            self.queue.submit(std::iter::once(encoder.finish()));
         }
     }
+
+    fn frame(&mut self) -> Option<Frame<'_>> {
+        Some(Frame {
+            device: &self.device,
+            encoder: self.encoder.as_mut()?,
+            color_view: self.color_view.as_ref()?,
+            depth_view: self.depth_view.as_ref()?,
+            width: self.config.width,
+            height: self.config.height,
+        })
+    }
 }
@@ -0,0 +1,18 @@
+/////////////////////////////////////////////////
+// Blit shader
+// Copies a frame drawn to an intermediate texture onto a surface of the same size, which may not support copies.
+
+@group(0) @binding(0)
+var t_frame: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // A triangle which covers the whole target.
+    let position = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(position * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureLoad(t_frame, vec2<i32>(position.xy), 0);
+}
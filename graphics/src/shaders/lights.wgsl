@@ -0,0 +1,48 @@
+/////////////////////////////////////////////////
+// Light bindings
+// The light bind group read by model shaders, for adapters with storage buffers. See light_shader_bindings in light.rs.
+// lights_downlevel.wgsl declares the same bindings as uniform arrays.
+
+const LIGHT_DIRECTIONAL = 0u;
+const LIGHT_POINT = 1u;
+const LIGHT_SPOT = 2u;
+struct Light {
+    position: vec3<f32>,
+    kind: u32,
+    direction: vec3<f32>,
+    // Zero if unlimited.
+    range: f32,
+    color: vec4<f32>,
+    // Constant, linear and quadratic.
+    attenuation: vec3<f32>,
+    cos_inner: f32,
+    cos_outer: f32,
+    shadow_bias: f32,
+    // Index of the light's first shadow view, or -1 if it casts no shadows.
+    shadow_view: i32,
+    shadow_view_count: u32,
+};
+struct LightBuffer {
+    ambient_light: vec4<f32>,
+    count: u32,
+    lights: array<Light>,
+};
+@group(2) @binding(0)
+var<storage, read> light_buffer: LightBuffer;
+
+struct ShadowView {
+    view_proj: mat4x4<f32>,
+    // Offset and size of the view's tile in the shadow atlas.
+    atlas_rect: vec4<f32>,
+    // View depth at which a directional light's cascade ends.
+    split_depth: f32,
+};
+struct ShadowViews {
+    views: array<ShadowView>,
+};
+@group(2) @binding(1)
+var t_shadow: texture_depth_2d;
+@group(2) @binding(2)
+var s_shadow: sampler_comparison;
+@group(2) @binding(3)
+var<storage, read> shadow_views: ShadowViews;
@@ -0,0 +1,456 @@
+use crate::camera::{Camera, Projection, OPENGL_TO_WGPU_MATRIX};
+use cgmath::*;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU64;
+
+/// Format of the shadow atlas.
+pub const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+/// Most cascades a directional light may split the view into.
+pub const MAX_CASCADES: u32 = 4;
+/// Most shadow maps drawn on adapters without storage buffers, such as WebGL: enough for four directional lights.
+/// Matches `ShadowViews` in lights_downlevel.wgsl. See [crate::storage_lights_supported].
+pub const DOWNLEVEL_MAX_SHADOW_VIEWS: usize = MAX_CASCADES as usize * 4;
+/// Width and height of the shadow atlas, in texels, unless set with [crate::LightWrapper::set_shadow_atlas_size].
+pub const DEFAULT_SHADOW_ATLAS_SIZE: u32 = 2048;
+/// Blend between logarithmic (1.0) and uniform (0.0) cascade splits.
+const CASCADE_SPLIT_LAMBDA: f32 = 0.5;
+/// Near plane of spot light shadow maps.
+const SPOT_SHADOW_NEAR: f32 = 0.05;
+
+/// Shadow settings of a [crate::Light]. Point lights don't cast shadows.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShadowSettings {
+    /// Width and height of each shadow map, in texels. Directional lights have one map per cascade.
+    pub resolution: u32,
+    /// Subtracted from a surface's depth before comparing it against the shadow map, so surfaces don't shadow themselves.
+    pub bias: f32,
+    /// How many cascades a directional light splits the view into, up to [MAX_CASCADES].
+    /// Nearer cascades cover less of the view, so nearby shadows are sharper.
+    pub cascades: u32,
+    /// How far from the camera directional lights cast shadows.
+    /// Spot lights with unlimited range also cast shadows this far.
+    pub max_distance: f32,
+}
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 1024,
+            bias: 0.002,
+            cascades: 3,
+            max_distance: 50.0,
+        }
+    }
+}
+
+/// A shadow map's square region of the atlas, in texels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShadowTile {
+    pub x: u32,
+    pub y: u32,
+    pub size: u32,
+}
+
+/// Packs square tiles into rows of an atlas `atlas_size` texels wide and tall, largest first.
+/// Returns the tile for each size, in the same order, or None for tiles that don't fit.
+pub fn pack_shadow_tiles(sizes: &[u32], atlas_size: u32) -> Vec<Option<ShadowTile>> {
+    let mut order = (0..sizes.len()).collect::<Vec<_>>();
+    order.sort_by_key(|i| std::cmp::Reverse(sizes[*i]));
+
+    let mut tiles = vec![None; sizes.len()];
+    let (mut x, mut y, mut row_height) = (0, 0, 0);
+    for i in order {
+        let size = sizes[i];
+        if x + size > atlas_size {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+        if size == 0 || x + size > atlas_size || y + size > atlas_size {
+            continue;
+        }
+        tiles[i] = Some(ShadowTile { x, y, size });
+        x += size;
+        row_height = row_height.max(size);
+    }
+    tiles
+}
+
+/// Returns the view depth at which each of `count` cascades ends, blending logarithmic and uniform splits between `near` and `far`.
+pub fn cascade_splits(near: f32, far: f32, count: u32) -> Vec<f32> {
+    (1..=count)
+        .map(|i| {
+            let t = i as f32 / count as f32;
+            let log = near * (far / near).powf(t);
+            let uniform = near + (far - near) * t;
+            CASCADE_SPLIT_LAMBDA * log + (1.0 - CASCADE_SPLIT_LAMBDA) * uniform
+        })
+        .collect()
+}
+
+/// Returns the view-projection matrix of each cascade of a directional light, and the view depth at which the cascade ends.
+/// Each cascade is fit around a slice of the camera's view, and snapped to texels so shadows don't shimmer as the camera moves.
+pub(crate) fn directional_shadow_views(
+    direction: [f32; 3],
+    settings: &ShadowSettings,
+    camera: &Camera,
+    projection: &Projection,
+) -> Vec<(Matrix4<f32>, f32)> {
+    let direction = Vector3::from(direction).normalize();
+    let up = light_up(direction);
+    let rotation = Matrix4::look_to_rh(Point3::origin(), direction, up);
+    let inverse_rotation = rotation.invert().unwrap();
+    let view = camera.calc_matrix();
+
+    let near = projection.znear();
+    let far = settings.max_distance.max(near * 2.0);
+    let mut slice_near = near;
+    cascade_splits(near, far, settings.cascades.clamp(1, MAX_CASCADES))
+        .into_iter()
+        .map(|split| {
+            let slice = projection.calc_matrix_with_range(slice_near, split) * view;
+            let corners = frustum_corners(slice.invert().unwrap());
+            slice_near = split;
+
+            let center = corners
+                .iter()
+                .fold(Vector3::zero(), |sum, corner| sum + corner.to_vec())
+                / 8.0;
+            let radius = corners
+                .iter()
+                .map(|corner| corner.to_vec().distance(center))
+                .fold(0.0, f32::max);
+            // Rounding keeps the cascade the same size as the camera turns.
+            let radius = (radius * 16.0).ceil() / 16.0;
+
+            let texel = 2.0 * radius / settings.resolution.max(1) as f32;
+            let center = rotation.transform_point(Point3::from_vec(center));
+            let center = inverse_rotation.transform_point(Point3::new(
+                (center.x / texel).floor() * texel,
+                (center.y / texel).floor() * texel,
+                center.z,
+            ));
+
+            // Anything up to max_distance behind the cascade still casts shadows into it.
+            let eye = center - direction * (radius + settings.max_distance);
+            let projection = OPENGL_TO_WGPU_MATRIX
+                * ortho(
+                    -radius,
+                    radius,
+                    -radius,
+                    radius,
+                    0.0,
+                    2.0 * radius + settings.max_distance,
+                );
+            (projection * Matrix4::look_to_rh(eye, direction, up), split)
+        })
+        .collect()
+}
+
+/// Returns the view-projection matrix of a spot light's shadow map, covering its cone out to `far`.
+pub(crate) fn spot_shadow_view(
+    position: [f32; 3],
+    direction: [f32; 3],
+    outer_angle: Rad<f32>,
+    far: f32,
+) -> Matrix4<f32> {
+    let direction = Vector3::from(direction).normalize();
+    // Wide cones are narrowed so the projection stays finite.
+    let fovy = Rad((outer_angle.0 * 2.0).clamp(0.01, 3.0));
+    OPENGL_TO_WGPU_MATRIX
+        * perspective(fovy, 1.0, SPOT_SHADOW_NEAR, far.max(SPOT_SHADOW_NEAR * 2.0))
+        * Matrix4::look_to_rh(Point3::from(position), direction, light_up(direction))
+}
+
+/// An up vector that isn't parallel to the light.
+fn light_up(direction: Vector3<f32>) -> Vector3<f32> {
+    match direction.y.abs() > 0.99 {
+        true => Vector3::unit_z(),
+        false => Vector3::unit_y(),
+    }
+}
+
+/// Returns the world space corners of a view frustum, given the inverse of its view-projection matrix.
+fn frustum_corners(inverse_view_proj: Matrix4<f32>) -> [Point3<f32>; 8] {
+    let mut corners = [Point3::origin(); 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let ndc = Vector4::new(
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { 0.0 } else { 1.0 },
+            1.0,
+        );
+        *corner = Point3::from_homogeneous(inverse_view_proj * ndc);
+    }
+    corners
+}
+
+/// A shadow map to draw this frame.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ShadowView {
+    pub tile: ShadowTile,
+    pub view_proj: Matrix4<f32>,
+    /// View depth at which a directional light's cascade ends.
+    pub split_depth: f32,
+}
+
+/// GPU layout of a [ShadowView]. Matches `ShadowView` in lights.wgsl.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowViewUniform {
+    view_proj: [[f32; 4]; 4],
+    /// Offset and size of the view's tile, in texture coordinates.
+    atlas_rect: [f32; 4],
+    split_depth: f32,
+    _padding: [f32; 3],
+}
+
+/// A depth texture holding every shadow map, and the buffers describing the view of each.
+#[derive(Debug)]
+pub struct ShadowAtlas {
+    size: u32,
+    _texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    /// Comparison sampler, filtering between neighbouring texels.
+    pub sampler: wgpu::Sampler,
+
+    views: Vec<ShadowView>,
+    /// False if the views are kept in a uniform array of [DOWNLEVEL_MAX_SHADOW_VIEWS].
+    storage: bool,
+    capacity: usize,
+    /// Every view, read by the model shader.
+    view_buffer: wgpu::Buffer,
+    /// Each view's matrix, spaced out for dynamic offsets. Read by the shadow pipeline.
+    pass_buffer: wgpu::Buffer,
+    pass_stride: u64,
+    pass_bind_group: wgpu::BindGroup,
+    /// Layout of the shadow pipeline's only bind group, holding the matrix of the view being drawn.
+    pub pass_layout: wgpu::BindGroupLayout,
+}
+
+impl ShadowAtlas {
+    /// Creates an atlas `size` texels wide and tall.
+    pub fn new(device: &wgpu::Device, size: u32) -> Self {
+        let size = size.clamp(1, device.limits().max_texture_dimension_2d);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Atlas"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let pass_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: NonZeroU64::new(std::mem::size_of::<[[f32; 4]; 4]>() as u64),
+                },
+                count: None,
+            }],
+            label: Some("Shadow Pass Layout"),
+        });
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let pass_stride =
+            (std::mem::size_of::<[[f32; 4]; 4]>() as u64).div_ceil(alignment) * alignment;
+
+        let storage = crate::storage_lights_supported(device);
+        let capacity = match storage {
+            true => MAX_CASCADES as usize,
+            false => DOWNLEVEL_MAX_SHADOW_VIEWS,
+        };
+        let (view_buffer, pass_buffer, pass_bind_group) =
+            Self::create_buffers(device, &pass_layout, pass_stride, storage, capacity);
+
+        Self {
+            size,
+            _texture: texture,
+            view,
+            sampler,
+            views: Vec::new(),
+            storage,
+            capacity,
+            view_buffer,
+            pass_buffer,
+            pass_stride,
+            pass_bind_group,
+            pass_layout,
+        }
+    }
+
+    fn create_buffers(
+        device: &wgpu::Device,
+        pass_layout: &wgpu::BindGroupLayout,
+        pass_stride: u64,
+        storage: bool,
+        capacity: usize,
+    ) -> (wgpu::Buffer, wgpu::Buffer, wgpu::BindGroup) {
+        let view_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow View Buffer"),
+            size: (capacity * std::mem::size_of::<ShadowViewUniform>()) as wgpu::BufferAddress,
+            usage: match storage {
+                true => wgpu::BufferUsages::STORAGE,
+                false => wgpu::BufferUsages::UNIFORM,
+            } | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let pass_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Pass Buffer"),
+            size: capacity as u64 * pass_stride,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let pass_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: pass_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &pass_buffer,
+                    offset: 0,
+                    size: NonZeroU64::new(std::mem::size_of::<[[f32; 4]; 4]>() as u64),
+                }),
+            }],
+            label: Some("Shadow Pass Bind Group"),
+        });
+        (view_buffer, pass_buffer, pass_bind_group)
+    }
+
+    /// Width and height of the atlas, in texels.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Buffer holding every view, as read by the model shader.
+    pub fn view_buffer(&self) -> &wgpu::Buffer {
+        &self.view_buffer
+    }
+
+    /// Most views which can be drawn, or None if the view buffer grows as needed. See [DOWNLEVEL_MAX_SHADOW_VIEWS].
+    pub fn max_views(&self) -> Option<usize> {
+        match self.storage {
+            true => None,
+            false => Some(self.capacity),
+        }
+    }
+
+    /// Uploads the views to draw this frame.
+    /// Returns true if the buffers had to grow, which invalidates any bind group using [ShadowAtlas::view_buffer].
+    pub(crate) fn write_views(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        views: Vec<ShadowView>,
+    ) -> bool {
+        let grown = views.len() > self.capacity;
+        if grown {
+            self.capacity = views.len().next_power_of_two();
+            (self.view_buffer, self.pass_buffer, self.pass_bind_group) = Self::create_buffers(
+                device,
+                &self.pass_layout,
+                self.pass_stride,
+                self.storage,
+                self.capacity,
+            );
+        }
+
+        if !views.is_empty() {
+            let size = self.size as f32;
+            let uniforms = views
+                .iter()
+                .map(|view| ShadowViewUniform {
+                    view_proj: view.view_proj.into(),
+                    atlas_rect: [
+                        view.tile.x as f32 / size,
+                        view.tile.y as f32 / size,
+                        view.tile.size as f32 / size,
+                        view.tile.size as f32 / size,
+                    ],
+                    split_depth: view.split_depth,
+                    _padding: [0.0; 3],
+                })
+                .collect::<Vec<_>>();
+            queue.write_buffer(&self.view_buffer, 0, bytemuck::cast_slice(&uniforms));
+
+            let mut matrices = vec![0u8; views.len() * self.pass_stride as usize];
+            for (view, chunk) in views
+                .iter()
+                .zip(matrices.chunks_mut(self.pass_stride as usize))
+            {
+                let view_proj: [[f32; 4]; 4] = view.view_proj.into();
+                let bytes = bytemuck::bytes_of(&view_proj);
+                chunk[..bytes.len()].copy_from_slice(bytes);
+            }
+            queue.write_buffer(&self.pass_buffer, 0, &matrices);
+        }
+
+        self.views = views;
+        grown
+    }
+
+    /// Clears the atlas and draws each view into its tile. Does nothing if no lights cast shadows.
+    /// `draw` is called once per view, with the view's viewport and bind group set.
+    pub fn render<'a, F>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        pipeline: &'a wgpu::RenderPipeline,
+        mut draw: F,
+    ) where
+        F: FnMut(&mut wgpu::RenderPass<'a>),
+    {
+        if self.views.is_empty() {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_pipeline(pipeline);
+        for (i, view) in self.views.iter().enumerate() {
+            let tile = view.tile;
+            render_pass.set_viewport(
+                tile.x as f32,
+                tile.y as f32,
+                tile.size as f32,
+                tile.size as f32,
+                0.0,
+                1.0,
+            );
+            render_pass.set_bind_group(
+                0,
+                &self.pass_bind_group,
+                &[(i as u64 * self.pass_stride) as u32],
+            );
+            draw(&mut render_pass);
+        }
+    }
+}
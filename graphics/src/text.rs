@@ -1,12 +1,16 @@
 use crate::*;
+use log::warn;
 use serde::*;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 use wgpu::util::StagingBelt;
 use wgpu_glyph::*;
 
 /// Thin wrapper around a Vec<u8> of font data.
 #[derive(Serialize, Deserialize)]
 pub struct Font {
+    #[serde(with = "crate::blob")]
     pub data: Vec<u8>,
 }
 #[derive(Debug)]
@@ -52,45 +56,92 @@ impl TextBlockInstance {
     }
 }
 
+/// Name of the font registered first, and used in place of unknown fonts.
+pub const DEFAULT_FONT_NAME: &str = "regular";
+
 pub struct GlyphRenderer {
     staging_belt: StagingBelt,
     brush: GlyphBrush<()>,
     fonts: HashMap<String, FontId>,
     submitted_already: bool,
+    fallback_mode: FallbackMode,
+    /// Font registered in place of fonts which cannot be parsed, and its id once it has been registered.
+    fallback_font: Option<ab_glyph::FontArc>,
+    fallback_id: Option<FontId>,
+    /// Unknown font names which have already been warned about.
+    warned: RefCell<HashSet<String>>,
 }
 
 impl GlyphRenderer {
-    pub fn new(render_target: &RenderTarget, raw_fonts: Option<HashMap<String, Font>>) -> Self {
+    /// Creates a new GlyphRenderer. The font named [DEFAULT_FONT_NAME] is registered first and becomes FontId(0), the default font;
+    /// the rest are registered in order of name.
+    /// In [FallbackMode::Placeholder], fonts which cannot be parsed are replaced with `fallback_font` with a warning,
+    /// or skipped if there is none. In [FallbackMode::Strict] they panic.
+    pub fn new<T: DrawTarget>(
+        render_target: &T,
+        raw_fonts: Option<HashMap<String, Rc<Font>>>,
+        fallback_font: Option<Rc<Font>>,
+        fallback_mode: FallbackMode,
+    ) -> Self {
         let staging_belt = StagingBelt::new(1024);
+        let fallback_font =
+            fallback_font.and_then(|font| ab_glyph::FontArc::try_from_vec(font.data.clone()).ok());
+        let mut fallback_id = None;
 
         let mut fonts = HashMap::<String, FontId>::new();
         let mut font_data = Vec::<ab_glyph::FontArc>::new();
         match raw_fonts {
             Some(raw_fonts) => {
+                let mut raw_fonts = raw_fonts.into_iter().collect::<Vec<_>>();
+                raw_fonts.sort_by_key(|(name, _)| (name != DEFAULT_FONT_NAME, name.clone()));
                 for (name, font) in raw_fonts {
-                    fonts.insert(name, FontId(font_data.len()));
-                    font_data.push(
-                        ab_glyph::FontArc::try_from_vec(font.data.clone())
-                            .expect("Unable to register font!"),
-                    );
+                    match ab_glyph::FontArc::try_from_vec(font.data.clone()) {
+                        Ok(data) => {
+                            fonts.insert(name, FontId(font_data.len()));
+                            font_data.push(data);
+                        }
+                        Err(_) if fallback_mode.is_placeholder() => match &fallback_font {
+                            Some(fallback) => {
+                                warn!(
+                                    "Font `{}` could not be parsed; using the fallback font.",
+                                    name
+                                );
+                                let id = *fallback_id.get_or_insert_with(|| {
+                                    font_data.push(fallback.clone());
+                                    FontId(font_data.len() - 1)
+                                });
+                                fonts.insert(name, id);
+                            }
+                            None => warn!("Font `{}` could not be parsed; skipping it.", name),
+                        },
+                        Err(e) => panic!("Unable to register font `{}`! {}", name, e),
+                    }
                 }
             }
             None => {
-                use log::warn;
                 warn!("No fonts found!");
             }
         }
         let brush = GlyphBrushBuilder::using_fonts(font_data)
-            .build(&render_target.device, render_target.config.format);
+            .build(render_target.device(), render_target.color_format());
 
         Self {
             staging_belt,
             brush,
             fonts,
             submitted_already: false,
+            fallback_mode,
+            fallback_font,
+            fallback_id,
+            warned: RefCell::new(HashSet::new()),
         }
     }
 
+    /// Sets what happens when an unknown font is requested or a font cannot be parsed.
+    pub fn set_fallback_mode(&mut self, mode: FallbackMode) {
+        self.fallback_mode = mode;
+    }
+
     pub fn start_pass(&mut self) {
         if self.submitted_already {
             self.staging_belt.recall();
@@ -98,15 +149,18 @@ impl GlyphRenderer {
         }
     }
 
-    pub fn end_pass(&mut self, render_target: &mut RenderTarget) {
+    pub fn end_pass<T: DrawTarget>(&mut self, render_target: &mut T) {
+        let frame = render_target
+            .frame()
+            .expect("No frame in progress! Call begin_frame first.");
         self.brush
             .draw_queued(
-                &render_target.device,
+                frame.device,
                 &mut self.staging_belt,
-                render_target.encoder.as_mut().unwrap(),
-                render_target.color_view.as_ref().unwrap(),
-                render_target.config.width,
-                render_target.config.height,
+                frame.encoder,
+                frame.color_view,
+                frame.width,
+                frame.height,
             )
             .expect("Could not submit GlyphBrush queue!");
         self.staging_belt.finish();
@@ -117,7 +171,62 @@ impl GlyphRenderer {
         self.brush.queue(section);
     }
 
-    pub fn font<'a>(&self, name: &String) -> FontId {
-        *self.fonts.get(name).unwrap()
+    /// Returns the id of a font. Unknown fonts are replaced with the default font and warned about once in [FallbackMode::Placeholder],
+    /// and panic in [FallbackMode::Strict].
+    pub fn font(&self, name: &str) -> FontId {
+        match self.fonts.get(name) {
+            Some(id) => *id,
+            None if self.fallback_mode.is_placeholder() => {
+                if self.warned.borrow_mut().insert(name.to_owned()) {
+                    warn!("Font `{}` not found; using the default font.", name);
+                }
+                FontId(0)
+            }
+            None => panic!("Font `{}` not found!", name),
+        }
+    }
+
+    /// Returns the id of a font, or None if no font is registered under that name.
+    pub fn try_font(&self, name: &str) -> Option<FontId> {
+        self.fonts.get(name).copied()
+    }
+
+    /// Registers a font under the given name, replacing any font previously registered under it.
+    /// The glyph brush cannot unload fonts, so replaced font data stays resident.
+    /// Fonts which cannot be parsed are handled as in [GlyphRenderer::new]. Without a fallback font, the default font is returned.
+    pub fn add_font(&mut self, name: &str, font: &Font) -> FontId {
+        let data = match ab_glyph::FontArc::try_from_vec(font.data.clone()) {
+            Ok(data) => data,
+            Err(_) if self.fallback_mode.is_placeholder() => match self.fallback_font.clone() {
+                Some(fallback) => {
+                    warn!(
+                        "Font `{}` could not be parsed; using the fallback font.",
+                        name
+                    );
+                    let id = *self
+                        .fallback_id
+                        .get_or_insert_with(|| self.brush.add_font(fallback));
+                    self.fonts.insert(name.to_owned(), id);
+                    return id;
+                }
+                None => {
+                    warn!(
+                        "Font `{}` could not be parsed; using the default font.",
+                        name
+                    );
+                    return FontId(0);
+                }
+            },
+            Err(e) => panic!("Unable to register font `{}`! {}", name, e),
+        };
+        let id = self.brush.add_font(data);
+        self.fonts.insert(name.to_owned(), id);
+        self.warned.borrow_mut().remove(name);
+        id
+    }
+
+    /// Unregisters a font. Text using it will fall back to the default font.
+    pub fn remove_font(&mut self, name: &str) {
+        self.fonts.remove(name);
     }
 }
@@ -97,6 +97,51 @@ impl TextureWrapper {
         Self::from_image(device, queue, &img, Some(label), is_normal_map)
     }
 
+    /// Creates the placeholder texture: a 16x16 magenta and black checkerboard.
+    pub fn placeholder(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let img = image::RgbaImage::from_fn(16, 16, |x, y| match (x / 4 + y / 4) % 2 {
+            0 => image::Rgba([255, 0, 255, 255]),
+            _ => image::Rgba([0, 0, 0, 255]),
+        });
+        Self::from_image(
+            device,
+            queue,
+            &image::DynamicImage::ImageRgba8(img),
+            Some("Placeholder Texture"),
+            false,
+        )
+        .expect("Unable to create placeholder texture!")
+    }
+
+    /// Creates a 1x1 normal map pointing straight out of the surface. Used in place of missing normal maps.
+    pub fn flat_normal(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::from_color(device, queue, [128, 128, 255, 255], "Flat Normal Map", true)
+    }
+
+    /// Creates a 1x1 white texture. Used in place of missing diffuse textures, so that the material's color shows through.
+    pub fn white(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::from_color(device, queue, [255; 4], "White Texture", false)
+    }
+
+    /// Creates a 1x1 texture of a single RGBA color.
+    pub fn from_color(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color: [u8; 4],
+        label: &str,
+        is_normal_map: bool,
+    ) -> Self {
+        let img = image::RgbaImage::from_pixel(1, 1, image::Rgba(color));
+        Self::from_image(
+            device,
+            queue,
+            &image::DynamicImage::ImageRgba8(img),
+            Some(label),
+            is_normal_map,
+        )
+        .expect("Unable to create texture from color!")
+    }
+
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -1,7 +1,8 @@
 use wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource};
 
-use crate::{texture::TextureWrapper, RenderTarget};
+use crate::{texture::TextureWrapper, DrawTarget};
 use std::collections::HashMap;
+use std::rc::Rc;
 
 /// Sprite struct. Always contained within a TextureAtlas.
 pub struct Sprite {
@@ -37,22 +38,47 @@ impl Sprite {
 }
 
 pub struct TextureAtlas {
-    pub texture: TextureWrapper,
+    pub texture: Rc<TextureWrapper>,
     pub bind_group: BindGroup,
     pub spritemap: HashMap<String, Sprite>,
 }
 impl TextureAtlas {
-    pub fn new(
-        render_target: &RenderTarget,
+    pub fn new<T: DrawTarget>(
+        render_target: &T,
         layout: &wgpu::BindGroupLayout,
-        texture: TextureWrapper,
+        texture: Rc<TextureWrapper>,
         spritemap: HashMap<String, Sprite>,
     ) -> Self {
         // let texture = texture::Texture::load(&render_target.device, &render_target.queue, "assets/textures/atlas_0.png", false).expect("Unable to create texture atlas!");
         // let texture = texture::Texture::from_bytes(&render_target.device, &render_target.queue, bytes, "2D Texture Atlas", false).expect("Unable to create texture atlas!");
 
-        let bind_group = render_target
-            .device
+        let bind_group = Self::create_bind_group(render_target, layout, &texture);
+
+        Self {
+            texture,
+            bind_group,
+            spritemap,
+        }
+    }
+
+    /// Swaps in a new texture, keeping the sprite map. Used when the underlying texture asset is replaced.
+    pub fn set_texture<T: DrawTarget>(
+        &mut self,
+        render_target: &T,
+        layout: &wgpu::BindGroupLayout,
+        texture: Rc<TextureWrapper>,
+    ) {
+        self.bind_group = Self::create_bind_group(render_target, layout, &texture);
+        self.texture = texture;
+    }
+
+    fn create_bind_group<T: DrawTarget>(
+        render_target: &T,
+        layout: &wgpu::BindGroupLayout,
+        texture: &TextureWrapper,
+    ) -> BindGroup {
+        render_target
+            .device()
             .create_bind_group(&BindGroupDescriptor {
                 label: Some("2D Texture Atlas Bind Group"),
                 layout,
@@ -66,13 +92,7 @@ impl TextureAtlas {
                         resource: BindingResource::Sampler(&texture.sampler),
                     },
                 ],
-            });
-
-        Self {
-            texture,
-            bind_group,
-            spritemap,
-        }
+            })
     }
 }
 
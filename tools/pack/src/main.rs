@@ -74,6 +74,10 @@ struct Args {
     #[arg(long)]
     zip: bool,
 
+    /// Bake profile for assets serialized during the build.
+    #[arg(long, default_value = "web")]
+    profile: String,
+
     // /// Arguments to be passed to wasm-pack.
     // #[arg(long,short)]
     // wasm_pack_args: Option<String>,
@@ -118,6 +122,9 @@ fn build<'a>(args: &Args) -> Result<(), BuildError> {
         println!("To enable development builds, pass '--dev'");
     }
 
+    // Read by sundile_assets::Serializer in the crate's build script.
+    cmd.env("SUNDILE_BAKE_PROFILE", &args.profile);
+
     cmd.arg(args.target_directory.clone())
         .args(args.cargo_args.clone());
 
@@ -25,9 +25,21 @@ struct Args {
     /// Compile fonts
     #[arg(short, long)]
     fonts: bool,
-    /// Compile all compatible types.
+    /// Compile every registered type.
     #[arg(short, long)]
     all: bool,
+    /// Number of threads to bake with. Defaults to one per core.
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+    /// Bake profile to use: `desktop` or `web`. Defaults to $SUNDILE_BAKE_PROFILE, if set.
+    #[arg(short, long)]
+    profile: Option<String>,
+    /// Lint rules file (.ron, .json or .toml) to check assets against before baking.
+    #[arg(short, long)]
+    lint: Option<String>,
+    /// Path to write the lint report to, as JSON.
+    #[arg(long, requires = "lint")]
+    lint_report: Option<String>,
 }
 
 fn main() {
@@ -42,18 +54,45 @@ fn main() {
     let mut ser = sundile_assets::Serializer::new()
         .with_asset_directory(args.in_path)
         .with_out_path(args.out_path);
+    if let Some(threads) = args.threads {
+        ser = ser.with_threads(threads);
+    }
+    if let Some(path) = args.lint {
+        match types::data::load_file::<sundile_assets::LintRules>(path.as_ref()) {
+            Ok(rules) => ser = ser.with_lint(rules),
+            Err(e) => {
+                eprintln!("Unable to read lint rules. {}", e);
+                exit(1);
+            }
+        }
+    }
+    if let Some(path) = args.lint_report {
+        ser = ser.with_lint_report(path);
+    }
+    if let Some(name) = args.profile {
+        match sundile_assets::BakeProfile::named(&name) {
+            Some(profile) => ser = ser.with_profile(profile),
+            None => {
+                eprintln!("Unknown profile `{}`. Use `desktop` or `web`.", name);
+                exit(1);
+            }
+        }
+    }
 
-    if args.shaders || args.all {
-        ser = ser.with_mapper("shaders", types::shaders::Mapper::new());
+    if args.all {
+        ser = ser.with_registered_mappers();
+    }
+    if args.shaders {
+        ser = ser.with_mapper(types::shaders::Mapper::new());
     }
-    if args.models || args.all {
-        ser = ser.with_mapper("models", types::models::Mapper::new());
+    if args.models {
+        ser = ser.with_mapper(types::models::Mapper::new());
     }
-    if args.textures || args.all {
-        ser = ser.with_mapper("textures", types::textures::Mapper::new());
+    if args.textures {
+        ser = ser.with_mapper(types::textures::Mapper::new());
     }
-    if args.fonts || args.all {
-        ser = ser.with_mapper("fonts", types::fonts::Mapper::new());
+    if args.fonts {
+        ser = ser.with_mapper(types::fonts::Mapper::new());
     }
 
     ser.serialize();